@@ -9,22 +9,30 @@ var<uniform> matrices: Matrices;
 @group(1) @binding(0)
 var<uniform> color: vec3<f32>;
 
+#include "fog.wgsl"
+
+@group(2) @binding(0)
+var<uniform> fog: Fog;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
 }
 
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = matrices.view_proj * matrices.world * vec4<f32>(in.position, 1.0);
+    let world_pos = matrices.world * vec4<f32>(in.position, 1.0);
+    out.world_pos = world_pos.xyz;
+    out.clip_position = matrices.view_proj * world_pos;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(color, 1.0);
+    return vec4<f32>(apply_fog(fog, color, in.world_pos), 1.0);
 }
@@ -0,0 +1,58 @@
+// Included by the shaders that fog what they draw, each binds its own `Fog` uniform.
+
+struct Fog {
+    color: vec3<f32>,
+    // 0 - off, 1 - linear, 2 - exponential
+    mode: u32,
+    camera_pos: vec3<f32>,
+    start: f32,
+    end: f32,
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+}
+
+fn apply_fog(params: Fog, color: vec3<f32>, world_pos: vec3<f32>) -> vec3<f32> {
+    if params.mode == 0u {
+        return color;
+    }
+    let to_pos = world_pos - params.camera_pos;
+    let dist = length(to_pos);
+    if params.mode == 1u {
+        let amount = clamp((dist - params.start) / max(params.end - params.start, 0.0001), 0.0, 1.0);
+        return mix(color, params.color, amount);
+    }
+
+    // Density integrated along the view ray, falling off exponentially with height
+    let density_at_camera = params.density * exp(-params.height_falloff * (params.camera_pos.y - params.base_height));
+    let dy = params.height_falloff * to_pos.y;
+    var thickness = density_at_camera * dist;
+    if abs(dy) > 0.0001 {
+        thickness *= (1.0 - exp(-dy)) / dy;
+    }
+    let amount = 1.0 - exp(-max(thickness, 0.0));
+    return mix(color, params.color, amount);
+}
+
+// Over linear fog the sky is fully fogged at the horizon and clears up to this height of the
+// view direction.
+const LINEAR_SKY_FOG_HEIGHT: f32 = 0.15;
+
+// The sky is infinitely far away, so it's fully fogged wherever the fog doesn't thin out along
+// the view direction, e.g. at and below the horizon. Otherwise distant objects stand out against
+// it as solid fog-coloured silhouettes.
+fn apply_sky_fog(params: Fog, color: vec3<f32>, dir: vec3<f32>) -> vec3<f32> {
+    if params.mode == 0u {
+        return color;
+    }
+    if params.mode == 1u {
+        let amount = 1.0 - smoothstep(0.0, LINEAR_SKY_FOG_HEIGHT, dir.y);
+        return mix(color, params.color, amount);
+    }
+
+    // The height fog's density integrated along the whole view ray
+    let density_at_camera = params.density * exp(-params.height_falloff * (params.camera_pos.y - params.base_height));
+    let thickness = density_at_camera / max(params.height_falloff * dir.y, 0.0001);
+    let amount = 1.0 - exp(-thickness);
+    return mix(color, params.color, amount);
+}
@@ -0,0 +1,25 @@
+struct Matrices {
+    world: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> matrices: Matrices;
+
+// Entity bits, low and high halves
+@group(1) @binding(0)
+var<uniform> id: vec2<u32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return matrices.view_proj * matrices.world * vec4<f32>(in.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec2<u32> {
+    return id;
+}
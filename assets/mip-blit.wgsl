@@ -0,0 +1,32 @@
+// Down-samples the previous mip into the next one, relies on the sampler's linear filtering.
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+// Full-screen triangle, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coords = uv;
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(texture, texSampler, in.tex_coords);
+}
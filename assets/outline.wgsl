@@ -0,0 +1,43 @@
+struct Matrices {
+    world: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> matrices: Matrices;
+
+struct Outline {
+    color: vec3<f32>,
+    // In pixels, 0 when only marking the object
+    width: f32,
+    // Of the viewport being rendered into, in pixels
+    viewport_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> outline: Outline;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+// Vertices are pushed away from the object's origin on the screen by the outline width. Works
+// best for convex meshes centered around the origin, with no gaps at hard edges unlike pushing
+// along the normals.
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    let world_to_clip = matrices.view_proj * matrices.world;
+    var pos = world_to_clip * vec4<f32>(in.position, 1.0);
+    let center = world_to_clip * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    let dir = (pos.xy / pos.w - center.xy / center.w) * outline.viewport_size;
+    if outline.width > 0.0 && length(dir) > 0.0001 {
+        let offset = normalize(dir) * outline.width * 2.0 / outline.viewport_size;
+        pos = vec4<f32>(pos.xy + offset * pos.w, pos.zw);
+    }
+    return pos;
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(outline.color, 1.0);
+}
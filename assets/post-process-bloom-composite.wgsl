@@ -0,0 +1,56 @@
+struct VertexInput {
+    @location(0)
+    position: vec3<f32>,
+
+    @location(1)
+    tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    // Intentionally inverting the coordinates, they're messed
+    out.tex_coords = vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(2) @binding(0)
+var extraTexture: texture_2d<f32>;
+
+@group(2) @binding(1)
+var extraSampler: sampler;
+
+// Adds the blurred bright parts (extra input) to the image.
+// values.z - intensity
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let src = textureSample(texture, texSampler, in.tex_coords);
+    let bloom = textureSample(extraTexture, extraSampler, in.tex_coords).rgb;
+    return vec4<f32>(src.rgb + bloom * params.values.z, src.a);
+}
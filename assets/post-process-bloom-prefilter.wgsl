@@ -0,0 +1,66 @@
+struct VertexInput {
+    @location(0)
+    position: vec3<f32>,
+
+    @location(1)
+    tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    // Intentionally inverting the coordinates, they're messed
+    out.tex_coords = vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+// Each bilinear tap averages a 2x2 block, so four of them cover a 4x4 block of the source.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let ts = params.texel_size;
+    return 0.25 * (
+        textureSample(texture, texSampler, uv + vec2<f32>(-1.0, -1.0) * ts).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(1.0, -1.0) * ts).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(-1.0, 1.0) * ts).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(1.0, 1.0) * ts).rgb);
+}
+
+// values.x - threshold, values.y - soft knee
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = downsample(in.tex_coords);
+    let threshold = params.values.x;
+    let knee = params.values.y;
+
+    let brightness = max(c.r, max(c.g, c.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.00001);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+
+    return vec4<f32>(c * contribution, 1.0);
+}
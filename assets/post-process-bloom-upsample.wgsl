@@ -0,0 +1,68 @@
+struct VertexInput {
+    @location(0)
+    position: vec3<f32>,
+
+    @location(1)
+    tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    // Intentionally inverting the coordinates, they're messed
+    out.tex_coords = vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(2) @binding(0)
+var extraTexture: texture_2d<f32>;
+
+@group(2) @binding(1)
+var extraSampler: sampler;
+
+// Upsamples the lower mip (main input) with a 3x3 tent filter and adds it to the current mip (extra input).
+// values.w - filter radius in texels of the lower mip
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let d = params.texel_size * params.values.w;
+
+    var c = textureSample(texture, texSampler, uv).rgb * 4.0;
+    c += (textureSample(texture, texSampler, uv + vec2<f32>(-d.x, 0.0)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(d.x, 0.0)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(0.0, -d.y)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(0.0, d.y)).rgb) * 2.0;
+    c += textureSample(texture, texSampler, uv + vec2<f32>(-d.x, -d.y)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(d.x, -d.y)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(-d.x, d.y)).rgb +
+        textureSample(texture, texSampler, uv + vec2<f32>(d.x, d.y)).rgb;
+
+    let current = textureSample(extraTexture, extraSampler, uv).rgb;
+    return vec4<f32>(current + c / 16.0, 1.0);
+}
@@ -0,0 +1,78 @@
+struct VertexInput {
+    @location(0)
+    position: vec3<f32>,
+
+    @location(1)
+    tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    // Intentionally inverting the coordinates, they're messed
+    out.tex_coords = vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+// LUT is a horizontal strip of LUT_SIZE slices, each LUT_SIZE x LUT_SIZE texels, blue growing along the strip.
+const LUT_SIZE: i32 = 16;
+
+@group(2) @binding(0)
+var lut: texture_2d<f32>;
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let lo = c * 12.92;
+    let hi = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(hi, lo, c <= vec3<f32>(0.0031308));
+}
+
+fn lut_texel(r: i32, g: i32, b: i32) -> vec3<f32> {
+    return textureLoad(lut, vec2<i32>(b * LUT_SIZE + r, g), 0).rgb;
+}
+
+// values.x - intensity
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let src = textureSample(texture, texSampler, in.tex_coords);
+
+    // LUTs are authored in display (sRGB) space. Filtering manually, the LUT texture can have any sampler.
+    let max_idx = f32(LUT_SIZE - 1);
+    let c = linear_to_srgb(clamp(src.rgb, vec3<f32>(0.0), vec3<f32>(1.0))) * max_idx;
+    let c0 = vec3<i32>(floor(c));
+    let c1 = min(c0 + vec3<i32>(1), vec3<i32>(LUT_SIZE - 1));
+    let f = fract(c);
+
+    let g00 = mix(lut_texel(c0.r, c0.g, c0.b), lut_texel(c1.r, c0.g, c0.b), f.r);
+    let g10 = mix(lut_texel(c0.r, c1.g, c0.b), lut_texel(c1.r, c1.g, c0.b), f.r);
+    let g01 = mix(lut_texel(c0.r, c0.g, c1.b), lut_texel(c1.r, c0.g, c1.b), f.r);
+    let g11 = mix(lut_texel(c0.r, c1.g, c1.b), lut_texel(c1.r, c1.g, c1.b), f.r);
+    let graded = mix(mix(g00, g10, f.g), mix(g01, g11, f.g), f.b);
+
+    return vec4<f32>(mix(src.rgb, graded, params.values.x), src.a);
+}
@@ -31,9 +31,16 @@ var texture: texture_2d<f32>;
 @group(0) @binding(1)
 var texSampler: sampler;
 
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Basic vignetting
-    var m = max(0.0, 1.0 - length(in.tex_coords - vec2<f32>(0.5, 0.5)) / 1.0);
-    return textureSample(texture, texSampler, in.tex_coords) * m;
+    return textureSample(texture, texSampler, in.tex_coords);
 }
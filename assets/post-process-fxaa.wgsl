@@ -0,0 +1,83 @@
+struct VertexInput {
+    @location(0)
+    position: vec3<f32>,
+
+    @location(1)
+    tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    // Intentionally inverting the coordinates, they're messed
+    out.tex_coords = vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var texSampler: sampler;
+
+struct Params {
+    // Effect-specific values
+    values: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+// Based on the classic "FXAA 2" implementation by Timothy Lottes.
+// values.x - max search span in texels
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let ts = params.texel_size;
+
+    let src = textureSample(texture, texSampler, uv);
+    let luma_nw = luma(textureSample(texture, texSampler, uv + vec2<f32>(-1.0, -1.0) * ts).rgb);
+    let luma_ne = luma(textureSample(texture, texSampler, uv + vec2<f32>(1.0, -1.0) * ts).rgb);
+    let luma_sw = luma(textureSample(texture, texSampler, uv + vec2<f32>(-1.0, 1.0) * ts).rgb);
+    let luma_se = luma(textureSample(texture, texSampler, uv + vec2<f32>(1.0, 1.0) * ts).rgb);
+    let luma_m = luma(src.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    let span = params.values.x;
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-span), vec2<f32>(span)) * ts;
+
+    let rgb_a = 0.5 * (
+        textureSample(texture, texSampler, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(texture, texSampler, uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        textureSample(texture, texSampler, uv + dir * -0.5).rgb +
+        textureSample(texture, texSampler, uv + dir * 0.5).rgb);
+    let luma_b = luma(rgb_b);
+
+    let rgb = select(rgb_b, rgb_a, luma_b < luma_min || luma_b > luma_max);
+    return vec4<f32>(rgb, src.a);
+}
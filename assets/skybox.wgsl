@@ -40,7 +40,124 @@ var cubeTexture: texture_cube<f32>;
 @group(1) @binding(1)
 var cubeSampler: sampler;
 
+struct Sky {
+    zenith: vec4<f32>,
+    horizon: vec4<f32>,
+    ground: vec4<f32>,
+    // w - intensity
+    sun: vec4<f32>,
+    // Rayleigh and Mie multipliers, Mie g
+    scattering: vec4<f32>,
+    // 0 - cubemap, 1 - gradient, 2 - atmosphere
+    mode: u32,
+}
+
+@group(2) @binding(0)
+var<uniform> sky: Sky;
+
+#include "fog.wgsl"
+
+@group(3) @binding(0)
+var<uniform> fog: Fog;
+
+fn gradient(dir: vec3<f32>) -> vec3<f32> {
+    let h = dir.y;
+    if h >= 0.0 {
+        return mix(sky.horizon.rgb, sky.zenith.rgb, sqrt(h));
+    }
+    return mix(sky.horizon.rgb, sky.ground.rgb, sqrt(-h));
+}
+
+const PI: f32 = 3.14159265;
+const PLANET_RADIUS: f32 = 6371e3;
+const ATMOSPHERE_RADIUS: f32 = 6471e3;
+const RAYLEIGH_COEFF: vec3<f32> = vec3<f32>(5.5e-6, 13.0e-6, 22.4e-6);
+const MIE_COEFF: f32 = 21e-6;
+const RAYLEIGH_HEIGHT: f32 = 8e3;
+const MIE_HEIGHT: f32 = 1.2e3;
+const PRIMARY_STEPS: i32 = 16;
+const LIGHT_STEPS: i32 = 8;
+
+// Distance to the far intersection with a sphere at the origin, negative if missed
+fn ray_sphere_exit(origin: vec3<f32>, dir: vec3<f32>, radius: f32) -> f32 {
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - radius * radius;
+    let d = b * b - c;
+    if d < 0.0 {
+        return -1.0;
+    }
+    return -b + sqrt(d);
+}
+
+// Single scattering, marching from the viewer standing on the ground
+fn atmosphere(dir: vec3<f32>) -> vec3<f32> {
+    let sun_dir = normalize(sky.sun.xyz);
+    let rayleigh_coeff = RAYLEIGH_COEFF * sky.scattering.x;
+    let mie_coeff = MIE_COEFF * sky.scattering.y;
+    let g = sky.scattering.z;
+
+    let origin = vec3<f32>(0.0, PLANET_RADIUS + 1.0, 0.0);
+    // Below the horizon the view ray is stopped by the ground
+    let view_dir = normalize(vec3<f32>(dir.x, max(dir.y, 0.01), dir.z));
+    let ray_length = ray_sphere_exit(origin, view_dir, ATMOSPHERE_RADIUS);
+    let step = ray_length / f32(PRIMARY_STEPS);
+
+    var rayleigh = vec3<f32>(0.0);
+    var mie = vec3<f32>(0.0);
+    var depth_rayleigh = 0.0;
+    var depth_mie = 0.0;
+
+    for (var i = 0; i < PRIMARY_STEPS; i++) {
+        let pos = origin + view_dir * (f32(i) + 0.5) * step;
+        let height = length(pos) - PLANET_RADIUS;
+        let density_rayleigh = exp(-height / RAYLEIGH_HEIGHT) * step;
+        let density_mie = exp(-height / MIE_HEIGHT) * step;
+        depth_rayleigh += density_rayleigh;
+        depth_mie += density_mie;
+
+        let light_length = ray_sphere_exit(pos, sun_dir, ATMOSPHERE_RADIUS);
+        let light_step = light_length / f32(LIGHT_STEPS);
+        var light_depth_rayleigh = 0.0;
+        var light_depth_mie = 0.0;
+        for (var j = 0; j < LIGHT_STEPS; j++) {
+            let light_pos = pos + sun_dir * (f32(j) + 0.5) * light_step;
+            let light_height = length(light_pos) - PLANET_RADIUS;
+            light_depth_rayleigh += exp(-light_height / RAYLEIGH_HEIGHT) * light_step;
+            light_depth_mie += exp(-light_height / MIE_HEIGHT) * light_step;
+        }
+
+        let attenuation = exp(
+            -(rayleigh_coeff * (depth_rayleigh + light_depth_rayleigh)
+                + mie_coeff * (depth_mie + light_depth_mie))
+        );
+        rayleigh += density_rayleigh * attenuation;
+        mie += density_mie * attenuation;
+    }
+
+    let mu = dot(view_dir, sun_dir);
+    let phase_rayleigh = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let gg = g * g;
+    let phase_mie = 3.0 / (8.0 * PI) * ((1.0 - gg) * (1.0 + mu * mu))
+        / ((2.0 + gg) * pow(1.0 + gg - 2.0 * mu * g, 1.5));
+
+    var color = sky.sun.w * (phase_rayleigh * rayleigh_coeff * rayleigh + phase_mie * mie_coeff * mie);
+    // Darken the ground so the horizon stays visible
+    if dir.y < 0.0 {
+        color *= mix(1.0, 0.3, clamp(-dir.y * 4.0, 0.0, 1.0));
+    }
+    return color;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(cubeTexture, cubeSampler, in.uv);
+    let dir = normalize(in.uv);
+    var color: vec4<f32>;
+    if sky.mode == 1u {
+        color = vec4<f32>(gradient(dir), 1.0);
+    } else if sky.mode == 2u {
+        color = vec4<f32>(atmosphere(dir), 1.0);
+    } else {
+        color = textureSample(cubeTexture, cubeSampler, in.uv);
+    }
+    return vec4<f32>(apply_sky_fog(fog, color.rgb, dir), color.a);
 }
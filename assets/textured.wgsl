@@ -6,6 +6,14 @@ struct Matrices {
 @group(1) @binding(0)
 var<uniform> matrices: Matrices;
 
+@group(2) @binding(0)
+var<uniform> uv_scale: vec2<f32>;
+
+#include "fog.wgsl"
+
+@group(3) @binding(0)
+var<uniform> fog: Fog;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
@@ -14,13 +22,16 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_pos: vec3<f32>,
 }
 
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.tex_coords = in.tex_coords;
-    out.clip_position = matrices.view_proj * matrices.world * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords * uv_scale;
+    let world_pos = matrices.world * vec4<f32>(in.position, 1.0);
+    out.world_pos = world_pos.xyz;
+    out.clip_position = matrices.view_proj * world_pos;
     return out;
 }
 
@@ -32,5 +43,6 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(apply_fog(fog, color.rgb, in.world_pos), color.a);
 }
@@ -6,15 +6,66 @@ use winit::event::{DeviceEvent, DeviceId, Event, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 
+use crate::file;
 use crate::frame_time::FrameTime;
-use crate::input::{Input, InputAction};
+use crate::input::{Bindings, Input, InputAction, Playback, Recording};
 use crate::render::{Renderer, SurfaceSize};
 use crate::scene::Scene;
-use crate::scene::{Assets, SceneCfg};
+use crate::scene::{Assets, PlayerSettings, SceneCfg};
 use crate::state::State;
 
+pub const BINDINGS_FILE: &str = "bindings.yml";
+pub const USER_BINDINGS_FILE: &str = "bindings.user.yml";
+pub const SETTINGS_FILE: &str = "settings.yml";
+// Saved from the HUD and layered over the shipped files, which so keep their comments
+pub const USER_SETTINGS_FILE: &str = "settings.user.yml";
+
+pub struct AppOptions {
+    // Input is recorded into this file, saved on exit
+    pub record_path: Option<String>,
+    // Input recorded into this file is replayed instead of the real one
+    pub play_path: Option<String>,
+    // Recorded frames per rendered frame, for fast-forwarding. Live input is ignored during the
+    // playback, so it can't be changed from the HUD.
+    pub play_speed: u32,
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            record_path: None,
+            play_path: None,
+            play_speed: 1,
+        }
+    }
+}
+
+impl AppOptions {
+    // `--record <file>` and/or `--play <file>` with optional `--speed <frames>`
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value after {arg}"))
+            };
+            match arg.as_str() {
+                "--record" => options.record_path = Some(value()?),
+                "--play" => options.play_path = Some(value()?),
+                "--speed" => {
+                    options.play_speed = value()?.parse()?;
+                    anyhow::ensure!(options.play_speed > 0, "Playback speed must be positive");
+                }
+                _ => anyhow::bail!("Unknown argument {arg}"),
+            }
+        }
+        Ok(options)
+    }
+}
+
 #[derive(Default)]
 pub struct App<'a> {
+    options: AppOptions,
     state: Option<State<'a>>,
     assets: Option<Assets>,
     scene: Option<Scene>,
@@ -23,21 +74,58 @@ pub struct App<'a> {
 }
 
 impl App<'_> {
+    pub fn new(options: AppOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
     fn update_and_render(&mut self, event_loop: &ActiveEventLoop) {
         // TODO Avoid this ugliness.
         let mut state = self.state.take().unwrap();
         let mut scene = self.scene.take().unwrap();
         let mut assets = self.assets.take().unwrap();
 
-        if state.input.action_activated(InputAction::Quit) {
-            event_loop.exit();
-        }
+        state.input.poll_gamepads();
 
         let dt = self.frame_time.as_mut().unwrap().advance();
 
         state.renderer.update(self.new_canvas_size);
-        scene.update(dt, &state, &mut assets, &self.new_canvas_size);
+
+        // The UI must get the recorded time as well to do the same thing
+        let mut ui_dt = dt;
+        if let Some(playback) = state.input.playback() {
+            let mut played_dt = 0.0;
+            // Several recorded frames per rendered one when fast-forwarding
+            for step in 0..playback.speed {
+                if step > 0 {
+                    if !state.input.playback().is_some_and(Playback::can_batch_next) {
+                        break;
+                    }
+                    state.input.clear_step();
+                }
+                let Some(recorded_dt) = state.input.play_frame() else {
+                    break;
+                };
+                scene.update(recorded_dt, &mut state, &mut assets);
+                played_dt += recorded_dt;
+            }
+            // Zero once the playback is over, the UI needs some
+            if played_dt > 0.0 {
+                ui_dt = played_dt;
+            }
+        } else {
+            if state.input.action_activated(InputAction::Quit) {
+                event_loop.exit();
+            }
+            scene.update(dt, &mut state, &mut assets);
+            state.input.record_frame(dt);
+        }
+
+        scene.prepare_frame(ui_dt, &mut state, &mut assets, &self.new_canvas_size);
         scene.render(&state.renderer, &assets);
+        state.renderer.present();
 
         state.input.clear();
         state.window.request_redraw();
@@ -49,6 +137,66 @@ impl App<'_> {
     }
 }
 
+// Defaults if the file is missing or broken
+pub fn shipped_bindings() -> Bindings {
+    match future::block_on(file::read_string_asset(BINDINGS_FILE)) {
+        Ok(yaml) => Bindings::from_yaml(&yaml).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {BINDINGS_FILE}: {e}");
+            Bindings::default()
+        }),
+        Err(_) => Bindings::default(),
+    }
+}
+
+fn load_bindings() -> Bindings {
+    let bindings = shipped_bindings();
+    match future::block_on(file::read_string_asset(USER_BINDINGS_FILE)) {
+        Ok(yaml) => bindings.clone().with_overrides(&yaml).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {USER_BINDINGS_FILE}: {e}");
+            bindings
+        }),
+        Err(_) => bindings,
+    }
+}
+
+// Only the actions bound differently than shipped are saved, like with `save_settings`.
+pub fn save_bindings(bindings: &Bindings) {
+    let yaml = bindings.overrides_yaml(&shipped_bindings());
+    if let Err(e) = future::block_on(file::write_string_asset(USER_BINDINGS_FILE, &yaml)) {
+        eprintln!("Failed to save {USER_BINDINGS_FILE}: {e}");
+    }
+}
+
+// Defaults if the file is missing or broken
+pub fn shipped_settings() -> PlayerSettings {
+    match future::block_on(file::read_string_asset(SETTINGS_FILE)) {
+        Ok(yaml) => PlayerSettings::from_yaml(&yaml).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {SETTINGS_FILE}: {e}");
+            PlayerSettings::default()
+        }),
+        Err(_) => PlayerSettings::default(),
+    }
+}
+
+fn load_settings() -> PlayerSettings {
+    let settings = shipped_settings();
+    match future::block_on(file::read_string_asset(USER_SETTINGS_FILE)) {
+        Ok(yaml) => settings.with_overrides(&yaml).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {USER_SETTINGS_FILE}: {e}");
+            settings
+        }),
+        Err(_) => settings,
+    }
+}
+
+// Only what differs from the shipped settings is saved, so later changes to them still apply.
+pub fn save_settings(settings: PlayerSettings) {
+    let yaml = settings.overrides_yaml(shipped_settings());
+    if let Err(e) = future::block_on(file::write_string_asset(USER_SETTINGS_FILE, &yaml)) {
+        eprintln!("Failed to save {USER_SETTINGS_FILE}: {e}");
+    }
+}
+
 impl ApplicationHandler for App<'_> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // This function should be re-entrant, see the docs. Exiting if already initialized.
@@ -73,13 +221,32 @@ impl ApplicationHandler for App<'_> {
         let rr = future::block_on(Renderer::new(Arc::clone(&window)));
         let mut assets = Assets::new();
 
+        let mut input = Input::new(load_bindings());
+        if let Some(path) = &self.options.play_path {
+            match Recording::load(path) {
+                Ok(recording) => {
+                    // Cursor-based picking depends on the window size
+                    let [width, height] = recording.window_size;
+                    let _ = window.request_inner_size(PhysicalSize { width, height });
+                    let mut playback = Playback::new(recording);
+                    playback.speed = self.options.play_speed;
+                    input.start_playback(playback);
+                }
+                Err(e) => eprintln!("Failed to load recording {path}: {e}"),
+            }
+        }
+        if self.options.record_path.is_some() {
+            let size = window.inner_size();
+            input.start_recording([size.width, size.height]);
+        }
+
         let state = State {
             window,
             renderer: rr,
-            input: Input::new(),
+            input,
         };
 
-        let mut scene = Scene::new(&state, &mut assets);
+        let mut scene = Scene::new(&state, &mut assets, load_settings());
         scene.insert_from_cfg(
             &SceneCfg::from_yaml(&String::from_utf8_lossy(include_bytes!(
                 "../assets/scene.yml"
@@ -118,6 +285,17 @@ impl ApplicationHandler for App<'_> {
             .handle_event(Event::WindowEvent { window_id, event });
     }
 
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let (Some(path), Some(state)) = (&self.options.record_path, &mut self.state)
+            && let Some(recording) = state.input.take_recording()
+        {
+            match recording.save(path) {
+                Ok(()) => println!("Input recorded into {path}"),
+                Err(e) => eprintln!("Failed to save recording {path}: {e}"),
+            }
+        }
+    }
+
     fn device_event(
         &mut self,
         _event_loop: &ActiveEventLoop,
@@ -13,3 +13,7 @@ pub async fn read_binary_asset(file_path: &str) -> Result<Vec<u8>> {
 pub async fn read_string_asset(file_path: &str) -> Result<String> {
     Ok(std::fs::read_to_string(full_path(file_path))?)
 }
+
+pub async fn write_string_asset(file_path: &str, contents: &str) -> Result<()> {
+    Ok(std::fs::write(full_path(file_path), contents)?)
+}
@@ -0,0 +1,511 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use super::{GamepadButton, InputAction};
+
+// Written as text, e.g. "F", "MouseLeft", "PadSouth", "WheelUp"
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Key {
+    Keyboard(KeyCode),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+    // Wheel "keys" are pressed only during the frame the wheel is scrolled
+    WheelUp,
+    WheelDown,
+}
+
+impl Key {
+    // Only keys with a name in the bindings file can be bound
+    pub fn is_bindable(&self) -> bool {
+        match self {
+            Key::Keyboard(code) => key_code_name(*code).is_some(),
+            _ => true,
+        }
+    }
+
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self,
+            Key::Keyboard(
+                KeyCode::ShiftLeft
+                    | KeyCode::ShiftRight
+                    | KeyCode::ControlLeft
+                    | KeyCode::ControlRight
+                    | KeyCode::AltLeft
+                    | KeyCode::AltRight
+                    | KeyCode::SuperLeft
+                    | KeyCode::SuperRight
+            )
+        )
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Key::Keyboard(code) => write!(f, "{}", key_code_name(code).unwrap_or("?")),
+            Key::MouseButton(MouseButton::Left) => write!(f, "MouseLeft"),
+            Key::MouseButton(MouseButton::Right) => write!(f, "MouseRight"),
+            Key::MouseButton(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Key::MouseButton(MouseButton::Back) => write!(f, "MouseBack"),
+            Key::MouseButton(MouseButton::Forward) => write!(f, "MouseForward"),
+            Key::MouseButton(MouseButton::Other(n)) => write!(f, "Mouse{n}"),
+            Key::GamepadButton(button) => write!(
+                f,
+                "{}",
+                GAMEPAD_BUTTON_NAMES
+                    .iter()
+                    .find(|&&(_, b)| b == button)
+                    .unwrap()
+                    .0
+            ),
+            Key::WheelUp => write!(f, "WheelUp"),
+            Key::WheelDown => write!(f, "WheelDown"),
+        }
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        key_from_name(&name).ok_or_else(|| anyhow!("Unknown key '{name}'"))
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key.to_string()
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    // Whether all modifiers of `other` are also in `self`.
+    pub fn contains(&self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+}
+
+// A key with modifiers that must be held along with it, e.g. Shift+F.
+// Written in the bindings file as text, e.g. "Ctrl+Shift+F", "MouseLeft", "WheelUp".
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Binding {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut parts = text.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().ok_or_else(|| anyhow!("Empty binding"))?;
+        for part in parts {
+            match part {
+                "Ctrl" => modifiers.ctrl = true,
+                "Shift" => modifiers.shift = true,
+                "Alt" => modifiers.alt = true,
+                _ => return Err(anyhow!("Unknown modifier '{part}' in '{text}'")),
+            }
+        }
+        let key = key_from_name(key).ok_or_else(|| anyhow!("Unknown key '{key}' in '{text}'"))?;
+        Ok(Self { key, modifiers })
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        Self::parse(&text)
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+// Any number of bindings per action
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings(BTreeMap<InputAction, Vec<Binding>>);
+
+impl Bindings {
+    // Actions missing in the file keep their default bindings.
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        Self::default().with_overrides(yaml)
+    }
+
+    // Actions in `yaml` replace their bindings in `self`, e.g. the user's over the shipped file.
+    pub fn with_overrides(mut self, yaml: &str) -> Result<Self> {
+        let loaded: BTreeMap<InputAction, Vec<Binding>> = serde_yaml::from_str(yaml)?;
+        self.0.extend(loaded);
+        Ok(self)
+    }
+
+    // Only the actions bound differently than in `base`, see `with_overrides`.
+    pub fn overrides_yaml(&self, base: &Bindings) -> String {
+        let changed: BTreeMap<_, _> = self
+            .0
+            .iter()
+            .filter(|&(action, bindings)| base.0.get(action) != Some(bindings))
+            .collect();
+        serde_yaml::to_string(&changed).unwrap()
+    }
+
+    pub fn get(&self, action: InputAction) -> &[Binding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Binding> {
+        self.0.values().flatten()
+    }
+
+    pub fn add(&mut self, action: InputAction, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn replace(&mut self, action: InputAction, index: usize, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        // Avoid duplicates when replacing with a binding the action already has
+        match bindings.iter().position(|&b| b == binding) {
+            Some(existing) if existing != index => {
+                bindings.remove(index);
+            }
+            Some(_) => (),
+            None => bindings[index] = binding,
+        }
+    }
+
+    pub fn remove(&mut self, action: InputAction, index: usize) {
+        self.0.entry(action).or_default().remove(index);
+    }
+
+    // Bindings shared by several actions, with those actions.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<InputAction>)> {
+        let mut actions_by_binding = BTreeMap::<String, (Binding, Vec<InputAction>)>::new();
+        for (&action, bindings) in &self.0 {
+            for &binding in bindings {
+                actions_by_binding
+                    .entry(binding.to_string())
+                    .or_insert((binding, Vec::new()))
+                    .1
+                    .push(action);
+            }
+        }
+        actions_by_binding
+            .into_values()
+            .filter(|(_, actions)| actions.len() > 1)
+            .collect()
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let key = |code| Binding::new(Key::Keyboard(code));
+        let pad = |button| Binding::new(Key::GamepadButton(button));
+        Self(BTreeMap::from([
+            (InputAction::MoveForward, vec![key(KeyCode::KeyW)]),
+            (InputAction::MoveBack, vec![key(KeyCode::KeyS)]),
+            (InputAction::MoveLeft, vec![key(KeyCode::KeyA)]),
+            (InputAction::MoveRight, vec![key(KeyCode::KeyD)]),
+            (InputAction::MoveUp, vec![key(KeyCode::KeyE)]),
+            (InputAction::MoveDown, vec![key(KeyCode::KeyQ)]),
+            (InputAction::Quit, vec![key(KeyCode::Escape)]),
+            (
+                InputAction::ControlPlayer,
+                vec![key(KeyCode::Tab), pad(GamepadButton::Start)],
+            ),
+            (
+                InputAction::Spawn,
+                vec![key(KeyCode::KeyF), pad(GamepadButton::West)],
+            ),
+            (
+                InputAction::Grab,
+                vec![
+                    Binding::new(Key::MouseButton(MouseButton::Left)),
+                    pad(GamepadButton::South),
+                ],
+            ),
+            (
+                InputAction::ZoomIn,
+                vec![key(KeyCode::Equal), pad(GamepadButton::DPadUp)],
+            ),
+            (
+                InputAction::ZoomOut,
+                vec![key(KeyCode::Minus), pad(GamepadButton::DPadDown)],
+            ),
+            (
+                InputAction::ToggleWalking,
+                vec![key(KeyCode::KeyV), pad(GamepadButton::North)],
+            ),
+            (
+                InputAction::Jump,
+                vec![key(KeyCode::Space), pad(GamepadButton::East)],
+            ),
+            (
+                InputAction::Crouch,
+                vec![key(KeyCode::KeyC), pad(GamepadButton::RightStick)],
+            ),
+            (
+                InputAction::SwitchCamera,
+                vec![key(KeyCode::F5), pad(GamepadButton::Select)],
+            ),
+            (
+                InputAction::Sprint,
+                vec![key(KeyCode::ShiftLeft), pad(GamepadButton::LeftStick)],
+            ),
+            (
+                InputAction::Slow,
+                vec![key(KeyCode::KeyZ), pad(GamepadButton::LeftBumper)],
+            ),
+            (
+                InputAction::RotateGrabbed,
+                vec![
+                    Binding::new(Key::MouseButton(MouseButton::Right)),
+                    pad(GamepadButton::RightBumper),
+                ],
+            ),
+            (
+                InputAction::Select,
+                vec![Binding {
+                    key: Key::MouseButton(MouseButton::Left),
+                    modifiers: Modifiers {
+                        ctrl: true,
+                        ..Default::default()
+                    },
+                }],
+            ),
+            (
+                InputAction::AddToSelection,
+                vec![Binding {
+                    key: Key::MouseButton(MouseButton::Left),
+                    modifiers: Modifiers {
+                        shift: true,
+                        ..Default::default()
+                    },
+                }],
+            ),
+            (InputAction::DeleteSelected, vec![key(KeyCode::Delete)]),
+            (InputAction::DuplicateSelected, vec![key(KeyCode::Insert)]),
+            (
+                InputAction::FreezeSelected,
+                vec![Binding {
+                    key: Key::Keyboard(KeyCode::KeyF),
+                    modifiers: Modifiers {
+                        ctrl: true,
+                        ..Default::default()
+                    },
+                }],
+            ),
+            (InputAction::SwitchGizmo, vec![key(KeyCode::KeyG)]),
+        ]))
+    }
+}
+
+const GAMEPAD_BUTTON_NAMES: &[(&str, GamepadButton)] = &[
+    ("PadSouth", GamepadButton::South),
+    ("PadEast", GamepadButton::East),
+    ("PadNorth", GamepadButton::North),
+    ("PadWest", GamepadButton::West),
+    ("PadLB", GamepadButton::LeftBumper),
+    ("PadRB", GamepadButton::RightBumper),
+    ("PadSelect", GamepadButton::Select),
+    ("PadStart", GamepadButton::Start),
+    ("PadUp", GamepadButton::DPadUp),
+    ("PadDown", GamepadButton::DPadDown),
+    ("PadLeft", GamepadButton::DPadLeft),
+    ("PadRight", GamepadButton::DPadRight),
+    ("PadLStick", GamepadButton::LeftStick),
+    ("PadRStick", GamepadButton::RightStick),
+];
+
+// Keys that can be bound. Others are ignored when capturing a new binding.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("A", KeyCode::KeyA),
+    ("B", KeyCode::KeyB),
+    ("C", KeyCode::KeyC),
+    ("D", KeyCode::KeyD),
+    ("E", KeyCode::KeyE),
+    ("F", KeyCode::KeyF),
+    ("G", KeyCode::KeyG),
+    ("H", KeyCode::KeyH),
+    ("I", KeyCode::KeyI),
+    ("J", KeyCode::KeyJ),
+    ("K", KeyCode::KeyK),
+    ("L", KeyCode::KeyL),
+    ("M", KeyCode::KeyM),
+    ("N", KeyCode::KeyN),
+    ("O", KeyCode::KeyO),
+    ("P", KeyCode::KeyP),
+    ("Q", KeyCode::KeyQ),
+    ("R", KeyCode::KeyR),
+    ("S", KeyCode::KeyS),
+    ("T", KeyCode::KeyT),
+    ("U", KeyCode::KeyU),
+    ("V", KeyCode::KeyV),
+    ("W", KeyCode::KeyW),
+    ("X", KeyCode::KeyX),
+    ("Y", KeyCode::KeyY),
+    ("Z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0),
+    ("1", KeyCode::Digit1),
+    ("2", KeyCode::Digit2),
+    ("3", KeyCode::Digit3),
+    ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5),
+    ("6", KeyCode::Digit6),
+    ("7", KeyCode::Digit7),
+    ("8", KeyCode::Digit8),
+    ("9", KeyCode::Digit9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Escape", KeyCode::Escape),
+    ("Tab", KeyCode::Tab),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Up", KeyCode::ArrowUp),
+    ("Down", KeyCode::ArrowDown),
+    ("Left", KeyCode::ArrowLeft),
+    ("Right", KeyCode::ArrowRight),
+    ("Minus", KeyCode::Minus),
+    ("Equal", KeyCode::Equal),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("Backslash", KeyCode::Backslash),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Quote", KeyCode::Quote),
+    ("Backquote", KeyCode::Backquote),
+    ("BracketLeft", KeyCode::BracketLeft),
+    ("BracketRight", KeyCode::BracketRight),
+    ("ShiftLeft", KeyCode::ShiftLeft),
+    ("ShiftRight", KeyCode::ShiftRight),
+    ("ControlLeft", KeyCode::ControlLeft),
+    ("ControlRight", KeyCode::ControlRight),
+    ("AltLeft", KeyCode::AltLeft),
+    ("AltRight", KeyCode::AltRight),
+];
+
+fn key_code_name(code: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, c)| c == code)
+        .map(|&(name, _)| name)
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    let button = match name {
+        "MouseLeft" => Some(MouseButton::Left),
+        "MouseRight" => Some(MouseButton::Right),
+        "MouseMiddle" => Some(MouseButton::Middle),
+        "MouseBack" => Some(MouseButton::Back),
+        "MouseForward" => Some(MouseButton::Forward),
+        _ => name
+            .strip_prefix("Mouse")
+            .and_then(|n| n.parse().ok())
+            .map(MouseButton::Other),
+    };
+    if let Some(button) = button {
+        return Some(Key::MouseButton(button));
+    }
+
+    match name {
+        "WheelUp" => Some(Key::WheelUp),
+        "WheelDown" => Some(Key::WheelDown),
+        _ => KEY_NAMES
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, code)| Key::Keyboard(code))
+            .or_else(|| {
+                GAMEPAD_BUTTON_NAMES
+                    .iter()
+                    .find(|&&(n, _)| n == name)
+                    .map(|&(_, button)| Key::GamepadButton(button))
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let yaml = String::from_utf8_lossy(include_bytes!("../../assets/bindings.yml"));
+        let bindings = Bindings::from_yaml(&yaml).unwrap();
+        assert!(bindings.conflicts().is_empty());
+        let reloaded = Bindings::from_yaml(&serde_yaml::to_string(&bindings).unwrap()).unwrap();
+        assert_eq!(bindings.0, reloaded.0);
+
+        let mut changed = bindings.clone();
+        changed
+            .0
+            .insert(InputAction::Jump, vec![Binding::parse("J").unwrap()]);
+        let overrides = changed.overrides_yaml(&bindings);
+        assert_eq!(overrides.trim(), "Jump:\n- J");
+        let layered = bindings.with_overrides(&overrides).unwrap();
+        assert_eq!(layered.0, changed.0);
+        assert_eq!(
+            Binding::parse("Ctrl+Shift+F").unwrap().to_string(),
+            "Ctrl+Shift+F"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use super::InputEvent;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum GamepadButton {
+    // Face buttons by position, e.g. South is A on Xbox and Cross on PlayStation
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftStick,
+    RightStick,
+}
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    // -1..1, up is positive
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    // 0..1
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StickParams {
+    // Deflection ignored around the center, 0..1
+    pub dead_zone: f32,
+    // Response curve past the dead zone, 1 is linear, higher gives finer control near the center
+    pub exponent: f32,
+    pub sensitivity: f32,
+}
+
+impl Default for StickParams {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            exponent: 2.0,
+            sensitivity: 1.0,
+        }
+    }
+}
+
+impl StickParams {
+    // Radial dead zone, so that diagonals don't snap to the axes.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let len = (x * x + y * y).sqrt();
+        if len <= self.dead_zone {
+            return (0.0, 0.0);
+        }
+        let scaled = ((len - self.dead_zone) / (1.0 - self.dead_zone))
+            .min(1.0)
+            .powf(self.exponent);
+        let k = scaled * self.sensitivity / len;
+        (x * k, y * k)
+    }
+}
+
+// Gamepads are read via gilrs with the `gamepad` feature, otherwise there are none.
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = gilrs::Gilrs::new()
+            .inspect_err(|e| eprintln!("Gamepads unavailable: {e}"))
+            .ok();
+        Self { gilrs }
+    }
+
+    pub fn poll(&mut self) -> Vec<InputEvent> {
+        use super::Key;
+        use gilrs::EventType;
+
+        let Some(gilrs) = &mut self.gilrs else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            match event {
+                // Analog triggers are reported as buttons with a value
+                EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    events.push(InputEvent::GamepadAxis(GamepadAxis::LeftTrigger, value));
+                }
+                EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    events.push(InputEvent::GamepadAxis(GamepadAxis::RightTrigger, value));
+                }
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = button_from_gilrs(button) {
+                        events.push(InputEvent::Key {
+                            key: Key::GamepadButton(button),
+                            pressed: true,
+                        });
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = button_from_gilrs(button) {
+                        events.push(InputEvent::Key {
+                            key: Key::GamepadButton(button),
+                            pressed: false,
+                        });
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        gilrs::Axis::LeftStickX => Some(GamepadAxis::LeftStickX),
+                        gilrs::Axis::LeftStickY => Some(GamepadAxis::LeftStickY),
+                        gilrs::Axis::RightStickX => Some(GamepadAxis::RightStickX),
+                        gilrs::Axis::RightStickY => Some(GamepadAxis::RightStickY),
+                        _ => None,
+                    };
+                    if let Some(axis) = axis {
+                        events.push(InputEvent::GamepadAxis(axis, value));
+                    }
+                }
+                // Buttons and axes are merged, so they're only reset once all pads are gone
+                EventType::Disconnected if gilrs.gamepads().next().is_none() => {
+                    events.push(InputEvent::GamepadDisconnected)
+                }
+                _ => (),
+            }
+        }
+        events
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn button_from_gilrs(button: gilrs::Button) -> Option<GamepadButton> {
+    use gilrs::Button;
+
+    match button {
+        Button::South => Some(GamepadButton::South),
+        Button::East => Some(GamepadButton::East),
+        Button::North => Some(GamepadButton::North),
+        Button::West => Some(GamepadButton::West),
+        Button::LeftTrigger => Some(GamepadButton::LeftBumper),
+        Button::RightTrigger => Some(GamepadButton::RightBumper),
+        Button::Select => Some(GamepadButton::Select),
+        Button::Start => Some(GamepadButton::Start),
+        Button::DPadUp => Some(GamepadButton::DPadUp),
+        Button::DPadDown => Some(GamepadButton::DPadDown),
+        Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        Button::DPadRight => Some(GamepadButton::DPadRight),
+        Button::LeftThumb => Some(GamepadButton::LeftStick),
+        Button::RightThumb => Some(GamepadButton::RightStick),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn poll(&mut self) -> Vec<InputEvent> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,608 @@
+mod bindings;
+mod gamepad;
+mod recording;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use winit::event::*;
+use winit::keyboard::PhysicalKey::Code;
+
+pub use bindings::{Binding, Bindings, Key, Modifiers};
+pub use gamepad::{GamepadButton, GamepadStick, StickParams};
+
+pub use recording::{Playback, Recording};
+
+use gamepad::{GamepadAxis, Gamepads};
+use recording::RecordedFrame;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Quit,
+    ControlPlayer,
+    Spawn,
+    Grab,
+    ZoomIn,
+    ZoomOut,
+    ToggleWalking,
+    Jump,
+    Crouch,
+    SwitchCamera,
+    Sprint,
+    Slow,
+    RotateGrabbed,
+    Select,
+    AddToSelection,
+    DeleteSelected,
+    DuplicateSelected,
+    FreezeSelected,
+    SwitchGizmo,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 25] = [
+        InputAction::MoveForward,
+        InputAction::MoveBack,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::Quit,
+        InputAction::ControlPlayer,
+        InputAction::Spawn,
+        InputAction::Grab,
+        InputAction::ZoomIn,
+        InputAction::ZoomOut,
+        InputAction::ToggleWalking,
+        InputAction::Jump,
+        InputAction::Crouch,
+        InputAction::SwitchCamera,
+        InputAction::Sprint,
+        InputAction::Slow,
+        InputAction::RotateGrabbed,
+        InputAction::Select,
+        InputAction::AddToSelection,
+        InputAction::DeleteSelected,
+        InputAction::DuplicateSelected,
+        InputAction::FreezeSelected,
+        InputAction::SwitchGizmo,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputAction::MoveForward => "Move forward",
+            InputAction::MoveBack => "Move back",
+            InputAction::MoveLeft => "Move left",
+            InputAction::MoveRight => "Move right",
+            InputAction::MoveUp => "Move up",
+            InputAction::MoveDown => "Move down",
+            InputAction::Quit => "Quit",
+            InputAction::ControlPlayer => "Capture/release mouse",
+            InputAction::Spawn => "Spawn a box",
+            InputAction::Grab => "Grab/release an object",
+            InputAction::ZoomIn => "Pull a grabbed object",
+            InputAction::ZoomOut => "Push a grabbed object",
+            InputAction::ToggleWalking => "Walk/fly",
+            InputAction::Jump => "Jump",
+            InputAction::Crouch => "Crouch",
+            InputAction::SwitchCamera => "Switch first-person/third-person/orbit camera",
+            InputAction::Sprint => "Move faster",
+            InputAction::Slow => "Move slower",
+            InputAction::RotateGrabbed => "Rotate a grabbed object (hold and look)",
+            InputAction::Select => "Select an object (click or drag a box)",
+            InputAction::AddToSelection => "Add to/remove from the selection",
+            InputAction::DeleteSelected => "Delete the selected objects",
+            InputAction::DuplicateSelected => "Duplicate the selected objects",
+            InputAction::FreezeSelected => "Freeze/unfreeze the selected objects",
+            InputAction::SwitchGizmo => "Switch move/rotate/scale gizmo",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InputAxis {
+    // Right, up and forward
+    MoveX,
+    MoveY,
+    MoveZ,
+    // Right and up
+    LookX,
+    LookY,
+    // In, i.e. nearer
+    Zoom,
+}
+
+// What `Input` and the UI take from winit and gamepads, in a form that can be recorded and replayed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key { key: Key, pressed: bool },
+    Modifiers(Modifiers),
+    // Typed characters, only for the UI
+    Text(String),
+    // In lines, up is positive
+    Wheel(f32),
+    CursorMoved(f32, f32),
+    CursorInWindow(bool),
+    MouseMotion(f32, f32),
+    GamepadAxis(GamepadAxis, f32),
+    // The last connected gamepad
+    GamepadDisconnected,
+}
+
+// Wheel scrolling reported in pixels (e.g. by touchpads) is converted to lines
+const WHEEL_PIXELS_PER_LINE: f32 = 40.0;
+
+// TODO Extract raw events, they don't belong here.
+// They were introduced to avoid passing raw events to Scene so that it could update Ui.
+pub struct Input {
+    mouse_delta: (f32, f32),
+    // In lines, up is positive
+    wheel_delta: f32,
+    last_cursor_position: (f32, f32),
+    cursor_in_window: bool,
+    // TODO Use something stack-based instead of HashMap?
+    key_pressed: HashMap<Key, bool>,
+    // Key presses from the previous frame
+    key_pressed_prev: HashMap<Key, bool>,
+    modifiers: Modifiers,
+    bindings: Bindings,
+    gamepads: Gamepads,
+    // Raw values, all gamepads are merged
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    left_stick_params: StickParams,
+    right_stick_params: StickParams,
+    // Off while e.g. a new binding is being captured
+    actions_enabled: bool,
+    // Whether the UI takes the mouse (e.g. it's over a window) or the keyboard (e.g. text input)
+    ui_wants_mouse: bool,
+    ui_wants_keyboard: bool,
+    // Handled during the current frame, kept only when recording
+    frame_events: Vec<InputEvent>,
+    recording: Option<Recording>,
+    playback: Option<Playback>,
+    new_raw_events: Vec<Event<()>>,
+    // For the UI, including the ones it captures. Recorded ones during playback.
+    ui_events: Vec<InputEvent>,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Self {
+        Input {
+            mouse_delta: (0.0, 0.0),
+            wheel_delta: 0.0,
+            last_cursor_position: (0.0, 0.0),
+            cursor_in_window: false,
+            key_pressed: HashMap::new(),
+            key_pressed_prev: HashMap::new(),
+            modifiers: Modifiers::default(),
+            bindings,
+            gamepads: Gamepads::new(),
+            gamepad_axes: HashMap::new(),
+            left_stick_params: StickParams::default(),
+            right_stick_params: StickParams::default(),
+            actions_enabled: true,
+            ui_wants_mouse: false,
+            ui_wants_keyboard: false,
+            frame_events: Vec::new(),
+            recording: None,
+            playback: None,
+            new_raw_events: Vec::new(),
+            ui_events: Vec::new(),
+        }
+    }
+
+    // -1..1 on both axes after the dead zone and response curve, scaled by the sensitivity.
+    // Up is positive.
+    pub fn gamepad_stick(&self, stick: GamepadStick) -> (f32, f32) {
+        let (x_axis, y_axis, params) = match stick {
+            GamepadStick::Left => (
+                GamepadAxis::LeftStickX,
+                GamepadAxis::LeftStickY,
+                &self.left_stick_params,
+            ),
+            GamepadStick::Right => (
+                GamepadAxis::RightStickX,
+                GamepadAxis::RightStickY,
+                &self.right_stick_params,
+            ),
+        };
+        params.apply(self.gamepad_axis(x_axis), self.gamepad_axis(y_axis))
+    }
+
+    // Left and right, 0..1
+    pub fn gamepad_triggers(&self) -> (f32, f32) {
+        // Triggers rarely rest exactly at zero
+        let dead_zone = |v: f32| if v < 0.05 { 0.0 } else { v };
+        (
+            dead_zone(self.gamepad_axis(GamepadAxis::LeftTrigger)),
+            dead_zone(self.gamepad_axis(GamepadAxis::RightTrigger)),
+        )
+    }
+
+    pub fn stick_params_mut(&mut self, stick: GamepadStick) -> &mut StickParams {
+        match stick {
+            GamepadStick::Left => &mut self.left_stick_params,
+            GamepadStick::Right => &mut self.right_stick_params,
+        }
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    pub fn set_actions_enabled(&mut self, enabled: bool) {
+        self.actions_enabled = enabled;
+    }
+
+    // From the UI's last frame, applies to events handled from now on.
+    pub fn set_ui_capture(&mut self, wants_mouse: bool, wants_keyboard: bool) {
+        self.ui_wants_mouse = wants_mouse;
+        self.ui_wants_keyboard = wants_keyboard;
+    }
+
+    // A bindable key pressed this frame, with the modifiers held along with it. For capturing
+    // new bindings. A modifier on its own is taken when it's released, any other key pressed
+    // while it's held makes a chord instead.
+    pub fn pressed_binding(&self) -> Option<Binding> {
+        let chord = self
+            .key_pressed
+            .keys()
+            .find(|key| !key.is_modifier() && key.is_bindable() && self.key_pressed_first(**key))
+            .map(|&key| Binding {
+                key,
+                modifiers: self.modifiers,
+            });
+        chord.or_else(|| {
+            self.key_pressed_prev
+                .keys()
+                .find(|key| key.is_modifier() && key.is_bindable() && self.key_released(**key))
+                .map(|&key| Binding::new(key))
+        })
+    }
+
+    // Held value from keys, sticks and triggers, -1..1. A rate, to be scaled by the frame time.
+    pub fn axis(&self, axis: InputAxis) -> f32 {
+        let keys = |positive, negative| {
+            self.action_active(positive) as i32 as f32 - self.action_active(negative) as i32 as f32
+        };
+        let (left_x, left_y) = self.gamepad_stick(GamepadStick::Left);
+        let (right_x, right_y) = self.gamepad_stick(GamepadStick::Right);
+        let (left_trigger, right_trigger) = self.gamepad_triggers();
+
+        let value = match axis {
+            InputAxis::MoveX => keys(InputAction::MoveRight, InputAction::MoveLeft) + left_x,
+            InputAxis::MoveY => {
+                keys(InputAction::MoveUp, InputAction::MoveDown) + right_trigger - left_trigger
+            }
+            InputAxis::MoveZ => keys(InputAction::MoveForward, InputAction::MoveBack) + left_y,
+            InputAxis::LookX => right_x,
+            InputAxis::LookY => right_y,
+            InputAxis::Zoom => keys(InputAction::ZoomIn, InputAction::ZoomOut),
+        };
+        if self.actions_enabled {
+            value.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    // What happened during this frame via the mouse: motion in pixels for looking and wheel lines
+    // for zooming. Not to be scaled by the frame time.
+    pub fn axis_delta(&self, axis: InputAxis) -> f32 {
+        if !self.actions_enabled {
+            return 0.0;
+        }
+        match axis {
+            InputAxis::LookX => self.mouse_delta.0,
+            InputAxis::LookY => -self.mouse_delta.1,
+            InputAxis::Zoom => self.wheel_delta,
+            InputAxis::MoveX | InputAxis::MoveY | InputAxis::MoveZ => 0.0,
+        }
+    }
+
+    pub fn new_raw_events(&self) -> &[Event<()>] {
+        &self.new_raw_events
+    }
+
+    pub fn ui_events(&self) -> &[InputEvent] {
+        &self.ui_events
+    }
+
+    pub fn cursor_position(&self) -> Option<(f32, f32)> {
+        if self.cursor_in_window {
+            Some(self.last_cursor_position)
+        } else {
+            None
+        }
+    }
+
+    pub fn action_active(&self, action: InputAction) -> bool {
+        self.actions_enabled
+            && self
+                .bindings
+                .get(action)
+                .iter()
+                .any(|b| self.key_pressed(b.key) && self.binding_held(b))
+    }
+
+    pub fn action_activated(&self, action: InputAction) -> bool {
+        self.actions_enabled
+            && self
+                .bindings
+                .get(action)
+                .iter()
+                .any(|b| self.key_pressed_first(b.key) && self.binding_held(b))
+    }
+
+    pub fn handle_event(&mut self, event: Event<()>) {
+        self.new_raw_events.push(event.clone());
+        // Real input is ignored during playback, by the UI too
+        if self.playback.is_none() {
+            for event in input_events_from_winit(event) {
+                self.handle_input_event(event);
+            }
+        }
+    }
+
+    // Gamepads aren't reported via winit, must be called every frame.
+    pub fn poll_gamepads(&mut self) {
+        for event in self.gamepads.poll() {
+            if self.playback.is_none() {
+                self.handle_input_event(event);
+            }
+        }
+    }
+
+    // Records input from now on, frames are added with `record_frame`.
+    pub fn start_recording(&mut self, window_size: [u32; 2]) {
+        self.recording = Some(Recording::new(window_size));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    // Must be called after the frame's input has been consumed, before `clear`.
+    pub fn record_frame(&mut self, dt: f32) {
+        if let Some(recording) = &mut self.recording {
+            recording.frames.push(RecordedFrame {
+                dt,
+                events: std::mem::take(&mut self.frame_events),
+            });
+        }
+    }
+
+    // Real input is ignored until the playback ends.
+    pub fn start_playback(&mut self, playback: Playback) {
+        self.playback = Some(playback);
+    }
+
+    pub fn playback(&self) -> Option<&Playback> {
+        self.playback.as_ref()
+    }
+
+    // Applies the next recorded frame's input and returns its dt, or none if the playback is over.
+    pub fn play_frame(&mut self) -> Option<f32> {
+        let playback = self.playback.as_mut()?;
+        let Some(frame) = playback.next_frame() else {
+            // Nothing recorded should stay pressed
+            self.playback = None;
+            self.key_pressed.clear();
+            self.gamepad_axes.clear();
+            self.modifiers = Modifiers::default();
+            return None;
+        };
+        let (dt, events) = (frame.dt, frame.events.clone());
+        for event in events {
+            self.handle_input_event(event);
+        }
+        Some(dt)
+    }
+
+    // Everything is recorded and goes to the UI, so that replaying reproduces what the UI did and
+    // so also what it captured.
+    fn handle_input_event(&mut self, event: InputEvent) {
+        if self.recording.is_some() {
+            self.frame_events.push(event.clone());
+        }
+        self.ui_events.push(event.clone());
+        if !self.captured_by_ui(&event) {
+            self.apply_event(event);
+        }
+    }
+
+    fn apply_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key { key, pressed } => {
+                self.key_pressed.insert(key, pressed);
+            }
+            InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+            InputEvent::Text(_) => {}
+            InputEvent::Wheel(dy) => {
+                self.wheel_delta += dy;
+                if dy > 0.0 {
+                    self.key_pressed.insert(Key::WheelUp, true);
+                } else if dy < 0.0 {
+                    self.key_pressed.insert(Key::WheelDown, true);
+                }
+            }
+            InputEvent::CursorMoved(x, y) => self.last_cursor_position = (x, y),
+            InputEvent::CursorInWindow(entered) => self.handle_cursor_entrance(entered),
+            InputEvent::MouseMotion(x, y) => {
+                self.mouse_delta.0 += x;
+                self.mouse_delta.1 += y;
+            }
+            InputEvent::GamepadAxis(axis, value) => {
+                self.gamepad_axes.insert(axis, value);
+            }
+            InputEvent::GamepadDisconnected => {
+                self.gamepad_axes.clear();
+                self.key_pressed
+                    .retain(|key, _| !matches!(key, Key::GamepadButton(_)));
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.clear_step();
+        self.new_raw_events.clear();
+        self.ui_events.clear();
+    }
+
+    // Like `clear` but keeps the events for the UI, for several simulation steps in one frame.
+    pub fn clear_step(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.wheel_delta = 0.0;
+        self.key_pressed_prev.clone_from(&self.key_pressed);
+        // Every scroll is a new press
+        for key in [Key::WheelUp, Key::WheelDown] {
+            self.key_pressed.remove(&key);
+            self.key_pressed_prev.remove(&key);
+        }
+        self.frame_events.clear();
+    }
+
+    // Presses go to the UI when it wants them, releases always pass so that nothing stays pressed.
+    // Capturing a new binding needs all of them.
+    fn captured_by_ui(&self, event: &InputEvent) -> bool {
+        if !self.actions_enabled {
+            return false;
+        }
+        match event {
+            InputEvent::Key {
+                key: Key::Keyboard(_),
+                pressed: true,
+            } => self.ui_wants_keyboard,
+            InputEvent::Key {
+                key: Key::MouseButton(_),
+                pressed: true,
+            }
+            | InputEvent::Wheel(_) => self.ui_wants_mouse,
+            _ => false,
+        }
+    }
+
+    fn handle_cursor_entrance(&mut self, entered: bool) {
+        self.cursor_in_window = entered;
+        // Reset all pressed mouse buttons when the cursor leaves
+        if !entered {
+            let keys = self.key_pressed.keys().cloned().collect::<Vec<_>>();
+            for key in keys {
+                if let Key::MouseButton(_) = key {
+                    self.key_pressed.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn key_pressed_first(&self, key: Key) -> bool {
+        let pressed = *self.key_pressed.get(&key).unwrap_or(&false);
+        let last_pressed = *self.key_pressed_prev.get(&key).unwrap_or(&false);
+        pressed && !last_pressed
+    }
+
+    fn key_released(&self, key: Key) -> bool {
+        let pressed = *self.key_pressed.get(&key).unwrap_or(&false);
+        let last_pressed = *self.key_pressed_prev.get(&key).unwrap_or(&false);
+        !pressed && last_pressed
+    }
+
+    fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        *self.gamepad_axes.get(&axis).unwrap_or(&0.0)
+    }
+
+    fn key_pressed(&self, key: Key) -> bool {
+        *self.key_pressed.get(&key).unwrap_or(&false)
+    }
+
+    // Whether the binding's modifiers are held and no longer chord of the same key is held, so
+    // that e.g. Shift+F doesn't also trigger F.
+    fn binding_held(&self, binding: &Binding) -> bool {
+        self.modifiers.contains(binding.modifiers)
+            && !self.bindings.all().any(|other| {
+                other.key == binding.key
+                    && other.modifiers != binding.modifiers
+                    && other.modifiers.contains(binding.modifiers)
+                    && self.modifiers.contains(other.modifiers)
+            })
+    }
+}
+
+fn input_events_from_winit(event: Event<()>) -> Vec<InputEvent> {
+    if let Event::WindowEvent {
+        event: WindowEvent::KeyboardInput { event, .. },
+        ..
+    } = event
+    {
+        let pressed = event.state == ElementState::Pressed;
+        let text = event
+            .text
+            .filter(|_| pressed)
+            .map(|text| InputEvent::Text(text.to_string()));
+        let key = match event.physical_key {
+            // Others can't be bound, nor recorded
+            Code(code) if Key::Keyboard(code).is_bindable() => Some(InputEvent::Key {
+                key: Key::Keyboard(code),
+                pressed,
+            }),
+            _ => None,
+        };
+        return text.into_iter().chain(key).collect();
+    }
+    input_event_from_winit(event).into_iter().collect()
+}
+
+// Except the keyboard, see `input_events_from_winit`
+fn input_event_from_winit(event: Event<()>) -> Option<InputEvent> {
+    match event {
+        Event::WindowEvent { event, .. } => match event {
+            WindowEvent::MouseInput { button, state, .. } => Some(InputEvent::Key {
+                key: Key::MouseButton(button),
+                pressed: state == ElementState::Pressed,
+            }),
+
+            WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Wheel(match delta {
+                MouseScrollDelta::LineDelta(_, y) => y,
+                MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / WHEEL_PIXELS_PER_LINE,
+            })),
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                Some(InputEvent::Modifiers(Modifiers {
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    alt: state.alt_key(),
+                }))
+            }
+
+            WindowEvent::CursorEntered { .. } => Some(InputEvent::CursorInWindow(true)),
+            WindowEvent::CursorLeft { .. } => Some(InputEvent::CursorInWindow(false)),
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved(
+                position.x as f32,
+                position.y as f32,
+            )),
+
+            _ => None,
+        },
+
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta: (x, y) },
+            ..
+        } => Some(InputEvent::MouseMotion(x as f32, y as f32)),
+
+        _ => None,
+    }
+}
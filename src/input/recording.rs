@@ -0,0 +1,90 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::InputEvent;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RecordedFrame {
+    pub dt: f32,
+    pub events: Vec<InputEvent>,
+}
+
+impl RecordedFrame {
+    // Anything but motion, e.g. clicks and key presses the UI reacts to at once
+    fn has_discrete_input(&self) -> bool {
+        self.events.iter().any(|event| {
+            !matches!(
+                event,
+                InputEvent::CursorMoved(..)
+                    | InputEvent::MouseMotion(..)
+                    | InputEvent::GamepadAxis(..)
+            )
+        })
+    }
+}
+
+// Input of every frame since the app start, replaying it reproduces the session as long as the
+// scene and the window size are the same.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Recording {
+    pub window_size: [u32; 2],
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn new(window_size: [u32; 2]) -> Self {
+        Self {
+            window_size,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        Ok(std::fs::write(path, serde_yaml::to_string(self)?)?)
+    }
+}
+
+pub struct Playback {
+    recording: Recording,
+    next_frame: usize,
+    // Recorded frames per rendered frame
+    pub speed: u32,
+}
+
+impl Playback {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            next_frame: 0,
+            speed: 1,
+        }
+    }
+
+    // Played and total frames
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next_frame, self.recording.frames.len())
+    }
+
+    // Whether the next frame can be played in the same rendered frame as the previous one. The UI
+    // handles all input of a rendered frame at once, so frames with discrete input are played on
+    // their own, for the UI to react to it in the same frame as when it was recorded.
+    pub fn can_batch_next(&self) -> bool {
+        let discrete = |i: usize| {
+            self.recording
+                .frames
+                .get(i)
+                .is_some_and(RecordedFrame::has_discrete_input)
+        };
+        !discrete(self.next_frame) && (self.next_frame == 0 || !discrete(self.next_frame - 1))
+    }
+
+    pub fn next_frame(&mut self) -> Option<&RecordedFrame> {
+        let frame = self.recording.frames.get(self.next_frame)?;
+        self.next_frame += 1;
+        Some(frame)
+    }
+}
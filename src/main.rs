@@ -1,4 +1,4 @@
-use crate::app::App;
+use crate::app::{App, AppOptions};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
@@ -15,8 +15,6 @@ mod window;
 // TODO Scene loading from file or at least from a scene definition.
 // TODO Spawned boxes should be rotated based on the camera view.
 // TODO Dragging should maintain box rotation relative to the camera.
-// TODO Selected object highlighting.
-// TODO Gizmos (e.g. axes instead of a box representing the player's target)
 // TODO Encapsulate/isolate library stuff (e.g. Rapier, wgpu, etc.) from the rest of the code better.
 // TODO Switch to raw Vulkan and/or introduce it as a separate backend. wgpu has an unstable API.
 
@@ -24,7 +22,15 @@ fn main() {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::default();
+    let options = match AppOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return;
+        }
+    };
+
+    let mut app = App::new(options);
     if let Err(e) = event_loop.run_app(&mut app) {
         eprintln!("Error: {e}");
     }
@@ -8,16 +8,19 @@ pub type Vec3 = na::Vector3<f32>;
 pub type Mat4 = na::Matrix4<f32>;
 pub type Quat = na::Quaternion<f32>;
 pub type UnitQuat = na::UnitQuaternion<f32>;
+pub use rapier3d::na::Orthographic3;
 pub use rapier3d::na::Perspective3;
 pub use rapier3d::na::Rotation3;
 pub use rapier3d::na::Translation3;
 pub use rapier3d::prelude::Ray;
 
+// Also reverses the depth so that the near plane ends up at 1 and the far one at 0,
+// spreading float depth precision more evenly.
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: Mat4 = Mat4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, -0.5, 0.5,
     0.0, 0.0, 0.0, 1.0,
 );
 
@@ -1,4 +1,6 @@
-use rapier3d::control::{EffectiveCharacterMovement, KinematicCharacterController};
+use rapier3d::control::{
+    CharacterAutostep, CharacterLength, EffectiveCharacterMovement, KinematicCharacterController,
+};
 use rapier3d::prelude::*;
 
 use crate::math::Vec3;
@@ -6,6 +8,12 @@ use crate::math::Vec3;
 pub use rapier3d::prelude::ColliderBuilder;
 pub use rapier3d::prelude::ColliderHandle;
 pub use rapier3d::prelude::RigidBodyHandle;
+pub use rapier3d::prelude::SharedShape;
+
+// Fixed so that the simulation doesn't depend on the frame rate, e.g. when replaying input
+const FIXED_DT: f32 = 1.0 / 60.0;
+// Slow frames drop time beyond this instead of spiraling into even slower ones
+const MAX_STEPS_PER_UPDATE: u32 = 8;
 
 pub struct Physics {
     colliders: ColliderSet,
@@ -18,7 +26,31 @@ pub struct Physics {
     impulse_joints: ImpulseJointSet,
     multibody_joints: MultibodyJointSet,
     ccd_solver: CCDSolver,
-    char_controller: KinematicCharacterController,
+    // Not yet simulated time
+    dt_acc: f32,
+}
+
+// Kinematic character controller options, lengths are absolute
+#[derive(Copy, Clone, Debug)]
+pub struct CharacterParams {
+    // Radians, steeper slopes stop the character
+    pub max_slope_climb_angle: f32,
+    // Radians, the character slides down steeper slopes
+    pub min_slope_slide_angle: f32,
+    // Steps up to this height are climbed without jumping
+    pub autostep_height: Option<f32>,
+    // Keeps the character on the ground when going down steps and slopes up to this height
+    pub snap_to_ground: Option<f32>,
+    // Dynamic bodies in the way get pushed, not if none
+    pub mass: Option<f32>,
+}
+
+pub struct CharacterMovement {
+    // Possible part of the desired translation
+    pub translation: Vec3,
+    pub grounded: bool,
+    // Collider position before the movement
+    pub collider_position: Vec3,
 }
 
 pub struct RayCastResult {
@@ -42,7 +74,7 @@ impl Physics {
             impulse_joints: ImpulseJointSet::new(),
             multibody_joints: MultibodyJointSet::new(),
             ccd_solver: CCDSolver::new(),
-            char_controller: KinematicCharacterController::default(),
+            dt_acc: 0.0,
         }
     }
 
@@ -59,6 +91,97 @@ impl Physics {
         body
     }
 
+    // Along with its colliders.
+    pub fn remove_body(&mut self, handle: RigidBodyHandle) {
+        self.bodies.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            true,
+        );
+    }
+
+    // A copy of the body and its colliders, moved by `offset`.
+    pub fn duplicate_body(&mut self, handle: RigidBodyHandle, offset: Vec3) -> RigidBodyHandle {
+        let body = self.bodies.get(handle).unwrap();
+        let colliders = body
+            .colliders()
+            .iter()
+            .map(|&c| self.colliders.get(c).unwrap().clone())
+            .collect::<Vec<_>>();
+        let mut copy = body.clone();
+        copy.set_translation(body.translation() + offset, true);
+
+        let copy = self.bodies.insert(copy);
+        for collider in colliders {
+            self.colliders
+                .insert_with_parent(collider, copy, &mut self.bodies);
+        }
+        copy
+    }
+
+    // Min and max corners of the box around all the body's colliders.
+    pub fn body_aabb(&self, handle: RigidBodyHandle) -> (Vec3, Vec3) {
+        let aabb = self
+            .body(handle)
+            .colliders()
+            .iter()
+            .map(|&c| self.colliders.get(c).unwrap().compute_aabb())
+            .reduce(|a, b| a.merged(&b))
+            .unwrap();
+        (aabb.mins.coords, aabb.maxs.coords)
+    }
+
+    // Bodies with colliders at least partially inside the convex volume bounded by the planes,
+    // given as inward normals and distances so that `normal.dot(p) + distance >= 0` inside.
+    pub fn bodies_inside_planes(&self, planes: &[(Vec3, f32)]) -> Vec<RigidBodyHandle> {
+        let mut bodies = self
+            .colliders
+            .iter()
+            .filter_map(|(_, collider)| {
+                let aabb = collider.compute_aabb();
+                // Outside if the box corner farthest along the normal is outside
+                let inside = planes.iter().all(|(normal, distance)| {
+                    let corner = Vec3::new(
+                        if normal.x >= 0.0 {
+                            aabb.maxs.x
+                        } else {
+                            aabb.mins.x
+                        },
+                        if normal.y >= 0.0 {
+                            aabb.maxs.y
+                        } else {
+                            aabb.mins.y
+                        },
+                        if normal.z >= 0.0 {
+                            aabb.maxs.z
+                        } else {
+                            aabb.mins.z
+                        },
+                    );
+                    normal.dot(&corner) + distance >= 0.0
+                });
+                collider.parent().filter(|_| inside)
+            })
+            .collect::<Vec<_>>();
+        bodies.sort_by_key(|b| b.into_raw_parts());
+        bodies.dedup();
+        bodies
+    }
+
+    // Scales the body's box colliders along their axes, other shapes are left as they are.
+    pub fn scale_body_colliders(&mut self, handle: RigidBodyHandle, factor: Vec3) {
+        for &c in self.bodies.get(handle).unwrap().colliders() {
+            let collider = self.colliders.get_mut(c).unwrap();
+            if let Some(cuboid) = collider.shape().as_cuboid() {
+                let half = cuboid.half_extents.component_mul(&factor);
+                collider.set_shape(SharedShape::cuboid(half.x, half.y, half.z));
+            }
+        }
+    }
+
     pub fn body(&self, handle: RigidBodyHandle) -> &RigidBody {
         self.bodies.get(handle).unwrap()
     }
@@ -76,33 +199,65 @@ impl Physics {
     }
 
     pub fn move_character(
-        &self,
+        &mut self,
         dt: f32,
         desired_translation: Vec3,
         collider: ColliderHandle,
-    ) -> (Vec3, Vec3) {
-        let (EffectiveCharacterMovement { translation, .. }, collider_current_pos) = {
-            let (collider_pos, collider_shape) = {
-                let collider = self.colliders.get(collider).unwrap();
-                (collider.position(), collider.shape())
-            };
-
-            let possible_movement = self.char_controller.move_shape(
+        params: &CharacterParams,
+    ) -> CharacterMovement {
+        let controller = KinematicCharacterController {
+            max_slope_climb_angle: params.max_slope_climb_angle,
+            min_slope_slide_angle: params.min_slope_slide_angle,
+            autostep: params.autostep_height.map(|height| CharacterAutostep {
+                max_height: CharacterLength::Absolute(height),
+                min_width: CharacterLength::Absolute(0.1),
+                // Pushed instead
+                include_dynamic_bodies: false,
+            }),
+            snap_to_ground: params.snap_to_ground.map(CharacterLength::Absolute),
+            ..KinematicCharacterController::default()
+        };
+        let filter = QueryFilter::default().exclude_collider(collider);
+        let (collider_pos, collider_shape) = {
+            let collider = self.colliders.get(collider).unwrap();
+            (*collider.position(), collider.shared_shape().clone())
+        };
+
+        let mut collisions = Vec::new();
+        let EffectiveCharacterMovement {
+            translation,
+            grounded,
+            ..
+        } = controller.move_shape(
+            dt,
+            &self.bodies,
+            &self.colliders,
+            &self.query_pipeline,
+            collider_shape.as_ref(),
+            &collider_pos,
+            desired_translation,
+            filter,
+            |collision| collisions.push(collision),
+        );
+
+        if let Some(mass) = params.mass {
+            controller.solve_character_collision_impulses(
                 dt,
-                &self.bodies,
+                &mut self.bodies,
                 &self.colliders,
                 &self.query_pipeline,
-                collider_shape,
-                collider_pos,
-                desired_translation,
-                QueryFilter::default().exclude_collider(collider),
-                |_| {},
+                collider_shape.as_ref(),
+                mass,
+                &collisions,
+                filter,
             );
+        }
 
-            (possible_movement, collider_pos.translation.vector)
-        };
-
-        (translation, collider_current_pos)
+        CharacterMovement {
+            translation,
+            grounded,
+            collider_position: collider_pos.translation.vector,
+        }
     }
 
     pub fn cast_ray(
@@ -139,9 +294,25 @@ impl Physics {
     }
 
     pub fn update(&mut self, dt: f32) {
+        self.dt_acc += dt;
+        let mut steps = 0;
+        while self.dt_acc >= FIXED_DT {
+            if steps == MAX_STEPS_PER_UPDATE {
+                self.dt_acc = 0.0;
+                break;
+            }
+            self.step();
+            self.dt_acc -= FIXED_DT;
+            steps += 1;
+        }
+
+        self.query_pipeline.update(&self.colliders);
+    }
+
+    fn step(&mut self) {
         let gravity = vector![0.0, -9.81, 0.0];
         let params = IntegrationParameters {
-            dt,
+            dt: FIXED_DT,
             ..IntegrationParameters::default()
         };
 
@@ -160,7 +331,5 @@ impl Physics {
             &(),
             &(),
         );
-
-        self.query_pipeline.update(&self.colliders);
     }
 }
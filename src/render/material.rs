@@ -1,3 +1,3 @@
 pub trait ApplyMaterial {
     fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>);
-}
\ No newline at end of file
+}
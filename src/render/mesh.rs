@@ -159,6 +159,22 @@ impl Mesh {
         }
     }
 
+    // Each pair of points is a line, for wireframe materials.
+    pub fn new_lines(device: &wgpu::Device, points: &[[f32; 3]]) -> Self {
+        let vertices = points
+            .iter()
+            .map(|&position| PositionUvNormalVertex {
+                position,
+                uv: Default::default(),     // unused
+                normal: Default::default(), // unused
+            })
+            .collect::<Vec<_>>();
+        let indices = (0..points.len() as u32).collect::<Vec<_>>();
+        Self {
+            parts: vec![MeshPart::from_buffers(device, &vertices, &indices)],
+        }
+    }
+
     // TODO Extract the file reading, this struct is only for rendering.
     pub async fn from_file(device: &wgpu::Device, file_path: &str) -> Mesh {
         let text = file::read_string_asset(file_path).await.unwrap();
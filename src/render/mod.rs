@@ -8,8 +8,10 @@ mod vertex;
 
 pub use material::ApplyMaterial;
 pub use mesh::Mesh;
-pub use render_target::RenderTarget;
-pub use renderer::{RenderPipelineParams, Renderer, SurfaceSize};
-pub use texture::Texture;
+pub use render_target::{RenderTarget, RenderTargetParams};
+pub use renderer::{
+    RenderPassParams, RenderPipelineParams, Renderer, StencilMode, SurfaceSize, Viewport,
+};
+pub use texture::{SamplerParams, Texture, TextureSize};
 pub use ui::Ui;
 pub use vertex::{PositionUvNormalVertex, PositionUvVertex};
@@ -2,20 +2,43 @@ use super::renderer::Renderer;
 use super::texture::Texture;
 use super::texture::TextureSize;
 
+#[derive(Default, Copy, Clone)]
+pub struct RenderTargetParams {
+    // Surface size if not specified
+    pub size: Option<TextureSize>,
+    // Surface format if not specified
+    pub format: Option<wgpu::TextureFormat>,
+    // No multisampling if not specified
+    pub sample_count: Option<u32>,
+}
+
 pub struct RenderTarget {
+    // Single-sampled, this is what gets sampled by whoever consumes the target.
     color_tex: Texture,
+    // Rendered into when multisampling is on, then resolved into `color_tex`.
+    msaa_color_tex: Option<Texture>,
+    // Multisampled if multisampling is on
     depth_tex: Texture,
+    size: TextureSize,
+    sample_count: u32,
 }
 
 impl RenderTarget {
-    pub fn new(rr: &Renderer, size: Option<TextureSize>) -> Self {
-        let size = size.unwrap_or(rr.surface_size().into());
-        let color_tex = Texture::new_render_attachment(rr, rr.surface_texture_format(), size);
-        let depth_tex = Texture::new_depth(rr, rr.depth_texture_format(), size);
+    pub fn new(rr: &Renderer, params: RenderTargetParams) -> Self {
+        let size = params.size.unwrap_or(rr.surface_size().into());
+        let format = params.format.unwrap_or(rr.surface_texture_format());
+        let sample_count = params.sample_count.unwrap_or(1);
+        let color_tex = Texture::new_render_attachment(rr, format, size, 1);
+        let msaa_color_tex = (sample_count > 1)
+            .then(|| Texture::new_render_attachment(rr, format, size, sample_count));
+        let depth_tex = Texture::new_depth(rr, rr.depth_texture_format(), size, sample_count);
 
         Self {
             color_tex,
+            msaa_color_tex,
             depth_tex,
+            size,
+            sample_count,
         }
     }
 
@@ -23,11 +46,38 @@ impl RenderTarget {
         &self.color_tex
     }
 
+    pub fn msaa_color_tex(&self) -> Option<&Texture> {
+        self.msaa_color_tex.as_ref()
+    }
+
     pub fn depth_tex(&self) -> &Texture {
         &self.depth_tex
     }
 
+    pub fn size(&self) -> TextureSize {
+        self.size
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
     pub fn resize(&mut self, new_size: TextureSize, rr: &Renderer) {
-        *self = RenderTarget::new(rr, Some(new_size));
+        self.rebuild(rr, new_size, self.sample_count);
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: u32, rr: &Renderer) {
+        self.rebuild(rr, self.size, sample_count);
+    }
+
+    fn rebuild(&mut self, rr: &Renderer, size: TextureSize, sample_count: u32) {
+        *self = RenderTarget::new(
+            rr,
+            RenderTargetParams {
+                size: Some(size),
+                format: Some(self.color_tex.format()),
+                sample_count: Some(sample_count),
+            },
+        );
     }
 }
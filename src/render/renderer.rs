@@ -1,11 +1,14 @@
+use std::cell::RefCell;
 use std::ops::Deref;
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
+use crate::file;
+
 use super::material::ApplyMaterial;
 use super::mesh::Mesh;
 use super::render_target::RenderTarget;
-use super::texture::Texture;
+use super::texture::{Texture, TextureSize};
 use super::ui::Ui;
 
 pub type SurfaceSize = winit::dpi::PhysicalSize<u32>;
@@ -15,10 +18,56 @@ pub struct RenderPipelineParams<'a> {
     pub depth_write: bool,
     pub depth_enabled: bool,
     pub wireframe: bool,
+    pub stencil: StencilMode,
+    pub color_format: wgpu::TextureFormat,
+    pub sample_count: u32,
     pub bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
     pub vertex_buffer_layouts: &'a [wgpu::VertexBufferLayout<'a>],
 }
 
+// Stencil use for outlines. Every pass clears the stencil to 0.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum StencilMode {
+    #[default]
+    Off,
+    // Marks covered pixels, without writing any color
+    Mark,
+    // Draws only where nothing is marked
+    Unmarked,
+}
+
+// Normalized (0..1) rectangle of a render target, with the origin in the top left corner
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RenderPassParams<'a> {
+    // Screen if not set
+    pub target: Option<&'a RenderTarget>,
+    pub viewport: Viewport,
+    // Load what's already in the color target instead of clearing it.
+    // Depth is always cleared.
+    pub keep_color: bool,
+    // Renderer's clear color if not set
+    pub clear_color: Option<wgpu::Color>,
+}
+
 pub struct Renderer<'a> {
     pub adapter_name: String,
     surface: wgpu::Surface<'a>,
@@ -26,18 +75,45 @@ pub struct Renderer<'a> {
     device: wgpu::Device,
     queue: wgpu::Queue,
     depth_tex: Texture,
+    // With stencil for outlines, see `Renderer::new`
+    depth_tex_format: wgpu::TextureFormat,
+    // Used for the screen and the scene cameras.
+    sample_count: u32,
+    supported_sample_counts: Vec<u32>,
+    // Screen is rendered into this one and then resolved into the surface texture when multisampling is on.
+    msaa_tex: Option<Texture>,
+    mip_blit_shader: wgpu::ShaderModule,
+    clear_color: wgpu::Color,
+    // Acquired on the first screen pass of the frame, see `present`
+    surface_tex: RefCell<Option<wgpu::SurfaceTexture>>,
 }
 
 impl<'a> Renderer<'a> {
-    // TODO Configurable?
-    const DEPTH_TEX_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // Scene is rendered in linear HDR, post-processing then brings it to the surface format.
+    const HDR_TEX_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
     pub fn surface_texture_format(&self) -> wgpu::TextureFormat {
         self.surface_cfg.format
     }
 
     pub fn depth_texture_format(&self) -> wgpu::TextureFormat {
-        Self::DEPTH_TEX_FORMAT
+        self.depth_tex_format
+    }
+
+    pub fn hdr_texture_format(&self) -> wgpu::TextureFormat {
+        Self::HDR_TEX_FORMAT
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+    }
+
+    pub fn supported_sample_counts(&self) -> &[u32] {
+        &self.supported_sample_counts
     }
 
     pub fn surface_size(&self) -> SurfaceSize {
@@ -48,6 +124,10 @@ impl<'a> Renderer<'a> {
         &self.queue
     }
 
+    pub fn mip_blit_shader(&self) -> &wgpu::ShaderModule {
+        &self.mip_blit_shader
+    }
+
     pub async fn new(window: Arc<winit::window::Window>) -> Renderer<'a> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
@@ -67,12 +147,34 @@ impl<'a> Renderer<'a> {
             .unwrap();
         let adapter_name = adapter.get_info().name.clone();
 
+        // Needed for sample counts other than 1 and 4
+        let adapter_specific_formats = adapter
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let mut features_wgpu = wgpu::FeaturesWGPU::POLYGON_MODE_LINE;
+        if adapter_specific_formats {
+            features_wgpu |= wgpu::FeaturesWGPU::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        }
+
+        // For compressed skyboxes and the full precision depth buffer, optional
+        let features_webgpu = adapter.features().features_webgpu
+            & (wgpu::FeaturesWebGPU::TEXTURE_COMPRESSION_BC
+                | wgpu::FeaturesWebGPU::DEPTH32FLOAT_STENCIL8);
+
+        // Reversed Z gets the most out of a float depth buffer, the packed one is the fallback.
+        let depth_tex_format =
+            if features_webgpu.contains(wgpu::FeaturesWebGPU::DEPTH32FLOAT_STENCIL8) {
+                wgpu::TextureFormat::Depth32FloatStencil8
+            } else {
+                wgpu::TextureFormat::Depth24PlusStencil8
+            };
+
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
                 required_features: wgpu::Features {
-                    features_wgpu: wgpu::FeaturesWGPU::POLYGON_MODE_LINE,
-                    features_webgpu: wgpu::FeaturesWebGPU::empty(),
+                    features_wgpu,
+                    features_webgpu,
                 },
                 required_limits: wgpu::Limits::default(),
                 memory_hints: wgpu::MemoryHints::default(),
@@ -106,7 +208,33 @@ impl<'a> Renderer<'a> {
         };
         surface.configure(&device, &surface_cfg);
 
-        let depth_tex = Texture::new_depth(&device, Self::DEPTH_TEX_FORMAT, surface_size.into());
+        let supported_sample_counts = [1, 2, 4, 8]
+            .into_iter()
+            .filter(|&count| {
+                [surface_cfg.format, Self::HDR_TEX_FORMAT, depth_tex_format]
+                    .into_iter()
+                    .all(|format| {
+                        let features = if adapter_specific_formats {
+                            adapter.get_texture_format_features(format)
+                        } else {
+                            format.guaranteed_format_features(device.features())
+                        };
+                        features.flags.sample_count_supported(count)
+                    })
+            })
+            .collect();
+
+        let depth_tex = Texture::new_depth(&device, depth_tex_format, surface_size.into(), 1);
+
+        let mip_blit_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                file::read_string_asset("mip-blit.wgsl")
+                    .await
+                    .unwrap()
+                    .into(),
+            ),
+        });
 
         Self {
             surface_cfg,
@@ -114,22 +242,49 @@ impl<'a> Renderer<'a> {
             device,
             queue,
             depth_tex,
+            depth_tex_format,
             adapter_name,
+            sample_count: 1,
+            supported_sample_counts,
+            msaa_tex: None,
+            mip_blit_shader,
+            clear_color: wgpu::Color::BLACK,
+            surface_tex: RefCell::new(None),
         }
     }
 
+    // Doesn't update anything created by the renderer users, e.g. render targets and pipelines.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.rebuild_screen_textures();
+    }
+
     pub fn update(&mut self, new_surface_size: Option<SurfaceSize>) {
-        if let Some(SurfaceSize { width, height }) = new_surface_size {
-            if width > 0 && height > 0 {
-                self.surface_cfg.width = width;
-                self.surface_cfg.height = height;
-                self.surface.configure(&self.device, &self.surface_cfg);
-                self.depth_tex =
-                    Texture::new_depth(&self.device, Self::DEPTH_TEX_FORMAT, (width, height));
-            }
+        if let Some(SurfaceSize { width, height }) = new_surface_size
+            && width > 0
+            && height > 0
+        {
+            self.surface_cfg.width = width;
+            self.surface_cfg.height = height;
+            self.surface.configure(&self.device, &self.surface_cfg);
+            self.rebuild_screen_textures();
         }
     }
 
+    fn rebuild_screen_textures(&mut self) {
+        let size = (self.surface_cfg.width, self.surface_cfg.height);
+        self.depth_tex =
+            Texture::new_depth(&self.device, self.depth_tex_format, size, self.sample_count);
+        self.msaa_tex = (self.sample_count > 1).then(|| {
+            Texture::new_render_attachment(
+                &self.device,
+                self.surface_cfg.format,
+                size,
+                self.sample_count,
+            )
+        });
+    }
+
     pub fn build_render_bundle(
         &self,
         mesh: &Mesh,
@@ -145,32 +300,46 @@ impl<'a> Renderer<'a> {
     pub fn render_pass(
         &self,
         bundles: &[wgpu::RenderBundle],
-        target: Option<&RenderTarget>,
+        params: RenderPassParams,
         // TODO More elegant.
         // Currently I cannot win the borrow checker and make Renderer NOT reference the Ui in some way.
         // Tried adding a lambda param here with a render pass param to allow "additional rendering"
         // but using the Ui in that lambda on the call site hits the lifetime wall.
         ui: Option<&mut Ui>,
     ) {
-        let surface_tex = target.is_none().then(|| {
-            self.surface
-                .get_current_texture()
-                // TODO Fix, this breaks on Linux when resizing.
-                .expect("Missing surface texture")
-        });
-        let surface_tex_view = surface_tex.as_ref().map(|t| {
-            t.texture
+        let target = params.target;
+        let surface_tex_view = target.is_none().then(|| {
+            self.surface_tex
+                .borrow_mut()
+                .get_or_insert_with(|| {
+                    self.surface
+                        .get_current_texture()
+                        // TODO Fix, this breaks on Linux when resizing.
+                        .expect("Missing surface texture")
+                })
+                .texture
                 .create_view(&wgpu::TextureViewDescriptor::default())
         });
 
+        // Resolved (single-sampled) view and the multisampled one if multisampling is on
+        let (view, msaa_view) = match target {
+            Some(t) => (t.color_tex().view(), t.msaa_color_tex().map(|t| t.view())),
+            None => (
+                surface_tex_view.as_ref().unwrap(),
+                self.msaa_tex.as_ref().map(|t| t.view()),
+            ),
+        };
+
+        // Multisampled contents must survive if a later pass is going to load them
         let color_attachment = Some(wgpu::RenderPassColorAttachment {
-            view: target
-                .map(|t| t.color_tex().view())
-                .or(surface_tex_view.as_ref())
-                .unwrap(),
-            resolve_target: None,
+            view: msaa_view.unwrap_or(view),
+            resolve_target: msaa_view.is_some().then_some(view),
             ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                load: if params.keep_color {
+                    wgpu::LoadOp::Load
+                } else {
+                    wgpu::LoadOp::Clear(params.clear_color.unwrap_or(self.clear_color))
+                },
                 store: wgpu::StoreOp::Store,
             },
         });
@@ -180,12 +349,21 @@ impl<'a> Renderer<'a> {
                 .map(|t| t.depth_tex().view())
                 .unwrap_or(self.depth_tex.view()),
             depth_ops: Some(wgpu::Operations {
-                load: wgpu::LoadOp::Clear(1.0),
+                // Reversed Z, the far plane is at 0
+                load: wgpu::LoadOp::Clear(0.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
                 store: wgpu::StoreOp::Store,
             }),
-            stencil_ops: None,
         });
 
+        let (width, height) = target
+            .map(|t| t.size())
+            .unwrap_or((self.surface_cfg.width, self.surface_cfg.height));
+        let vp = params.viewport;
+
         let cmd_buf = {
             let mut encoder =
                 self.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -199,6 +377,14 @@ impl<'a> Renderer<'a> {
                     timestamp_writes: None,
                 });
 
+                pass.set_viewport(
+                    vp.x * width as f32,
+                    vp.y * height as f32,
+                    vp.width * width as f32,
+                    vp.height * height as f32,
+                    0.0,
+                    1.0,
+                );
                 pass.execute_bundles(bundles.iter());
                 if let Some(ui) = ui {
                     ui.draw(self, &mut pass);
@@ -209,8 +395,67 @@ impl<'a> Renderer<'a> {
         };
 
         self.queue.submit(Some(cmd_buf));
+    }
+
+    // Reads a region of a single-sampled texture, waiting for the GPU. Rows are tightly packed.
+    pub fn read_texture(
+        &self,
+        texture: &Texture,
+        origin: (u32, u32),
+        size: TextureSize,
+    ) -> Vec<u8> {
+        let texel_size = texture.format().block_copy_size(None).unwrap();
+        let row_size = size.0 * texel_size;
+        let padded_row_size = row_size.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row_size * size.1) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
 
-        if let Some(t) = surface_tex {
+        let mut encoder =
+            self.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: texture.texture(),
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.0,
+                    y: origin.1,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_size),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::PollType::Wait).unwrap();
+        let data = slice.get_mapped_range();
+        data.chunks(padded_row_size as usize)
+            .flat_map(|row| &row[..row_size as usize])
+            .copied()
+            .collect()
+    }
+
+    // Shows everything rendered to the screen during the frame.
+    pub fn present(&self) {
+        if let Some(t) = self.surface_tex.borrow_mut().take() {
             t.present()
         }
     }
@@ -239,16 +484,39 @@ impl<'a> Renderer<'a> {
             label: None,
         });
 
-        let group = self.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &layout,
+        let group = self.new_uniform_bind_group_for_buffer(&layout, &buffer);
+
+        (layout, group, buffer)
+    }
+
+    // With a layout from `new_uniform_bind_group`, e.g. for another copy of the same uniform.
+    pub fn new_uniform_bind_group_with_layout(
+        &self,
+        layout: &wgpu::BindGroupLayout,
+        data: &[u8],
+    ) -> (wgpu::BindGroup, wgpu::Buffer) {
+        let buffer = self.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: data,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let group = self.new_uniform_bind_group_for_buffer(layout, &buffer);
+        (group, buffer)
+    }
+
+    fn new_uniform_bind_group_for_buffer(
+        &self,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        self.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: buffer.as_entire_binding(),
             }],
             label: None,
-        });
-
-        (layout, group, buffer)
+        })
     }
 
     pub fn new_texture_bind_group(
@@ -317,9 +585,18 @@ impl<'a> Renderer<'a> {
                 entry_point: Some("fs_main"),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: self.surface_cfg.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
+                    format: params.color_format,
+                    // Integer formats can't be blended
+                    blend: (!matches!(
+                        params.color_format.sample_type(None, None),
+                        Some(wgpu::TextureSampleType::Uint | wgpu::TextureSampleType::Sint)
+                    ))
+                    .then_some(wgpu::BlendState::REPLACE),
+                    write_mask: if params.stencil == StencilMode::Mark {
+                        wgpu::ColorWrites::empty()
+                    } else {
+                        wgpu::ColorWrites::ALL
+                    },
                 })],
             }),
             primitive: wgpu::PrimitiveState {
@@ -339,19 +616,24 @@ impl<'a> Renderer<'a> {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: if params.depth_enabled {
+            depth_stencil: if params.depth_enabled || params.stencil != StencilMode::Off {
                 Some(wgpu::DepthStencilState {
-                    format: Self::DEPTH_TEX_FORMAT,
-                    depth_write_enabled: params.depth_write,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
+                    format: self.depth_tex_format,
+                    depth_write_enabled: params.depth_enabled && params.depth_write,
+                    // Reversed Z. Equal lets full-screen quads at the far plane pass.
+                    depth_compare: if params.depth_enabled {
+                        wgpu::CompareFunction::GreaterEqual
+                    } else {
+                        wgpu::CompareFunction::Always
+                    },
+                    stencil: stencil_state(params.stencil),
                     bias: wgpu::DepthBiasState::default(),
                 })
             } else {
                 None
             },
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: params.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -360,15 +642,16 @@ impl<'a> Renderer<'a> {
         })
     }
 
-    fn new_bundle_encoder(&self, target: Option<&RenderTarget>) -> wgpu::RenderBundleEncoder {
+    fn new_bundle_encoder(&self, target: Option<&RenderTarget>) -> wgpu::RenderBundleEncoder<'_> {
         let color_format = target.map_or(self.surface_texture_format(), |t| t.color_tex().format());
         let depth_format = target.map_or(self.depth_texture_format(), |t| t.depth_tex().format());
+        let sample_count = target.map_or(self.sample_count, |t| t.sample_count());
 
         self.device
             .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
                 label: None,
                 multiview: None,
-                sample_count: 1,
+                sample_count,
                 color_formats: &[Some(color_format)],
                 depth_stencil: Some(wgpu::RenderBundleDepthStencil {
                     format: depth_format,
@@ -379,6 +662,32 @@ impl<'a> Renderer<'a> {
     }
 }
 
+// Render bundles can't set the stencil reference, so marking increments and the test is against
+// the default reference of 0.
+fn stencil_state(mode: StencilMode) -> wgpu::StencilState {
+    let face = match mode {
+        StencilMode::Off => return wgpu::StencilState::default(),
+        StencilMode::Mark => wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Always,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::IncrementClamp,
+        },
+        StencilMode::Unmarked => wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Keep,
+        },
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask: 0xff,
+    }
+}
+
 impl Deref for Renderer<'_> {
     type Target = wgpu::Device;
 
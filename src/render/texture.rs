@@ -2,11 +2,31 @@ use anyhow::*;
 use image::GenericImageView;
 use wgpu::util::{DeviceExt, TextureDataOrder};
 
-use super::Renderer;
+use super::{RenderPipelineParams, Renderer, StencilMode};
 
 pub type TextureSize = (u32, u32);
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerParams {
+    // Used for mips as well
+    pub filter: wgpu::FilterMode,
+    pub address_mode: wgpu::AddressMode,
+    // 1 means no anisotropic filtering. Only has effect with linear filtering.
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerParams {
+    fn default() -> Self {
+        Self {
+            filter: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            anisotropy: 1,
+        }
+    }
+}
+
 pub struct Texture {
+    texture: wgpu::Texture,
     view: wgpu::TextureView,
     sampler: wgpu::Sampler,
     format: wgpu::TextureFormat,
@@ -19,21 +39,24 @@ impl Texture {
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
         size: TextureSize,
+        sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: size.0,
             height: size.1,
             depth_or_array_layers: 1,
         };
-        let texture = new_empty_texture_2d(device, size, format);
+        let texture = new_empty_texture_2d(device, size, format, sample_count);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&new_sampler_descriptor(
             wgpu::FilterMode::Nearest,
             wgpu::FilterMode::Nearest,
-            Some(wgpu::CompareFunction::LessEqual),
+            // Reversed Z
+            Some(wgpu::CompareFunction::GreaterEqual),
         ));
 
         Self {
+            texture,
             view,
             sampler,
             format,
@@ -44,27 +67,34 @@ impl Texture {
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
         size: TextureSize,
+        sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: size.0,
             height: size.1,
             depth_or_array_layers: 1,
         };
-        let texture = new_empty_texture_2d(device, size, format);
+        let texture = new_empty_texture_2d(device, size, format, sample_count);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Linear so that post-processing can resample, e.g. when downscaling
         let sampler = device.create_sampler(&new_sampler_descriptor(
-            wgpu::FilterMode::Nearest,
+            wgpu::FilterMode::Linear,
             wgpu::FilterMode::Nearest,
             None,
         ));
 
         Self {
+            texture,
             view,
             sampler,
             format,
         }
     }
 
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
     pub fn view(&self) -> &wgpu::TextureView {
         &self.view
     }
@@ -77,7 +107,8 @@ impl Texture {
         self.format
     }
 
-    pub fn new_2d(rr: &Renderer, data: &[u8]) -> Result<Self> {
+    // Mips are generated on the GPU.
+    pub fn new_2d(rr: &Renderer, data: &[u8], sampler_params: SamplerParams) -> Result<Self> {
         let img = image::load_from_memory(data)?;
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
@@ -87,82 +118,401 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let texture = rr.create_texture_with_data(
-            rr.queue(),
-            &wgpu::TextureDescriptor {
-                label: None,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: Self::DEFAULT_FORMAT,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            },
-            TextureDataOrder::default(),
+        let texture = rr.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEFAULT_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        rr.queue().write_texture(
+            texture.as_image_copy(),
             &rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: None,
+            },
+            size,
         );
+        generate_mips(rr, &texture);
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = rr.create_sampler(&new_sampler_descriptor(
-            wgpu::FilterMode::Nearest,
-            wgpu::FilterMode::Nearest,
-            None,
-        ));
+        let anisotropy = if sampler_params.filter == wgpu::FilterMode::Linear {
+            sampler_params.anisotropy.clamp(1, 16)
+        } else {
+            1
+        };
+        let sampler = rr.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: sampler_params.address_mode,
+            address_mode_v: sampler_params.address_mode,
+            address_mode_w: sampler_params.address_mode,
+            anisotropy_clamp: anisotropy,
+            ..new_sampler_descriptor(sampler_params.filter, sampler_params.filter, None)
+        });
 
         Ok(Self {
+            texture,
             view,
             sampler,
             format: Self::DEFAULT_FORMAT,
         })
     }
 
-    pub fn new_cube(rr: &Renderer, data: &[u8]) -> Result<Self> {
-        let image = ddsfile::Dds::read(&mut std::io::Cursor::new(&data))?;
+    // Size, mips and format come from the header, the file must contain all six faces.
+    pub fn new_cube_from_dds(rr: &Renderer, data: &[u8]) -> Result<Self> {
+        let dds = ddsfile::Dds::read(&mut std::io::Cursor::new(data))?;
+        let layers = dds_cube_layers(&dds);
+        ensure!(
+            layers == 6,
+            "DDS is not a single cubemap, has {layers} cube faces"
+        );
 
-        let size = wgpu::Extent3d {
-            width: 128,
-            height: 128,
-            depth_or_array_layers: 6,
-        };
+        let format = if let Some(format) = dds.get_dxgi_format() {
+            dxgi_to_wgpu_format(format)
+        } else if let Some(format) = dds.get_d3d_format() {
+            d3d_to_wgpu_format(format)
+        } else {
+            None
+        }
+        .ok_or_else(|| anyhow!("Unsupported DDS format"))?;
 
-        let layer_size = wgpu::Extent3d {
-            depth_or_array_layers: 1,
-            ..size
-        };
+        new_cube_with_data(
+            rr,
+            format,
+            dds.get_width(),
+            dds.get_num_mipmap_levels().max(1),
+            // DDS stores each face with all its mips before the next face
+            TextureDataOrder::LayerMajor,
+            &dds.data,
+        )
+    }
 
-        let texture = rr.create_texture_with_data(
-            rr.queue(),
-            &wgpu::TextureDescriptor {
-                size,
-                mip_level_count: layer_size.max_mips(wgpu::TextureDimension::D2),
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: Self::DEFAULT_FORMAT,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                label: None,
-                view_formats: &[],
-            },
-            TextureDataOrder::default(),
-            &image.data,
+    pub fn new_cube_from_ktx2(rr: &Renderer, data: &[u8]) -> Result<Self> {
+        let reader = ktx2::Reader::new(data)?;
+        let header = reader.header();
+        ensure!(
+            header.face_count == 6,
+            "KTX2 is not a cubemap, has {} faces",
+            header.face_count
         );
+        ensure!(
+            header.supercompression_scheme.is_none(),
+            "Supercompressed KTX2 is not supported"
+        );
+        let format = header
+            .format
+            .and_then(ktx2_to_wgpu_format)
+            .ok_or_else(|| anyhow!("Unsupported KTX2 format {:?}", header.format))?;
+
+        // KTX2 stores all faces of a mip before the next mip
+        let data = reader
+            .levels()
+            .flat_map(|level| level.data)
+            .copied()
+            .collect::<Vec<_>>();
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        new_cube_with_data(
+            rr,
+            format,
+            header.pixel_width,
+            header.level_count.max(1),
+            TextureDataOrder::MipMajor,
+            &data,
+        )
+    }
+
+    // Faces are in +X, -X, +Y, -Y, +Z, -Z order, must be square and of the same size.
+    pub fn new_cube_from_faces(rr: &Renderer, faces: [&[u8]; 6]) -> Result<Self> {
+        let (face_size, data) = decode_cube_faces(faces)?;
+        new_cube_with_mips(rr, Self::DEFAULT_FORMAT, face_size, &data)
+    }
+
+    // Projects a lat-long image (e.g. an .hdr panorama) onto the faces, keeping the HDR range.
+    pub fn new_cube_from_equirect(rr: &Renderer, data: &[u8]) -> Result<Self> {
+        let img = image::load_from_memory(data)?.to_rgba32f();
+        let face_size = (img.width() / 4).max(1);
+
+        let mut data = Vec::with_capacity((face_size * face_size * 6 * 4 * 2) as usize);
+        for face in 0..6 {
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    let u = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                    let v = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                    let dir = cube_face_dir(face, u, v);
+                    for c in sample_equirect(&img, dir) {
+                        data.extend_from_slice(&half::f16::from_f32(c).to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        new_cube_with_mips(rr, wgpu::TextureFormat::Rgba16Float, face_size, &data)
+    }
+}
+
+fn new_cube_with_data(
+    rr: &Renderer,
+    format: wgpu::TextureFormat,
+    face_size: u32,
+    mip_level_count: u32,
+    order: TextureDataOrder,
+    data: &[u8],
+) -> Result<Texture> {
+    ensure!(
+        rr.features().contains(format.required_features()),
+        "Texture format {format:?} is not supported by the adapter"
+    );
+
+    let texture = rr.create_texture_with_data(
+        rr.queue(),
+        &wgpu::TextureDescriptor {
+            size: cube_size(face_size),
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             label: None,
-            dimension: Some(wgpu::TextureViewDimension::Cube),
-            ..wgpu::TextureViewDescriptor::default()
-        });
+            view_formats: &[],
+        },
+        order,
+        data,
+    );
 
-        let sampler = rr.create_sampler(&new_sampler_descriptor(
-            wgpu::FilterMode::Linear,
-            wgpu::FilterMode::Linear,
-            None,
-        ));
+    Ok(new_cube_texture(rr, &texture))
+}
 
-        Ok(Self {
-            view,
-            sampler,
-            format: Self::DEFAULT_FORMAT,
-        })
+// Face size and the faces' pixels one after another.
+fn decode_cube_faces(faces: [&[u8]; 6]) -> Result<(u32, Vec<u8>)> {
+    let faces = faces
+        .iter()
+        .map(|data| Ok(image::load_from_memory(data)?.to_rgba8()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let face_size = faces[0].width();
+    ensure!(
+        faces
+            .iter()
+            .all(|f| f.width() == face_size && f.height() == face_size),
+        "Cubemap faces must be square and of the same size"
+    );
+
+    let data = faces
+        .iter()
+        .flat_map(|f| f.as_raw())
+        .copied()
+        .collect::<Vec<_>>();
+    Ok((face_size, data))
+}
+
+// `data` holds the first mip of each face, the rest are generated.
+fn new_cube_with_mips(
+    rr: &Renderer,
+    format: wgpu::TextureFormat,
+    face_size: u32,
+    data: &[u8],
+) -> Result<Texture> {
+    let texture = new_cube_first_mips(rr, rr.queue(), format, face_size, data);
+    generate_mips(rr, &texture);
+
+    Ok(new_cube_texture(rr, &texture))
+}
+
+// Has room for all mips but only the first one of each face is written.
+fn new_cube_first_mips(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    face_size: u32,
+    data: &[u8],
+) -> wgpu::Texture {
+    let size = cube_size(face_size);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size,
+        mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        label: None,
+        view_formats: &[],
+    });
+
+    let bytes_per_row = format.block_copy_size(None).unwrap() * face_size;
+    let face_len = (bytes_per_row * face_size) as usize;
+    for (layer, face) in data.chunks_exact(face_len).enumerate() {
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            face,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    texture
+}
+
+fn new_cube_texture(rr: &Renderer, texture: &wgpu::Texture) -> Texture {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: None,
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..wgpu::TextureViewDescriptor::default()
+    });
+
+    let sampler = rr.create_sampler(&new_sampler_descriptor(
+        wgpu::FilterMode::Linear,
+        wgpu::FilterMode::Linear,
+        None,
+    ));
+
+    Texture {
+        texture: texture.clone(),
+        view,
+        sampler,
+        format: texture.format(),
+    }
+}
+
+fn cube_size(face_size: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: face_size,
+        height: face_size,
+        depth_or_array_layers: 6,
+    }
+}
+
+// Direction through a face texel, `u` and `v` in -1..1 with `v` pointing down.
+fn cube_face_dir(face: u32, u: f32, v: f32) -> [f32; 3] {
+    let dir = match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    };
+    let len = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+    dir.map(|c| c / len)
+}
+
+// Bilinear, wrapping horizontally.
+fn sample_equirect(img: &image::Rgba32FImage, dir: [f32; 3]) -> [f32; 4] {
+    let (w, h) = img.dimensions();
+    let u = 0.5 + dir[0].atan2(-dir[2]) / (2.0 * std::f32::consts::PI);
+    let v = dir[1].clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+
+    let x = u * w as f32 - 0.5;
+    let y = (v * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(w as i64) as u32;
+        let y = (y as u32).min(h - 1);
+        img.get_pixel(x, y).0
+    };
+
+    let (t00, t10) = (texel(x0, y0), texel(x0 + 1.0, y0));
+    let (t01, t11) = (texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0));
+    std::array::from_fn(|i| {
+        let top = t00[i] + (t10[i] - t00[i]) * fx;
+        let bottom = t01[i] + (t11[i] - t01[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+// Faces of all the cubes in the file, 0 if it's not a cubemap. Files with the DX10 header, e.g.
+// all BC6H and BC7 ones, count whole cubes in their array size.
+fn dds_cube_layers(dds: &ddsfile::Dds) -> u32 {
+    match &dds.header10 {
+        Some(h10) if h10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE) => h10.array_size * 6,
+        Some(_) => 0,
+        None if dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP) => 6,
+        None => 0,
+    }
+}
+
+fn d3d_to_wgpu_format(format: ddsfile::D3DFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::D3DFormat as D;
+    use wgpu::TextureFormat as F;
+    match format {
+        D::A8R8G8B8 => Some(F::Bgra8UnormSrgb),
+        D::A8B8G8R8 => Some(F::Rgba8UnormSrgb),
+        D::A16B16G16R16F => Some(F::Rgba16Float),
+        D::DXT1 => Some(F::Bc1RgbaUnormSrgb),
+        D::DXT3 => Some(F::Bc2RgbaUnormSrgb),
+        D::DXT5 => Some(F::Bc3RgbaUnormSrgb),
+        _ => None,
+    }
+}
+
+fn dxgi_to_wgpu_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::DxgiFormat as D;
+    use wgpu::TextureFormat as F;
+    match format {
+        D::R8G8B8A8_UNorm => Some(F::Rgba8Unorm),
+        D::R8G8B8A8_UNorm_sRGB => Some(F::Rgba8UnormSrgb),
+        D::B8G8R8A8_UNorm => Some(F::Bgra8Unorm),
+        D::B8G8R8A8_UNorm_sRGB => Some(F::Bgra8UnormSrgb),
+        D::R16G16B16A16_Float => Some(F::Rgba16Float),
+        D::BC1_UNorm => Some(F::Bc1RgbaUnorm),
+        D::BC1_UNorm_sRGB => Some(F::Bc1RgbaUnormSrgb),
+        D::BC2_UNorm => Some(F::Bc2RgbaUnorm),
+        D::BC2_UNorm_sRGB => Some(F::Bc2RgbaUnormSrgb),
+        D::BC3_UNorm => Some(F::Bc3RgbaUnorm),
+        D::BC3_UNorm_sRGB => Some(F::Bc3RgbaUnormSrgb),
+        D::BC6H_UF16 => Some(F::Bc6hRgbUfloat),
+        D::BC6H_SF16 => Some(F::Bc6hRgbFloat),
+        D::BC7_UNorm => Some(F::Bc7RgbaUnorm),
+        D::BC7_UNorm_sRGB => Some(F::Bc7RgbaUnormSrgb),
+        _ => None,
+    }
+}
+
+fn ktx2_to_wgpu_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as F;
+    match format {
+        K::R8G8B8A8_UNORM => Some(F::Rgba8Unorm),
+        K::R8G8B8A8_SRGB => Some(F::Rgba8UnormSrgb),
+        K::B8G8R8A8_UNORM => Some(F::Bgra8Unorm),
+        K::B8G8R8A8_SRGB => Some(F::Bgra8UnormSrgb),
+        K::R16G16B16A16_SFLOAT => Some(F::Rgba16Float),
+        K::BC1_RGBA_UNORM_BLOCK => Some(F::Bc1RgbaUnorm),
+        K::BC1_RGBA_SRGB_BLOCK => Some(F::Bc1RgbaUnormSrgb),
+        K::BC2_UNORM_BLOCK => Some(F::Bc2RgbaUnorm),
+        K::BC2_SRGB_BLOCK => Some(F::Bc2RgbaUnormSrgb),
+        K::BC3_UNORM_BLOCK => Some(F::Bc3RgbaUnorm),
+        K::BC3_SRGB_BLOCK => Some(F::Bc3RgbaUnormSrgb),
+        K::BC6H_UFLOAT_BLOCK => Some(F::Bc6hRgbUfloat),
+        K::BC6H_SFLOAT_BLOCK => Some(F::Bc6hRgbFloat),
+        K::BC7_UNORM_BLOCK => Some(F::Bc7RgbaUnorm),
+        K::BC7_SRGB_BLOCK => Some(F::Bc7RgbaUnormSrgb),
+        _ => None,
     }
 }
 
@@ -186,20 +536,199 @@ fn new_sampler_descriptor<'a>(
     }
 }
 
+// Fills each mip level from the previous one, starting from the first.
+fn generate_mips(rr: &Renderer, texture: &wgpu::Texture) {
+    let bind_group_layout = rr.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: None,
+    });
+
+    let pipeline = rr.new_render_pipeline(RenderPipelineParams {
+        shader_module: rr.mip_blit_shader(),
+        depth_write: false,
+        depth_enabled: false,
+        wireframe: false,
+        stencil: StencilMode::Off,
+        color_format: texture.format(),
+        sample_count: 1,
+        bind_group_layouts: &[&bind_group_layout],
+        vertex_buffer_layouts: &[],
+    });
+
+    let sampler = rr.create_sampler(&new_sampler_descriptor(
+        wgpu::FilterMode::Linear,
+        wgpu::FilterMode::Nearest,
+        None,
+    ));
+
+    let mut encoder = rr.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+    // Each layer (e.g. cubemap face) has its own chain
+    for layer in 0..texture.depth_or_array_layers() {
+        let views = (0..texture.mip_level_count())
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for mip in 1..views.len() {
+            let bind_group = rr.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[mip - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+                label: None,
+            });
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[mip],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+
+    rr.queue().submit(Some(encoder.finish()));
+}
+
 // TODO Move to Renderer?
 fn new_empty_texture_2d(
     device: &wgpu::Device,
     size: wgpu::Extent3d,
     format: wgpu::TextureFormat,
+    sample_count: u32,
 ) -> wgpu::Texture {
     device.create_texture(&wgpu::TextureDescriptor {
         label: None,
         size,
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count,
         dimension: wgpu::TextureDimension::D2,
         format,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        // Single-sampled ones can be read back, e.g. for picking
+        usage: if sample_count == 1 {
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT
+        },
         view_formats: &[],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_from_faces() {
+        let faces = (0..6u8)
+            .map(|i| {
+                let img = image::RgbaImage::from_pixel(4, 4, image::Rgba([i * 40, 0, 0, 255]));
+                let mut png = Vec::new();
+                img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+                    .unwrap();
+                png
+            })
+            .collect::<Vec<_>>();
+        let (face_size, data) =
+            decode_cube_faces(std::array::from_fn(|i| faces[i].as_slice())).unwrap();
+        assert_eq!(face_size, 4);
+        assert_eq!(data.len(), 4 * 4 * 4 * 6);
+
+        // Skipped where there's no adapter at all, not even a software one
+        let instance = wgpu::Instance::default();
+        let Some(adapter) = futures_lite::future::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        )
+        .ok() else {
+            return;
+        };
+        let (device, queue) = futures_lite::future::block_on(
+            adapter.request_device(&wgpu::DeviceDescriptor::default()),
+        )
+        .unwrap();
+
+        // Upload errors panic on the device
+        let texture =
+            new_cube_first_mips(&device, &queue, Texture::DEFAULT_FORMAT, face_size, &data);
+        queue.submit(None);
+        device.poll(wgpu::PollType::Wait).unwrap();
+        assert_eq!(texture.mip_level_count(), 3);
+        assert_eq!(texture.depth_or_array_layers(), 6);
+    }
+
+    #[test]
+    fn dds_cube_with_dx10_header() {
+        let bc7 = |is_cubemap| {
+            let dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+                height: 4,
+                width: 4,
+                depth: None,
+                format: ddsfile::DxgiFormat::BC7_UNorm_sRGB,
+                mipmap_levels: Some(1),
+                array_layers: Some(6),
+                caps2: None,
+                is_cubemap,
+                resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+                alpha_mode: ddsfile::AlphaMode::Unknown,
+            })
+            .unwrap();
+            let mut file = Vec::new();
+            dds.write(&mut file).unwrap();
+            ddsfile::Dds::read(&mut std::io::Cursor::new(&file)).unwrap()
+        };
+
+        let cube = bc7(true);
+        assert_eq!(cube.get_num_array_layers(), 1);
+        assert_eq!(dds_cube_layers(&cube), 6);
+        // One 16-byte block per face
+        assert_eq!(cube.data.len(), 6 * 16);
+
+        assert_eq!(dds_cube_layers(&bc7(false)), 0);
+    }
+}
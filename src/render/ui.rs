@@ -1,10 +1,13 @@
-use imgui::{Context, FontSource, MouseCursor};
+use imgui::{ConfigFlags, Context, FontSource, MouseCursor};
 use imgui_wgpu::RendererConfig;
 use imgui_winit_support::WinitPlatform;
 use std::time::Duration;
-use winit::event::Event;
+use winit::dpi::PhysicalPosition;
+use winit::event::{Event, MouseButton, WindowEvent};
+use winit::keyboard::KeyCode;
 
 use super::renderer::Renderer;
+use crate::input::{InputEvent, Key};
 
 // By the virtue of how the underlying library is implemented, this should be used as a singleton.
 // Or I just don't know Rust enough to make it work :|
@@ -41,16 +44,7 @@ impl Ui {
             }),
         }]);
 
-        let renderer = imgui_wgpu::Renderer::new(
-            &mut context,
-            rr,
-            rr.queue(),
-            RendererConfig {
-                texture_format: rr.surface_texture_format(),
-                depth_format: Some(rr.depth_texture_format()),
-                ..Default::default()
-            },
-        );
+        let renderer = new_renderer(&mut context, rr);
         let last_cursor = None;
 
         Ui {
@@ -84,9 +78,79 @@ impl Ui {
         }
     }
 
+    // Re-creates the underlying renderer to match the renderer's current sample count.
+    pub fn rebuild_renderer(&mut self, rr: &Renderer) {
+        self.renderer = new_renderer(&mut self.context, rr);
+    }
+
+    // Off while the cursor is grabbed, so that windows under it don't take the clicks.
+    pub fn set_mouse_enabled(&mut self, enabled: bool) {
+        self.context
+            .io_mut()
+            .config_flags
+            .set(ConfigFlags::NO_MOUSE, !enabled);
+    }
+
+    pub fn wants_mouse(&self) -> bool {
+        self.context.io().want_capture_mouse
+    }
+
+    pub fn wants_keyboard(&self) -> bool {
+        self.context.io().want_capture_keyboard
+    }
+
+    // Only for changes of the window, input comes via `handle_input_event` so that it can be
+    // replayed.
     pub fn handle_event(&mut self, event: &Event<()>, window: &winit::window::Window) {
-        self.platform
-            .handle_event(self.context.io_mut(), window, event)
+        if let Event::WindowEvent {
+            event: WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. },
+            ..
+        } = event
+        {
+            self.platform
+                .handle_event(self.context.io_mut(), window, event)
+        }
+    }
+
+    // Like the platform does it with the winit events.
+    pub fn handle_input_event(&mut self, event: &InputEvent, window: &winit::window::Window) {
+        let io = self.context.io_mut();
+        match event {
+            InputEvent::Key {
+                key: Key::Keyboard(code),
+                pressed,
+            } => {
+                if let Some(key) = imgui_key(*code) {
+                    io.add_key_event(key, *pressed);
+                }
+            }
+            InputEvent::Key {
+                key: Key::MouseButton(button),
+                pressed,
+            } => {
+                if let Some(button) = imgui_mouse_button(*button) {
+                    io.add_mouse_button_event(button, *pressed);
+                }
+            }
+            InputEvent::Modifiers(modifiers) => {
+                io.add_key_event(imgui::Key::ModCtrl, modifiers.ctrl);
+                io.add_key_event(imgui::Key::ModShift, modifiers.shift);
+                io.add_key_event(imgui::Key::ModAlt, modifiers.alt);
+            }
+            InputEvent::Text(text) => {
+                for ch in text.chars().filter(|&ch| ch != '\u{7f}') {
+                    io.add_input_character(ch);
+                }
+            }
+            InputEvent::Wheel(dy) => io.add_mouse_wheel_event([0.0, *dy]),
+            InputEvent::CursorMoved(x, y) => {
+                let pos =
+                    PhysicalPosition::new(*x as f64, *y as f64).to_logical(window.scale_factor());
+                let pos = self.platform.scale_pos_from_winit(window, pos);
+                io.add_mouse_pos_event([pos.x as f32, pos.y as f32]);
+            }
+            _ => {}
+        }
     }
 
     pub fn draw<'a>(&'a mut self, rr: &Renderer, pass: &mut wgpu::RenderPass<'a>) {
@@ -95,3 +159,117 @@ impl Ui {
             .expect("Rendering UI failed");
     }
 }
+
+fn new_renderer(context: &mut Context, rr: &Renderer) -> imgui_wgpu::Renderer {
+    imgui_wgpu::Renderer::new(
+        context,
+        rr,
+        rr.queue(),
+        RendererConfig {
+            texture_format: rr.surface_texture_format(),
+            depth_format: Some(rr.depth_texture_format()),
+            sample_count: rr.sample_count(),
+            ..Default::default()
+        },
+    )
+}
+
+fn imgui_mouse_button(button: MouseButton) -> Option<imgui::MouseButton> {
+    match button {
+        MouseButton::Left => Some(imgui::MouseButton::Left),
+        MouseButton::Right => Some(imgui::MouseButton::Right),
+        MouseButton::Middle => Some(imgui::MouseButton::Middle),
+        MouseButton::Back => Some(imgui::MouseButton::Extra1),
+        MouseButton::Forward => Some(imgui::MouseButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+// By the physical key, only the ones that can be recorded, see `Key::is_bindable`
+fn imgui_key(code: KeyCode) -> Option<imgui::Key> {
+    use imgui::Key as K;
+    let key = match code {
+        KeyCode::KeyA => K::A,
+        KeyCode::KeyB => K::B,
+        KeyCode::KeyC => K::C,
+        KeyCode::KeyD => K::D,
+        KeyCode::KeyE => K::E,
+        KeyCode::KeyF => K::F,
+        KeyCode::KeyG => K::G,
+        KeyCode::KeyH => K::H,
+        KeyCode::KeyI => K::I,
+        KeyCode::KeyJ => K::J,
+        KeyCode::KeyK => K::K,
+        KeyCode::KeyL => K::L,
+        KeyCode::KeyM => K::M,
+        KeyCode::KeyN => K::N,
+        KeyCode::KeyO => K::O,
+        KeyCode::KeyP => K::P,
+        KeyCode::KeyQ => K::Q,
+        KeyCode::KeyR => K::R,
+        KeyCode::KeyS => K::S,
+        KeyCode::KeyT => K::T,
+        KeyCode::KeyU => K::U,
+        KeyCode::KeyV => K::V,
+        KeyCode::KeyW => K::W,
+        KeyCode::KeyX => K::X,
+        KeyCode::KeyY => K::Y,
+        KeyCode::KeyZ => K::Z,
+        KeyCode::Digit0 => K::Alpha0,
+        KeyCode::Digit1 => K::Alpha1,
+        KeyCode::Digit2 => K::Alpha2,
+        KeyCode::Digit3 => K::Alpha3,
+        KeyCode::Digit4 => K::Alpha4,
+        KeyCode::Digit5 => K::Alpha5,
+        KeyCode::Digit6 => K::Alpha6,
+        KeyCode::Digit7 => K::Alpha7,
+        KeyCode::Digit8 => K::Alpha8,
+        KeyCode::Digit9 => K::Alpha9,
+        KeyCode::F1 => K::F1,
+        KeyCode::F2 => K::F2,
+        KeyCode::F3 => K::F3,
+        KeyCode::F4 => K::F4,
+        KeyCode::F5 => K::F5,
+        KeyCode::F6 => K::F6,
+        KeyCode::F7 => K::F7,
+        KeyCode::F8 => K::F8,
+        KeyCode::F9 => K::F9,
+        KeyCode::F10 => K::F10,
+        KeyCode::F11 => K::F11,
+        KeyCode::F12 => K::F12,
+        KeyCode::Escape => K::Escape,
+        KeyCode::Tab => K::Tab,
+        KeyCode::Space => K::Space,
+        KeyCode::Enter => K::Enter,
+        KeyCode::Backspace => K::Backspace,
+        KeyCode::Delete => K::Delete,
+        KeyCode::Insert => K::Insert,
+        KeyCode::Home => K::Home,
+        KeyCode::End => K::End,
+        KeyCode::PageUp => K::PageUp,
+        KeyCode::PageDown => K::PageDown,
+        KeyCode::ArrowUp => K::UpArrow,
+        KeyCode::ArrowDown => K::DownArrow,
+        KeyCode::ArrowLeft => K::LeftArrow,
+        KeyCode::ArrowRight => K::RightArrow,
+        KeyCode::Minus => K::Minus,
+        KeyCode::Equal => K::Equal,
+        KeyCode::Comma => K::Comma,
+        KeyCode::Period => K::Period,
+        KeyCode::Slash => K::Slash,
+        KeyCode::Backslash => K::Backslash,
+        KeyCode::Semicolon => K::Semicolon,
+        KeyCode::Quote => K::Apostrophe,
+        KeyCode::Backquote => K::GraveAccent,
+        KeyCode::BracketLeft => K::LeftBracket,
+        KeyCode::BracketRight => K::RightBracket,
+        KeyCode::ShiftLeft => K::LeftShift,
+        KeyCode::ShiftRight => K::RightShift,
+        KeyCode::ControlLeft => K::LeftCtrl,
+        KeyCode::ControlRight => K::RightCtrl,
+        KeyCode::AltLeft => K::LeftAlt,
+        KeyCode::AltRight => K::RightAlt,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -2,7 +2,7 @@ use super::materials::Material;
 use crate::file;
 use crate::render::Mesh;
 use crate::render::Renderer;
-use crate::render::Texture;
+use crate::render::{SamplerParams, Texture};
 use futures_lite::future;
 use slotmap::{DefaultKey, SlotMap};
 use std::collections::HashMap;
@@ -64,17 +64,50 @@ impl Assets {
         self.textures.get(handle).unwrap()
     }
 
-    pub fn add_2d_texture_from_file(&mut self, rr: &Renderer, path: &str) -> TextureHandle {
-        self.add_texture(path, || {
+    // The same file with different sampling ends up as separate textures.
+    pub fn add_2d_texture_from_file(
+        &mut self,
+        rr: &Renderer,
+        path: &str,
+        sampler: SamplerParams,
+    ) -> TextureHandle {
+        self.add_texture(&format!("{path}{sampler:?}"), || {
             let data = future::block_on(file::read_binary_asset(path)).unwrap();
-            Texture::new_2d(rr, &data).unwrap()
+            Texture::new_2d(rr, &data, sampler).unwrap()
         })
     }
 
+    // Format is picked by extension: .dds and .ktx2 contain all faces, .hdr is an equirectangular
+    // panorama.
     pub fn add_cube_texture_from_file(&mut self, rr: &Renderer, path: &str) -> TextureHandle {
         self.add_texture(path, || {
             let data = future::block_on(file::read_binary_asset(path)).unwrap();
-            Texture::new_cube(rr, &data).unwrap()
+            let ext = std::path::Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or_default()
+                .to_lowercase();
+            match ext.as_str() {
+                "dds" => Texture::new_cube_from_dds(rr, &data),
+                "ktx2" => Texture::new_cube_from_ktx2(rr, &data),
+                "hdr" => Texture::new_cube_from_equirect(rr, &data),
+                _ => Err(anyhow::anyhow!("Unsupported cubemap file {path}")),
+            }
+            .unwrap()
+        })
+    }
+
+    // Faces are in +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn add_cube_texture_from_faces(
+        &mut self,
+        rr: &Renderer,
+        paths: &[String; 6],
+    ) -> TextureHandle {
+        self.add_texture(&paths.join(";"), || {
+            let data = paths
+                .each_ref()
+                .map(|path| future::block_on(file::read_binary_asset(path)).unwrap());
+            Texture::new_cube_from_faces(rr, data.each_ref().map(|d| d.as_slice())).unwrap()
         })
     }
 
@@ -89,6 +122,14 @@ impl Assets {
         &self.materials[handle]
     }
 
+    pub fn material_mut(&mut self, handle: MaterialHandle) -> &mut Material {
+        &mut self.materials[handle]
+    }
+
+    pub fn materials_mut(&mut self) -> impl Iterator<Item = &mut Material> {
+        self.materials.values_mut()
+    }
+
     pub fn add_material(&mut self, material: Material) -> MaterialHandle {
         self.materials.insert(material)
     }
@@ -100,6 +141,18 @@ impl Assets {
 
 async fn new_shader_module(device: &wgpu::Device, src_file_path: &str) -> wgpu::ShaderModule {
     let src = file::read_string_asset(src_file_path).await.unwrap();
+    // Lines like `#include "fog.wgsl"` are replaced with that file, for code shared by shaders
+    let mut lines = Vec::new();
+    for line in src.lines() {
+        match line
+            .strip_prefix("#include \"")
+            .and_then(|rest| rest.strip_suffix('"'))
+        {
+            Some(path) => lines.push(file::read_string_asset(path).await.unwrap()),
+            None => lines.push(line.to_string()),
+        }
+    }
+    let src = lines.join("\n");
     device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: None,
         source: wgpu::ShaderSource::Wgsl(src.into()),
@@ -1,36 +1,85 @@
-use crate::math::{Mat4, Perspective3};
-use crate::render::RenderTarget;
+use crate::math::{Mat4, Orthographic3, Perspective3, Vec2, Vec3, to_point3};
+use crate::render::{RenderTarget, Viewport};
+
+use super::transform::Transform;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    // Vertical field of view in degrees
+    Perspective { fov: f32 },
+    // Visible height in world units, width follows the aspect
+    Orthographic { height: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraParams {
+    pub projection: Projection,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Default for CameraParams {
+    fn default() -> Self {
+        Self {
+            projection: Projection::Perspective { fov: 45.0 },
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+}
 
 pub struct Camera {
     aspect: f32,
-    znear: f32,
-    zfar: f32,
-    fov: f32,
+    params: CameraParams,
     proj_matrix: Mat4,
     // Tags to render via this camera
     render_tags: u32,
+    // Cameras with lower priority render first
+    priority: i32,
+    // Part of the target (or the screen) to render into
+    viewport: Viewport,
     // TODO Move out of `Camera`?
+    // Cameras without own target render into the post-processor's input and end up on the screen.
     target: Option<RenderTarget>,
+    // Own target follows the screen size times this, otherwise it keeps its size
+    target_scale: Option<f32>,
 }
 
 impl Camera {
-    pub fn new(aspect: f32, render_tags: u32, target: Option<RenderTarget>) -> Self {
-        let znear = 0.1;
-        let zfar = 100.0;
-        let fov = 45.0;
-        let proj_matrix = Perspective3::new(aspect, fov, znear, zfar).to_homogeneous();
-
+    pub fn new(
+        aspect: f32,
+        params: CameraParams,
+        render_tags: u32,
+        target: Option<RenderTarget>,
+    ) -> Self {
         Self {
             aspect,
-            znear,
-            zfar,
-            fov,
-            proj_matrix,
+            params,
+            proj_matrix: new_proj_matrix(aspect, &params),
             render_tags,
+            priority: 0,
+            viewport: Viewport::default(),
             target,
+            target_scale: None,
         }
     }
 
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
     pub fn target(&self) -> &Option<RenderTarget> {
         &self.target
     }
@@ -39,17 +88,63 @@ impl Camera {
         self.target.as_mut()
     }
 
+    pub fn target_scale(&self) -> Option<f32> {
+        self.target_scale
+    }
+
+    pub fn set_target_scale(&mut self, scale: Option<f32>) {
+        self.target_scale = scale;
+    }
+
     pub fn should_render(&self, tags: u32) -> bool {
         self.render_tags & tags == tags
     }
 
+    // OpenGL-style, with -1..1 depth. Converted to wgpu's (reversed) depth range when uploaded.
     pub fn proj_matrix(&self) -> Mat4 {
         self.proj_matrix
     }
 
+    // Points on the near and far planes under a window position in pixels, in world space.
+    // Both planes so that it also works with orthographic projection.
+    pub fn window_to_world(&self, tr: &Transform, pos: Vec2, window_size: Vec2) -> (Vec3, Vec3) {
+        // Relative to the camera viewport
+        let vp = self.viewport;
+        let vp_pos = (pos.component_div(&window_size) - Vec2::new(vp.x, vp.y))
+            .component_div(&Vec2::new(vp.width, vp.height));
+        // Normalized device coordinates (-1..1)
+        let mut ndc_pos = vp_pos * 2.0 - Vec2::from_element(1.0);
+        // Needed for some reason... Is there a bug somewhere that gets compensated by this, or is wgpu
+        // NDC origin in the lower left window corner?
+        ndc_pos.y *= -1.0;
+        let m = tr.matrix() * self.proj_matrix.try_inverse().unwrap();
+        let unproject = |z: f32| {
+            m.transform_point(&to_point3(Vec3::new(ndc_pos.x, ndc_pos.y, z)))
+                .coords
+        };
+        (unproject(-1.0), unproject(1.0))
+    }
+
+    pub fn set_params(&mut self, params: CameraParams) {
+        self.params = params;
+        self.proj_matrix = new_proj_matrix(self.aspect, &self.params);
+    }
+
     pub fn set_aspect(&mut self, aspect: f32) {
         self.aspect = aspect;
-        self.proj_matrix =
-            Perspective3::new(self.aspect, self.fov, self.znear, self.zfar).to_homogeneous();
+        self.proj_matrix = new_proj_matrix(self.aspect, &self.params);
+    }
+}
+
+fn new_proj_matrix(aspect: f32, params: &CameraParams) -> Mat4 {
+    match params.projection {
+        Projection::Perspective { fov } => {
+            Perspective3::new(aspect, fov.to_radians(), params.znear, params.zfar).to_homogeneous()
+        }
+        Projection::Orthographic { height } => {
+            let (half_w, half_h) = (height * aspect * 0.5, height * 0.5);
+            Orthographic3::new(-half_w, half_w, -half_h, half_h, params.znear, params.zfar)
+                .to_homogeneous()
+        }
     }
 }
@@ -0,0 +1,485 @@
+use hecs::{Entity, World};
+use rapier3d::prelude::LockedAxes;
+
+use crate::math::{UnitQuat, Vec3};
+use crate::physics::Physics;
+use crate::render::Renderer;
+use crate::scene::materials::MaterialParams;
+use crate::scene::{Assets, MeshHandle, materials};
+
+use super::{
+    Camera, CameraTexture, Environment, Gizmo, GizmoHandle, Grab, Hud, Material, Mesh, Name,
+    Outliner, Picker, Player, PlayerAvatar, PlayerTarget, PostProcessor, RENDER_TAG_SCENE,
+    RenderOrder, RenderTags, RigidBody, RigidBodyParams, Selected, Selection, Transform,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransformParams {
+    pub pos: Vec3,
+    // Euler angles in degrees, around X, Y and Z in turn
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BodyParams {
+    pub movable: bool,
+    // Continuous collision detection, for fast bodies not to pass through thin ones
+    pub ccd: bool,
+    pub lock_rotations: bool,
+}
+
+// Components that can be added and removed in the editor
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EditorComponent {
+    RigidBody,
+    Mesh,
+    Material,
+    RenderOrder,
+    RenderTags,
+}
+
+impl EditorComponent {
+    pub const ALL: [EditorComponent; 5] = [
+        EditorComponent::RigidBody,
+        EditorComponent::Mesh,
+        EditorComponent::Material,
+        EditorComponent::RenderOrder,
+        EditorComponent::RenderTags,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorComponent::RigidBody => "Body",
+            EditorComponent::Mesh => "Mesh",
+            EditorComponent::Material => "Material",
+            EditorComponent::RenderOrder => "Render order",
+            EditorComponent::RenderTags => "Render tags",
+        }
+    }
+}
+
+// What the editor spawns in front of the player
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Prefab {
+    Crate,
+    // Fixed box
+    Block,
+    Basis,
+}
+
+impl Prefab {
+    pub const ALL: [Prefab; 3] = [Prefab::Crate, Prefab::Block, Prefab::Basis];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Prefab::Crate => "crate",
+            Prefab::Block => "block",
+            Prefab::Basis => "basis",
+        }
+    }
+}
+
+// What the HUD has edited since the last update. Only that is written back, the rest of the
+// snapshot is a frame old, e.g. a falling body would be put back to where it was.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct EditorChanges {
+    pub position: bool,
+    pub rotation: bool,
+    pub scale: bool,
+    pub body: bool,
+    pub material: bool,
+    pub rendering: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EditorCommand {
+    Add(EditorComponent),
+    Remove(EditorComponent),
+    Despawn,
+}
+
+// The inspected entity's components, edited by the HUD and written back on the next update
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inspected {
+    pub entity: Entity,
+    pub label: String,
+    pub transform: Option<TransformParams>,
+    pub body: Option<BodyParams>,
+    pub mesh: bool,
+    pub material: Option<MaterialParams>,
+    pub render_order: Option<i32>,
+    pub render_tags: Option<u32>,
+    // Components of the engine itself, shown by name only
+    pub managed: Vec<&'static str>,
+}
+
+impl Inspected {
+    pub fn has(&self, component: EditorComponent) -> bool {
+        match component {
+            EditorComponent::RigidBody => self.body.is_some(),
+            EditorComponent::Mesh => self.mesh,
+            EditorComponent::Material => self.material.is_some(),
+            EditorComponent::RenderOrder => self.render_order.is_some(),
+            EditorComponent::RenderTags => self.render_tags.is_some(),
+        }
+    }
+
+    // Entities the engine relies on, e.g. the player or the sky, keep their components.
+    pub fn editable(&self) -> bool {
+        self.managed.is_empty() && !matches!(self.material, Some(MaterialParams::Fixed(_)))
+    }
+
+    // Bodies and meshes are placed with the transform.
+    pub fn can_add(&self, component: EditorComponent) -> bool {
+        let placed = matches!(
+            component,
+            EditorComponent::RigidBody | EditorComponent::Mesh
+        );
+        self.editable() && !self.has(component) && (!placed || self.transform.is_some())
+    }
+}
+
+// Lists the entities of the live world and edits the components of one of them. There are no
+// transform hierarchies yet, so the hierarchy is a flat list. A single selected entity gets
+// inspected, and picking one in the list selects it.
+#[derive(Default)]
+pub struct Editor {
+    // All but the gizmo handles, with their labels
+    entities: Vec<(Entity, String)>,
+    pub inspected: Option<Inspected>,
+    // Set by the HUD when the inspected values are edited
+    pub changed: EditorChanges,
+    // Set by the HUD, run on the next update
+    pub command: Option<EditorCommand>,
+    pub spawn: Option<Prefab>,
+    // Inspected from the next update
+    pub pick: Option<Entity>,
+    // The single selected entity when last checked, only a new one gets inspected
+    last_selected: Option<Entity>,
+    // For meshes added in the editor
+    box_mesh: MeshHandle,
+}
+
+impl Editor {
+    const MIN_SCALE: f32 = 0.05;
+    const NEW_MATERIAL_COLOR: Vec3 = Vec3::new(0.8, 0.8, 0.8);
+
+    pub fn new(box_mesh: MeshHandle) -> Self {
+        Self {
+            box_mesh,
+            ..Default::default()
+        }
+    }
+
+    pub fn entities(&self) -> &[(Entity, String)] {
+        &self.entities
+    }
+
+    pub fn update(world: &mut World, physics: &mut Physics, rr: &Renderer, assets: &mut Assets) {
+        // Taken out while the world is being changed
+        let editor_entity = world.query_mut::<&Editor>().into_iter().next().unwrap().0;
+        let mut editor = std::mem::take(world.query_one_mut::<&mut Editor>(editor_entity).unwrap());
+
+        let selected = world
+            .query_mut::<&Selected>()
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        let single_selected = match selected[..] {
+            [e] => Some(e),
+            _ => None,
+        };
+        if single_selected != editor.last_selected {
+            editor.last_selected = single_selected;
+            editor.pick = editor.pick.or(single_selected);
+        }
+
+        let inspected = editor
+            .inspected
+            .take()
+            .filter(|inspected| world.contains(inspected.entity));
+        if let Some(inspected) = &inspected {
+            if editor.changed != EditorChanges::default() {
+                apply(world, physics, rr, assets, inspected, editor.changed);
+            }
+            if let Some(command) = editor.command
+                && inspected.editable()
+            {
+                run(
+                    world,
+                    physics,
+                    rr,
+                    assets,
+                    inspected,
+                    command,
+                    editor.box_mesh,
+                );
+            }
+        }
+        editor.changed = EditorChanges::default();
+        editor.command = None;
+
+        let picked = editor.pick.take().filter(|&e| world.contains(e));
+        let entity = picked.or_else(|| {
+            inspected
+                .as_ref()
+                .map(|inspected| inspected.entity)
+                .filter(|&e| world.contains(e))
+        });
+        editor.inspected = entity.map(|e| inspect(world, physics, assets, e, inspected.as_ref()));
+        // The gizmo then appears on it
+        if let Some(e) = picked
+            && editor
+                .inspected
+                .as_ref()
+                .is_some_and(|i| i.editable() && i.transform.is_some())
+        {
+            Selection::select_only(world, e, rr, assets);
+        }
+
+        let mut entities = world
+            .query_mut::<hecs::Without<(), &GizmoHandle>>()
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        entities.sort_by_key(|e| e.id());
+        editor.entities = entities.into_iter().map(|e| (e, label(world, e))).collect();
+
+        *world.query_one_mut::<&mut Editor>(editor_entity).unwrap() = editor;
+    }
+}
+
+fn label(world: &World, e: Entity) -> String {
+    let managed = is_managed(world, e);
+    let name = world
+        .get::<&Name>(e)
+        .map_or_else(|_| format!("Entity {}", e.id()), |name| name.0.clone());
+    if managed.is_empty() {
+        name
+    } else {
+        format!("{name} ({})", managed.join(", "))
+    }
+}
+
+// Names of the engine's own components on the entity.
+fn is_managed(world: &World, e: Entity) -> Vec<&'static str> {
+    let Ok(entity) = world.entity(e) else {
+        return Vec::new();
+    };
+    [
+        ("Player", entity.has::<Player>()),
+        ("Camera", entity.has::<Camera>()),
+        ("Player target", entity.has::<PlayerTarget>()),
+        ("Player avatar", entity.has::<PlayerAvatar>()),
+        ("Environment", entity.has::<Environment>()),
+        ("Post-processor", entity.has::<PostProcessor>()),
+        ("HUD", entity.has::<Hud>()),
+        ("Selection", entity.has::<Selection>()),
+        ("Outliner", entity.has::<Outliner>()),
+        ("Picker", entity.has::<Picker>()),
+        ("Gizmo", entity.has::<Gizmo>()),
+        ("Editor", entity.has::<Editor>()),
+    ]
+    .into_iter()
+    .filter_map(|(name, has)| has.then_some(name))
+    .collect()
+}
+
+// `prev` keeps the Euler angles from jumping to an equivalent set while they're being edited.
+fn inspect(
+    world: &World,
+    physics: &Physics,
+    assets: &Assets,
+    e: Entity,
+    prev: Option<&Inspected>,
+) -> Inspected {
+    let transform = world.get::<&Transform>(e).ok().map(|tr| {
+        let rotation = tr.rotation();
+        let prev_angles = prev
+            .filter(|prev| prev.entity == e)
+            .and_then(|prev| prev.transform)
+            .map(|prev| prev.rotation)
+            .filter(|&angles| euler_to_rotation(angles).angle_to(&rotation) < 1e-4);
+        TransformParams {
+            pos: tr.position(),
+            rotation: prev_angles.unwrap_or_else(|| {
+                let (x, y, z) = rotation.euler_angles();
+                Vec3::new(x, y, z).map(f32::to_degrees)
+            }),
+            scale: tr.scale(),
+        }
+    });
+
+    let body = world.get::<&RigidBody>(e).ok().map(|body| {
+        let rapier_body = physics.body(body.handle());
+        BodyParams {
+            movable: body.movable,
+            ccd: rapier_body.is_ccd_enabled(),
+            lock_rotations: rapier_body
+                .locked_axes()
+                .contains(LockedAxes::ROTATION_LOCKED),
+        }
+    });
+
+    Inspected {
+        entity: e,
+        label: label(world, e),
+        transform,
+        body,
+        mesh: world.get::<&Mesh>(e).is_ok(),
+        material: world
+            .get::<&Material>(e)
+            .ok()
+            .map(|material| assets.material(material.0).params()),
+        render_order: world.get::<&RenderOrder>(e).ok().map(|order| order.0),
+        render_tags: world.get::<&RenderTags>(e).ok().map(|tags| tags.0),
+        managed: is_managed(world, e),
+    }
+}
+
+// Writes the edited values to the entity's components, the others keep their current ones.
+fn apply(
+    world: &mut World,
+    physics: &mut Physics,
+    rr: &Renderer,
+    assets: &mut Assets,
+    inspected: &Inspected,
+    changed: EditorChanges,
+) {
+    let e = inspected.entity;
+    if let Some(params) = inspected.transform
+        && (changed.position || changed.rotation || changed.scale)
+    {
+        let tr = *world.get::<&Transform>(e).unwrap();
+        RigidBody::set_pose(
+            world,
+            physics,
+            e,
+            if changed.position {
+                params.pos
+            } else {
+                tr.position()
+            },
+            if changed.rotation {
+                euler_to_rotation(params.rotation)
+            } else {
+                tr.rotation()
+            },
+            if changed.scale {
+                params.scale.map(|s| s.max(Editor::MIN_SCALE))
+            } else {
+                tr.scale()
+            },
+        );
+    }
+
+    if let Some(params) = inspected.body
+        && changed.body
+        && let Ok(mut body) = world.get::<&mut RigidBody>(e)
+    {
+        if params.movable != body.movable {
+            // Held bodies are kinematic, the new type applies when released
+            let apply = !physics.body(body.handle()).is_kinematic();
+            body.set_movable(physics, params.movable, apply);
+        }
+        let rapier_body = physics.body_mut(body.handle());
+        rapier_body.enable_ccd(params.ccd);
+        rapier_body.lock_rotations(params.lock_rotations, true);
+    }
+
+    if let Some(params) = inspected.material
+        && changed.material
+        && let Ok(material) = world.get::<&Material>(e)
+    {
+        assets.material_mut(material.0).set_params(rr, params);
+    }
+
+    if let Some(order) = inspected.render_order
+        && changed.rendering
+        && let Ok(mut current) = world.get::<&mut RenderOrder>(e)
+    {
+        current.0 = order;
+    }
+    if let Some(tags) = inspected.render_tags
+        && changed.rendering
+        && let Ok(mut current) = world.get::<&mut RenderTags>(e)
+    {
+        current.0 = tags;
+    }
+}
+
+fn run(
+    world: &mut World,
+    physics: &mut Physics,
+    rr: &Renderer,
+    assets: &mut Assets,
+    inspected: &Inspected,
+    command: EditorCommand,
+    box_mesh: MeshHandle,
+) {
+    let e = inspected.entity;
+    match command {
+        EditorCommand::Add(component) if inspected.can_add(component) => match component {
+            EditorComponent::RigidBody => {
+                let tr = *world.get::<&Transform>(e).unwrap();
+                let body = RigidBody::cuboid(
+                    RigidBodyParams {
+                        pos: tr.position(),
+                        scale: tr.scale(),
+                        movable: true,
+                    },
+                    physics,
+                );
+                physics
+                    .body_mut(body.handle())
+                    .set_rotation(tr.rotation(), true);
+                world.insert_one(e, body).unwrap();
+            }
+            EditorComponent::Mesh => world.insert_one(e, Mesh(box_mesh)).unwrap(),
+            EditorComponent::Material => {
+                let material =
+                    materials::Material::color(rr, assets, Editor::NEW_MATERIAL_COLOR, false);
+                world
+                    .insert_one(e, Material(assets.add_material(material)))
+                    .unwrap();
+            }
+            EditorComponent::RenderOrder => world.insert_one(e, RenderOrder(0)).unwrap(),
+            EditorComponent::RenderTags => {
+                world.insert_one(e, RenderTags(RENDER_TAG_SCENE)).unwrap()
+            }
+        },
+        EditorCommand::Add(_) => {}
+        EditorCommand::Remove(component) => match component {
+            EditorComponent::RigidBody => {
+                // Nothing to hold anymore
+                let _ = world.remove_one::<Grab>(e);
+                if let Ok(body) = world.remove_one::<RigidBody>(e) {
+                    physics.remove_body(body.handle());
+                }
+            }
+            EditorComponent::Mesh => {
+                let _ = world.remove_one::<Mesh>(e);
+            }
+            EditorComponent::Material => {
+                // A new material wouldn't show the camera target anymore
+                let _ = world.remove_one::<CameraTexture>(e);
+                if let Ok(material) = world.remove_one::<Material>(e) {
+                    assets.remove_material(material.0);
+                }
+            }
+            EditorComponent::RenderOrder => {
+                let _ = world.remove_one::<RenderOrder>(e);
+            }
+            EditorComponent::RenderTags => {
+                let _ = world.remove_one::<RenderTags>(e);
+            }
+        },
+        EditorCommand::Despawn => Selection::despawn(world, physics, assets, e),
+    }
+}
+
+fn euler_to_rotation(angles: Vec3) -> UnitQuat {
+    let angles = angles.map(f32::to_radians);
+    UnitQuat::from_euler_angles(angles.x, angles.y, angles.z)
+}
@@ -0,0 +1,161 @@
+use hecs::{Entity, World};
+
+use crate::math::Vec3;
+use crate::render::Renderer;
+
+use super::{RENDER_TAG_HIDDEN, RENDER_TAG_SCENE, RenderTags};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SkyMode {
+    // Only the clear color is visible
+    None,
+    Skybox,
+    Gradient,
+    // Procedural Rayleigh/Mie scattering
+    Atmosphere,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SkyGradient {
+    pub zenith: Vec3,
+    pub horizon: Vec3,
+    pub ground: Vec3,
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self {
+            zenith: Vec3::new(0.1, 0.3, 0.8),
+            horizon: Vec3::new(0.7, 0.8, 0.9),
+            ground: Vec3::new(0.2, 0.2, 0.2),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Atmosphere {
+    // Degrees, elevation above the horizon and rotation around the up axis
+    pub sun_elevation: f32,
+    pub sun_azimuth: f32,
+    pub sun_intensity: f32,
+    // Multipliers of the Earth-like scattering coefficients
+    pub rayleigh: f32,
+    pub mie: f32,
+    // Mie phase anisotropy, closer to 1 gives a tighter halo around the sun
+    pub mie_g: f32,
+}
+
+impl Atmosphere {
+    pub fn sun_direction(&self) -> Vec3 {
+        let (elevation, azimuth) = (
+            self.sun_elevation.to_radians(),
+            self.sun_azimuth.to_radians(),
+        );
+        Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        )
+    }
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            sun_elevation: 20.0,
+            sun_azimuth: 0.0,
+            sun_intensity: 20.0,
+            rayleigh: 1.0,
+            mie: 1.0,
+            mie_g: 0.76,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Linear,
+    Exponential,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Fog {
+    pub enabled: bool,
+    pub mode: FogMode,
+    pub color: Vec3,
+    // Linear fog: distances from the camera where the fog starts and becomes opaque
+    pub start: f32,
+    pub end: f32,
+    // Exponential fog: thickness at `base_height`, thinning out above it with `height_falloff`.
+    // Zero falloff makes it uniform.
+    pub density: f32,
+    pub height_falloff: f32,
+    pub base_height: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: FogMode::Linear,
+            color: Vec3::new(0.7, 0.8, 0.9),
+            start: 20.0,
+            end: 100.0,
+            density: 0.03,
+            height_falloff: 0.2,
+            base_height: 0.0,
+        }
+    }
+}
+
+// Scene-wide look: what's behind everything and how distant objects fade
+pub struct Environment {
+    pub sky_mode: SkyMode,
+    pub gradient: SkyGradient,
+    pub atmosphere: Atmosphere,
+    pub clear_color: Vec3,
+    pub fog: Fog,
+    sky: Entity,
+}
+
+impl Environment {
+    // `sky` is the entity with the full-screen sky material.
+    pub fn new(sky: Entity) -> Self {
+        Self {
+            sky_mode: SkyMode::Skybox,
+            gradient: SkyGradient::default(),
+            atmosphere: Atmosphere::default(),
+            clear_color: Vec3::zeros(),
+            fog: Fog::default(),
+            sky,
+        }
+    }
+
+    pub fn sky(&self) -> Entity {
+        self.sky
+    }
+
+    pub fn update(world: &mut World, rr: &mut Renderer) {
+        let (sky, visible) = {
+            let (_, env) = world
+                .query_mut::<&Environment>()
+                .into_iter()
+                .next()
+                .unwrap();
+            let c = env.clear_color;
+            rr.set_clear_color(wgpu::Color {
+                r: c.x as f64,
+                g: c.y as f64,
+                b: c.z as f64,
+                a: 1.0,
+            });
+            (env.sky, env.sky_mode != SkyMode::None)
+        };
+
+        world.get::<&mut RenderTags>(sky).unwrap().0 = if visible {
+            RENDER_TAG_SCENE
+        } else {
+            RENDER_TAG_HIDDEN
+        };
+    }
+}
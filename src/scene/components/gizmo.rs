@@ -0,0 +1,501 @@
+use hecs::{Entity, World};
+
+use crate::input::{Input, InputAction};
+use crate::math::{Ray, UnitQuat, Vec3};
+use crate::physics::Physics;
+use crate::render;
+use crate::render::Renderer;
+use crate::scene::{Assets, materials};
+
+use super::{
+    Grab, Material, Mesh, Player, RENDER_TAG_GIZMO, RENDER_TAG_HIDDEN, RenderOrder, RenderTags,
+    RigidBody, Selected, Transform,
+};
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    fn next(self) -> Self {
+        match self {
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Translate,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GizmoSettings {
+    pub mode: GizmoMode,
+    pub snapping: bool,
+    // In world units, degrees and scale units
+    pub translate_step: f32,
+    pub rotate_step: f32,
+    pub scale_step: f32,
+}
+
+impl Default for GizmoSettings {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            snapping: false,
+            translate_step: 0.5,
+            rotate_step: 15.0,
+            scale_step: 0.25,
+        }
+    }
+}
+
+// Axes are 0, 1 and 2 for X, Y and Z. Moving and rotating is along the world axes, scaling along
+// the object's own ones.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GizmoHandleKind {
+    Axis(usize),
+    // Moves in the plane across the axis
+    Plane(usize),
+    // Rotates around the axis
+    Ring(usize),
+    ScaleAxis(usize),
+    ScaleUniform,
+}
+
+impl GizmoHandleKind {
+    const ALL: [GizmoHandleKind; 13] = [
+        GizmoHandleKind::Axis(0),
+        GizmoHandleKind::Axis(1),
+        GizmoHandleKind::Axis(2),
+        GizmoHandleKind::Plane(0),
+        GizmoHandleKind::Plane(1),
+        GizmoHandleKind::Plane(2),
+        GizmoHandleKind::Ring(0),
+        GizmoHandleKind::Ring(1),
+        GizmoHandleKind::Ring(2),
+        GizmoHandleKind::ScaleAxis(0),
+        GizmoHandleKind::ScaleAxis(1),
+        GizmoHandleKind::ScaleAxis(2),
+        GizmoHandleKind::ScaleUniform,
+    ];
+
+    fn mode(self) -> GizmoMode {
+        match self {
+            GizmoHandleKind::Axis(_) | GizmoHandleKind::Plane(_) => GizmoMode::Translate,
+            GizmoHandleKind::Ring(_) => GizmoMode::Rotate,
+            GizmoHandleKind::ScaleAxis(_) | GizmoHandleKind::ScaleUniform => GizmoMode::Scale,
+        }
+    }
+
+    fn color(self) -> Vec3 {
+        match self {
+            GizmoHandleKind::Axis(i)
+            | GizmoHandleKind::Plane(i)
+            | GizmoHandleKind::Ring(i)
+            | GizmoHandleKind::ScaleAxis(i) => Gizmo::AXIS_COLORS[i],
+            GizmoHandleKind::ScaleUniform => Vec3::from_element(0.9),
+        }
+    }
+
+    // Line segments in gizmo space, where the handles are about 1 in size.
+    fn lines(self) -> Vec<(Vec3, Vec3)> {
+        let axes = |i: usize| {
+            (
+                Vec3::ith(i, 1.0),
+                Vec3::ith((i + 1) % 3, 1.0),
+                Vec3::ith((i + 2) % 3, 1.0),
+            )
+        };
+        match self {
+            GizmoHandleKind::Axis(i) => {
+                let (a, b, c) = axes(i);
+                vec![
+                    (Vec3::zeros(), a),
+                    (a, a * 0.9 + b * 0.05),
+                    (a, a * 0.9 - b * 0.05),
+                    (a, a * 0.9 + c * 0.05),
+                    (a, a * 0.9 - c * 0.05),
+                ]
+            }
+            GizmoHandleKind::Plane(i) => {
+                let (_, b, c) = axes(i);
+                let (near, far) = (Gizmo::PLANE_HANDLE_MIN, Gizmo::PLANE_HANDLE_MAX);
+                let corners = [
+                    b * near + c * near,
+                    b * far + c * near,
+                    b * far + c * far,
+                    b * near + c * far,
+                ];
+                (0..4).map(|k| (corners[k], corners[(k + 1) % 4])).collect()
+            }
+            GizmoHandleKind::Ring(i) => {
+                let (_, b, c) = axes(i);
+                let point = |k: usize| {
+                    let angle = k as f32 / Gizmo::RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                    (b * angle.cos() + c * angle.sin()) * Gizmo::RING_RADIUS
+                };
+                (0..Gizmo::RING_SEGMENTS)
+                    .map(|k| (point(k), point(k + 1)))
+                    .collect()
+            }
+            GizmoHandleKind::ScaleAxis(i) => {
+                let a = Vec3::ith(i, 1.0);
+                let mut lines = vec![(Vec3::zeros(), a * 0.94)];
+                lines.extend(cube_lines(a, 0.06));
+                lines
+            }
+            GizmoHandleKind::ScaleUniform => cube_lines(Vec3::zeros(), 0.12),
+        }
+    }
+}
+
+// One part of the gizmo, with its own material to be highlighted on its own
+pub struct GizmoHandle {
+    kind: GizmoHandleKind,
+    highlighted: bool,
+}
+
+struct GizmoDrag {
+    entity: Entity,
+    kind: GizmoHandleKind,
+    // Gizmo center and size, kept for the whole drag
+    center: Vec3,
+    size: f32,
+    // Point on the handle's axis, plane or ring where the drag started
+    start: Vec3,
+    // Of the entity when the drag started
+    pos: Vec3,
+    rotation: UnitQuat,
+    scale: Vec3,
+}
+
+// Moves, rotates and scales the selected entity when it's the only one selected. Handles are
+// dragged with the grab binding, the gizmo takes precedence over grabbing while it's hovered.
+#[derive(Default)]
+pub struct Gizmo {
+    pub settings: GizmoSettings,
+    hovered: Option<GizmoHandleKind>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Gizmo {
+    // Of the distance to the camera, so that it keeps about the same size on screen
+    const SIZE: f32 = 0.15;
+    // How far from a handle's lines it's still hit, in gizmo sizes
+    const HIT_DISTANCE: f32 = 0.06;
+    const AXIS_COLORS: [Vec3; 3] = [
+        Vec3::new(0.9, 0.2, 0.2),
+        Vec3::new(0.3, 0.85, 0.3),
+        Vec3::new(0.25, 0.45, 1.0),
+    ];
+    const ACTIVE_COLOR: Vec3 = Vec3::new(1.0, 0.8, 0.1);
+    const PLANE_HANDLE_MIN: f32 = 0.3;
+    const PLANE_HANDLE_MAX: f32 = 0.5;
+    const RING_RADIUS: f32 = 0.9;
+    const RING_SEGMENTS: usize = 48;
+    const MIN_SCALE: f32 = 0.05;
+
+    // The gizmo with its handles, each handle is an entity of its own.
+    pub fn spawn(world: &mut World, rr: &Renderer, assets: &mut Assets) -> Entity {
+        for kind in GizmoHandleKind::ALL {
+            let points = kind
+                .lines()
+                .into_iter()
+                .flat_map(|(from, to)| [from.into(), to.into()])
+                .collect::<Vec<_>>();
+            let mesh = assets.add_mesh(render::Mesh::new_lines(rr, &points));
+            let material = materials::Material::color(rr, assets, kind.color(), true);
+            world.spawn((
+                Transform::default(),
+                Mesh(mesh),
+                Material(assets.add_material(material)),
+                RenderOrder(0),
+                RenderTags(RENDER_TAG_HIDDEN),
+                GizmoHandle {
+                    kind,
+                    highlighted: false,
+                },
+            ));
+        }
+        world.spawn((Gizmo::default(),))
+    }
+
+    // Whether the mouse is over a handle or dragging one, grabbing is off then.
+    pub fn in_use(world: &World) -> bool {
+        let mut q = world.query::<&Gizmo>();
+        let (_, gizmo) = q.iter().next().unwrap();
+        gizmo.hovered.is_some() || gizmo.drag.is_some()
+    }
+
+    pub fn update(
+        world: &mut World,
+        physics: &mut Physics,
+        input: &Input,
+        rr: &Renderer,
+        assets: &mut Assets,
+    ) {
+        let (ray, cam_pos) = {
+            let (_, (player, tr)) = world
+                .query_mut::<(&Player, &Transform)>()
+                .into_iter()
+                .next()
+                .unwrap();
+            (player.focus_ray(), tr.position())
+        };
+
+        // Grabbing moves the objects already
+        let target = {
+            let selected = world
+                .query_mut::<(&Selected, &Transform)>()
+                .into_iter()
+                .map(|(e, (_, tr))| (e, *tr))
+                .collect::<Vec<_>>();
+            let grabbing = world.query_mut::<&Grab>().into_iter().next().is_some();
+            match selected[..] {
+                [target] if !grabbing => Some(target),
+                _ => None,
+            }
+        };
+
+        // Taken out while the world is being changed
+        let gizmo_entity = world.query_mut::<&Gizmo>().into_iter().next().unwrap().0;
+        let mut gizmo = std::mem::take(world.query_one_mut::<&mut Gizmo>(gizmo_entity).unwrap());
+        if input.action_activated(InputAction::SwitchGizmo) && gizmo.drag.is_none() {
+            gizmo.settings.mode = gizmo.settings.mode.next();
+        }
+
+        // Ends when released or when the entity is no longer the target, e.g. deleted
+        if let Some(drag) = &gizmo.drag
+            && (!input.action_active(InputAction::Grab)
+                || target.is_none_or(|(e, _)| e != drag.entity))
+        {
+            if let Ok(body) = world.get::<&RigidBody>(drag.entity) {
+                body.set_kinematic(physics, false);
+                let body = physics.body_mut(body.handle());
+                body.set_linvel(Vec3::zeros(), true);
+                body.set_angvel(Vec3::zeros(), true);
+            }
+            gizmo.drag = None;
+        }
+
+        gizmo.hovered = None;
+        if let (Some((entity, tr)), Some(ray)) = (target, ray) {
+            if let Some(drag) = &gizmo.drag {
+                if let Some(point) = drag_point(drag.kind, drag.center, &tr, &ray, cam_pos) {
+                    let (pos, rotation, scale) = gizmo.dragged(drag, point);
+                    RigidBody::set_pose(world, physics, entity, pos, rotation, scale);
+                }
+            } else {
+                let size = (tr.position() - cam_pos).magnitude() * Self::SIZE;
+                gizmo.hovered = gizmo.hit(tr, size, &ray);
+
+                if let Some(kind) = gizmo.hovered
+                    && input.action_activated(InputAction::Grab)
+                    && let Some(start) = drag_point(kind, tr.position(), &tr, &ray, cam_pos)
+                {
+                    if let Ok(body) = world.get::<&RigidBody>(entity) {
+                        body.set_kinematic(physics, true);
+                    }
+                    gizmo.drag = Some(GizmoDrag {
+                        entity,
+                        kind,
+                        center: tr.position(),
+                        size,
+                        start,
+                        pos: tr.position(),
+                        rotation: tr.rotation(),
+                        scale: tr.scale(),
+                    });
+                }
+            }
+        }
+
+        // Handles follow the target, possibly just moved
+        let target_tr = target.map(|(e, _)| *world.get::<&Transform>(e).unwrap());
+        let mode = gizmo.settings.mode;
+        let active = gizmo.drag.as_ref().map(|d| d.kind).or(gizmo.hovered);
+        for (_, (handle, tr, tags, material)) in
+            world.query_mut::<(&mut GizmoHandle, &mut Transform, &mut RenderTags, &Material)>()
+        {
+            let Some(target_tr) = target_tr.filter(|_| handle.kind.mode() == mode) else {
+                tags.0 = RENDER_TAG_HIDDEN;
+                continue;
+            };
+            tags.0 = RENDER_TAG_GIZMO;
+            let size = gizmo.drag.as_ref().map_or_else(
+                || (target_tr.position() - cam_pos).magnitude() * Self::SIZE,
+                |d| d.size,
+            );
+            let rotation = handle_rotation(handle.kind, &target_tr);
+            tr.set(target_tr.position(), *rotation.inverse().quaternion());
+            tr.set_scale(Vec3::from_element(size));
+
+            let highlighted = active == Some(handle.kind);
+            if handle.highlighted != highlighted {
+                handle.highlighted = highlighted;
+                let color = if highlighted {
+                    Self::ACTIVE_COLOR
+                } else {
+                    handle.kind.color()
+                };
+                assets.material_mut(material.0).set_color(rr, color);
+            }
+        }
+
+        *world.query_one_mut::<&mut Gizmo>(gizmo_entity).unwrap() = gizmo;
+    }
+
+    // The nearest handle of the current mode under the ray.
+    fn hit(&self, tr: Transform, size: f32, ray: &Ray) -> Option<GizmoHandleKind> {
+        let center = tr.position();
+        GizmoHandleKind::ALL
+            .into_iter()
+            .filter(|kind| kind.mode() == self.settings.mode)
+            .filter_map(|kind| {
+                let to_world = |p: Vec3| center + handle_rotation(kind, &tr) * p * size;
+                // Plane handles are hit anywhere inside, the rest only near their lines
+                if let GizmoHandleKind::Plane(i) = kind {
+                    let hit = ray_plane(ray, center, Vec3::ith(i, 1.0))?;
+                    let local = (hit - center) / size;
+                    let (b, c) = (local[(i + 1) % 3], local[(i + 2) % 3]);
+                    let range = Self::PLANE_HANDLE_MIN..=Self::PLANE_HANDLE_MAX;
+                    return (range.contains(&b) && range.contains(&c))
+                        .then(|| (kind, (hit - ray.origin.coords).magnitude(), 0.0));
+                }
+                kind.lines()
+                    .into_iter()
+                    .filter_map(|(from, to)| ray_segment(ray, to_world(from), to_world(to)))
+                    .filter(|&(_, distance)| distance <= Self::HIT_DISTANCE * size)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(along, distance)| (kind, along, distance))
+            })
+            // Closest to the ray first, then nearest to the camera
+            .min_by(|a, b| a.2.total_cmp(&b.2).then(a.1.total_cmp(&b.1)))
+            .map(|(kind, ..)| kind)
+    }
+
+    // The entity's position, rotation and scale with the handle dragged to `point`.
+    fn dragged(&self, drag: &GizmoDrag, point: Vec3) -> (Vec3, UnitQuat, Vec3) {
+        let settings = &self.settings;
+        let snap = |v: f32, step: f32| {
+            if settings.snapping && step > 0.0 {
+                (v / step).round() * step
+            } else {
+                v
+            }
+        };
+        let (mut pos, mut rotation, mut scale) = (drag.pos, drag.rotation, drag.scale);
+        match drag.kind {
+            GizmoHandleKind::Axis(_) | GizmoHandleKind::Plane(_) => {
+                pos += (point - drag.start).map(|v| snap(v, settings.translate_step));
+            }
+            GizmoHandleKind::Ring(i) => {
+                let axis = Vec3::ith_axis(i);
+                let (from, to) = (drag.start - drag.center, point - drag.center);
+                let angle = axis.dot(&from.cross(&to)).atan2(from.dot(&to));
+                let angle = snap(angle.to_degrees(), settings.rotate_step).to_radians();
+                rotation = UnitQuat::from_axis_angle(&axis, angle) * rotation;
+            }
+            GizmoHandleKind::ScaleAxis(i) => {
+                let axis = drag.rotation * Vec3::ith(i, 1.0);
+                let from = axis.dot(&(drag.start - drag.center));
+                if from.abs() > f32::EPSILON {
+                    let factor = axis.dot(&(point - drag.center)) / from;
+                    scale[i] = snap(scale[i] * factor, settings.scale_step);
+                }
+            }
+            GizmoHandleKind::ScaleUniform => {
+                let from = (drag.start - drag.center).magnitude();
+                if from > f32::EPSILON {
+                    let factor = (point - drag.center).magnitude() / from;
+                    scale = scale.map(|s| snap(s * factor, settings.scale_step));
+                }
+            }
+        }
+        (pos, rotation, scale.map(|s| s.max(Self::MIN_SCALE)))
+    }
+}
+
+// Scale handles turn with the object, the rest stay along the world axes.
+fn handle_rotation(kind: GizmoHandleKind, tr: &Transform) -> UnitQuat {
+    if kind.mode() == GizmoMode::Scale {
+        tr.rotation()
+    } else {
+        UnitQuat::identity()
+    }
+}
+
+// Where the ray points on the handle's axis, plane or ring. Uniform scaling works in the plane
+// facing the camera.
+fn drag_point(
+    kind: GizmoHandleKind,
+    center: Vec3,
+    tr: &Transform,
+    ray: &Ray,
+    cam_pos: Vec3,
+) -> Option<Vec3> {
+    match kind {
+        GizmoHandleKind::Axis(i) | GizmoHandleKind::ScaleAxis(i) => {
+            let axis = handle_rotation(kind, tr) * Vec3::ith(i, 1.0);
+            let (_, along) = closest_params(ray.origin.coords, ray.dir, center, axis)?;
+            Some(center + axis * along)
+        }
+        GizmoHandleKind::Plane(i) | GizmoHandleKind::Ring(i) => {
+            ray_plane(ray, center, Vec3::ith(i, 1.0))
+        }
+        GizmoHandleKind::ScaleUniform => ray_plane(ray, center, cam_pos - center),
+    }
+}
+
+// Parameters of the closest points on two lines, none if they're parallel.
+fn closest_params(o1: Vec3, d1: Vec3, o2: Vec3, d2: Vec3) -> Option<(f32, f32)> {
+    let w = o1 - o2;
+    let (a, b, c) = (d1.dot(&d1), d1.dot(&d2), d2.dot(&d2));
+    let (d, e) = (d1.dot(&w), d2.dot(&w));
+    let denom = a * c - b * b;
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    Some(((b * e - c * d) / denom, (a * e - b * d) / denom))
+}
+
+// Distance along the ray to the point nearest to the segment, and the distance between them.
+fn ray_segment(ray: &Ray, from: Vec3, to: Vec3) -> Option<(f32, f32)> {
+    let origin = ray.origin.coords;
+    let dir = to - from;
+    let t = closest_params(origin, ray.dir, from, dir)
+        .map_or(0.0, |(_, t)| t)
+        .clamp(0.0, 1.0);
+    let on_segment = from + dir * t;
+    let along = (on_segment - origin).dot(&ray.dir) / ray.dir.dot(&ray.dir);
+    (along >= 0.0).then(|| (along, (origin + ray.dir * along - on_segment).magnitude()))
+}
+
+fn ray_plane(ray: &Ray, point: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denom = ray.dir.dot(&normal);
+    if denom.abs() < 1e-4 {
+        return None;
+    }
+    let along = (point - ray.origin.coords).dot(&normal) / denom;
+    (along >= 0.0).then(|| ray.origin.coords + ray.dir * along)
+}
+
+fn cube_lines(center: Vec3, half: f32) -> Vec<(Vec3, Vec3)> {
+    let corner = |k: usize| {
+        center
+            + Vec3::new(
+                if k & 1 == 0 { -half } else { half },
+                if k & 2 == 0 { -half } else { half },
+                if k & 4 == 0 { -half } else { half },
+            )
+    };
+    // Corners differing in one coordinate
+    (0..8)
+        .flat_map(|k| [1, 2, 4].map(|bit| (k, k | bit)))
+        .filter(|&(k, n)| k != n)
+        .map(|(k, n)| (corner(k), corner(n)))
+        .collect()
+}
@@ -1,81 +1,236 @@
+use std::collections::VecDeque;
+
 use hecs::World;
 
-use crate::input::{Input, InputAction};
-use crate::math::Vec3;
+use crate::input::{Input, InputAction, InputAxis};
+use crate::math::{UnitQuat, Vec3};
 use crate::physics::Physics;
 
 use super::player::Player;
+use super::selection::Selected;
+use super::transform::Transform;
 use super::RigidBody;
 
 pub struct Grab {
-    // Original distance from the player when the grab was triggered.
+    // Pulled by impulses with limited force and colliding, otherwise teleported as kinematic
+    physical: bool,
+    // Gravity is off while physically held, this is restored on release
+    gravity_scale: f32,
+    // Distance from the player, initially from when the grab was triggered. Changed by zooming.
     distance: f32,
-    // Vector between the body position and the grab point at the moment of grab, both in global coordinates.
+    // Vector between the body position and the grab point, in camera space so that it turns
+    // along with the camera.
     offset: Vec3,
+    // Body rotation in camera space
+    rotation: UnitQuat,
+    // Time since the grab
+    time: f32,
+    // Recent body positions and rotations with their times, for throwing on release
+    samples: VecDeque<(f32, Vec3, UnitQuat)>,
 }
 
 impl Grab {
-    pub fn update(world: &mut World, input: &Input, physics: &mut Physics) {
+    const MIN_DISTANCE: f32 = 1.0;
+    const MAX_DISTANCE: f32 = 50.0;
+    // Per second when held, per line when scrolling
+    const ZOOM_SPEED: f32 = 10.0;
+    const ZOOM_STEP: f32 = 1.0;
+    // Radians per mouse pixel and per second at full stick deflection
+    const ROTATION_SPEED: f32 = 0.005;
+    const GAMEPAD_ROTATION_SPEED: f32 = 3.0;
+    // Motion over this many last seconds gives the throw velocity
+    const THROW_WINDOW: f32 = 0.1;
+    const MAX_THROW_SPEED: f32 = 30.0;
+    // Physical grab: target speed per unit of distance from the target position (1/s), force
+    // and torque limits and the distance at which a snagged body gets dropped
+    const STIFFNESS: f32 = 15.0;
+    const MAX_FORCE: f32 = 1000.0;
+    const MAX_TORQUE: f32 = 500.0;
+    const MAX_ANGULAR_SPEED: f32 = 20.0;
+    const SNAG_DISTANCE: f32 = 2.0;
+
+    // Whether looking around should rotate the held object instead of the camera.
+    pub fn rotating(world: &World, input: &Input) -> bool {
+        input.action_active(InputAction::RotateGrabbed) && world.query::<&Grab>().iter().len() > 0
+    }
+
+    // Grabbing a selected body grabs the whole selection, all held at the same distance and
+    // turning together around the grab point.
+    pub fn update(dt: f32, world: &mut World, input: &Input, physics: &mut Physics) {
         fn release_grab(world: &mut World, physics: &mut Physics) {
-            let entity = if let Some((entity, (_grab, body))) =
-                world.query::<(&Grab, &RigidBody)>().iter().next()
-            {
-                body.set_kinematic(physics, false);
-                Some(entity)
-            } else {
-                None
-            };
+            let mut released = Vec::new();
+            for (entity, (grab, body)) in world.query::<(&Grab, &RigidBody)>().iter() {
+                if grab.physical {
+                    // Already moving on its own
+                    physics
+                        .body_mut(body.handle())
+                        .set_gravity_scale(grab.gravity_scale, true);
+                } else {
+                    body.set_kinematic(physics, false);
+                    // Fixed bodies just stay where they were put
+                    if body.movable {
+                        let (linvel, angvel) = grab.throw_velocity();
+                        let body = physics.body_mut(body.handle());
+                        body.set_linvel(linvel, true);
+                        body.set_angvel(angvel, true);
+                    }
+                }
+                released.push(entity);
+            }
 
-            if let Some(entity) = entity {
+            for entity in released {
                 world.remove_one::<Grab>(entity).unwrap();
             }
         }
 
-        let (player_focus, player_focus_ray) = {
-            let (_, player) = world.query_mut::<&Player>().into_iter().next().unwrap();
-            (player.focus(), player.focus_ray())
+        let (player_focus, player_focus_ray, camera_rotation, physical_grab) = {
+            let (_, (player, tr)) = world
+                .query_mut::<(&Player, &Transform)>()
+                .into_iter()
+                .next()
+                .unwrap();
+            (
+                player.focus(),
+                player.focus_ray(),
+                tr.rotation(),
+                player.settings().physical_grab,
+            )
         };
 
         if input.action_activated(InputAction::Grab) {
             if world.query::<&Grab>().iter().next().is_none() {
                 if let Some(player_focus) = player_focus {
-                    let body_entity = {
-                        let mut q = world.query::<&RigidBody>();
-                        let (body_entity, body) = q
-                            .into_iter()
-                            .find(|(_, body)| body.handle() == player_focus.body)
+                    let focus_selected = world
+                        .query::<(&RigidBody, &Selected)>()
+                        .iter()
+                        .any(|(_, (body, _))| body.handle() == player_focus.body);
+                    let grabbed = world
+                        .query::<(&RigidBody, Option<&Selected>)>()
+                        .iter()
+                        .filter(|(_, (body, selected))| {
+                            body.handle() == player_focus.body
+                                || (focus_selected && selected.is_some())
+                        })
+                        .map(|(e, (body, _))| {
+                            // Fixed bodies can only be teleported
+                            let physical = physical_grab && body.movable;
+                            let gravity_scale = physics.body(body.handle()).gravity_scale();
+                            if physical {
+                                physics.body_mut(body.handle()).set_gravity_scale(0.0, true);
+                            } else {
+                                body.set_kinematic(physics, true);
+                            }
+                            (e, body.handle(), physical, gravity_scale)
+                        })
+                        .collect::<Vec<_>>();
+
+                    let inv_camera_rotation = camera_rotation.inverse();
+                    for (entity, handle, physical, gravity_scale) in grabbed {
+                        let body = physics.body(handle);
+                        let offset = *body.translation() - player_focus.point;
+                        world
+                            .insert_one(
+                                entity,
+                                Grab {
+                                    physical,
+                                    gravity_scale,
+                                    distance: player_focus.distance,
+                                    offset: inv_camera_rotation * offset,
+                                    rotation: inv_camera_rotation * body.rotation(),
+                                    time: 0.0,
+                                    samples: VecDeque::new(),
+                                },
+                            )
                             .unwrap();
-                        body.set_kinematic(physics, true);
-                        body_entity
-                    };
-                    let body = physics.body_mut(player_focus.body);
-                    let offset = *body.translation() - player_focus.point;
-                    world
-                        .insert(
-                            body_entity,
-                            (Grab {
-                                distance: player_focus.distance,
-                                offset,
-                            },),
-                        )
-                        .unwrap();
+                    }
                 }
             } else {
                 release_grab(world, physics);
             }
         }
 
-        // Update the grabbed object if any
+        // Update the grabbed objects if any
+        let mut snagged = false;
         if let Some(player_focus_ray) = player_focus_ray {
-            if let Some((_, (grab, body))) = world.query::<(&Grab, &RigidBody)>().iter().next() {
-                let new_pos = player_focus_ray.point_at(grab.distance) + grab.offset;
-                physics
-                    .body_mut(body.handle())
-                    .set_translation(new_pos.coords, true);
+            let zoom = input.axis(InputAxis::Zoom) * Self::ZOOM_SPEED * dt
+                + input.axis_delta(InputAxis::Zoom) * Self::ZOOM_STEP;
+            for (_, (grab, body)) in world.query::<(&mut Grab, &RigidBody)>().iter() {
+                grab.distance =
+                    (grab.distance - zoom).clamp(Self::MIN_DISTANCE, Self::MAX_DISTANCE);
+
+                if input.action_active(InputAction::RotateGrabbed) {
+                    grab.rotate(dt, input);
+                }
+
+                let new_pos =
+                    player_focus_ray.point_at(grab.distance).coords + camera_rotation * grab.offset;
+                let new_rotation = camera_rotation * grab.rotation;
+                let body = physics.body_mut(body.handle());
+                if grab.physical {
+                    let to_target = new_pos - body.translation();
+                    snagged |= to_target.magnitude() > Self::SNAG_DISTANCE;
+                    let target_vel = to_target * Self::STIFFNESS;
+                    let impulse = ((target_vel - body.linvel()) * body.mass())
+                        .cap_magnitude(Self::MAX_FORCE * dt);
+                    body.apply_impulse(impulse, true);
+                    let target_angvel = ((new_rotation * body.rotation().inverse()).scaled_axis()
+                        * Self::STIFFNESS)
+                        .cap_magnitude(Self::MAX_ANGULAR_SPEED);
+                    let inertia = body.mass_properties().effective_angular_inertia();
+                    let torque_impulse = (inertia * (target_angvel - body.angvel()))
+                        .cap_magnitude(Self::MAX_TORQUE * dt);
+                    body.apply_torque_impulse(torque_impulse, true);
+                } else {
+                    body.set_translation(new_pos, true);
+                    body.set_rotation(new_rotation, true);
+                }
+
+                grab.time += dt;
+                grab.samples.push_back((grab.time, new_pos, new_rotation));
+                while grab
+                    .samples
+                    .front()
+                    .is_some_and(|&(time, _, _)| grab.time - time > Self::THROW_WINDOW)
+                {
+                    grab.samples.pop_front();
+                }
             }
         } else {
             // Release grab if there's no player focus anymore
             release_grab(world, physics);
         }
+
+        // Stuck behind something, the whole group is dropped
+        if snagged {
+            release_grab(world, physics);
+        }
+    }
+
+    // Turns the body around the grab point, horizontal look around the camera's up axis and
+    // vertical around its right one.
+    fn rotate(&mut self, dt: f32, input: &Input) {
+        let h = input.axis_delta(InputAxis::LookX) * Self::ROTATION_SPEED
+            + input.axis(InputAxis::LookX) * Self::GAMEPAD_ROTATION_SPEED * dt;
+        let v = input.axis_delta(InputAxis::LookY) * Self::ROTATION_SPEED
+            + input.axis(InputAxis::LookY) * Self::GAMEPAD_ROTATION_SPEED * dt;
+        let delta = UnitQuat::from_scaled_axis(Vec3::y() * h - Vec3::x() * v);
+        self.rotation = delta * self.rotation;
+        self.offset = delta * self.offset;
+    }
+
+    // Linear and angular velocities over the recent samples.
+    fn throw_velocity(&self) -> (Vec3, Vec3) {
+        let (Some(&(t0, p0, r0)), Some(&(t1, p1, r1))) =
+            (self.samples.front(), self.samples.back())
+        else {
+            return (Vec3::zeros(), Vec3::zeros());
+        };
+        let elapsed = t1 - t0;
+        if elapsed <= 0.0 {
+            return (Vec3::zeros(), Vec3::zeros());
+        }
+        let linvel = ((p1 - p0) / elapsed).cap_magnitude(Self::MAX_THROW_SPEED);
+        let angvel = (r1 * r0.inverse()).scaled_axis() / elapsed;
+        (linvel, angvel)
     }
 }
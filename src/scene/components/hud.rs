@@ -1,16 +1,57 @@
-use imgui::Condition;
+use imgui::{Condition, TreeNodeFlags};
+use winit::keyboard::KeyCode;
 
-use crate::state::State;
+use crate::app;
+use crate::input::{GamepadStick, Input, InputAction, Key};
 use crate::render::Ui;
+use crate::state::State;
+
+use crate::math::{Vec2, Vec3};
+use crate::scene::materials::MaterialParams;
+
+use super::{
+    Editor, EditorCommand, EditorComponent, Environment, FogMode, GizmoMode, GizmoSettings,
+    OutlineSettings, PlayerSettings, PostProcessEffect, PostProcessor, Prefab, RENDER_TAG_GIZMO,
+    RENDER_TAG_HIDDEN, RENDER_TAG_SCENE, Selection, SelectionCommand, SkyMode, TonemappingOperator,
+};
 
-pub struct Hud;
+#[derive(Default)]
+pub struct Hud {
+    // Action waiting for a key press to bind, with the index of the binding to replace or none
+    // to add a new one
+    capture: Option<(InputAction, Option<usize>)>,
+}
+
+// What the HUD edits besides the input
+pub struct HudTargets<'a> {
+    pub pp: &'a mut PostProcessor,
+    pub env: &'a mut Environment,
+    pub player_settings: &'a mut PlayerSettings,
+    pub selection: &'a mut Selection,
+    pub outline: &'a mut OutlineSettings,
+    pub gpu_picking: &'a mut bool,
+    pub gizmo: &'a mut GizmoSettings,
+    pub editor: &'a mut Editor,
+    pub sample_count: &'a mut u32,
+}
 
 // TODO Ideally every component should have the possibility to render something to the UI.
 // So far I haven't been able to pass the `frame` reference around to allow for that,
 // the the UI rendering code continues to be a "singleton". Maybe the UI rendering should be done
 // as a list of commands generated by various components and then fed to the Ui component.
 impl Hud {
-    pub fn build(&mut self, dt: f32, state: &State, ui: &mut Ui) {
+    pub fn build(&mut self, dt: f32, state: &mut State, ui: &mut Ui, targets: HudTargets) {
+        let HudTargets {
+            pp,
+            env,
+            player_settings,
+            selection,
+            outline,
+            gpu_picking,
+            gizmo,
+            editor,
+            sample_count,
+        } = targets;
         ui.prepare_frame(dt, &state.window, |frame| {
             let window = frame.window("Info");
             window
@@ -18,12 +59,6 @@ impl Hud {
                 .size([300.0, 150.0], Condition::FirstUseEver)
                 .position([20.0, 20.0], Condition::FirstUseEver)
                 .build(|| {
-                    frame.text("Controls:");
-                    frame.text("Tab: capture/release mouse");
-                    frame.text("WASDQE: move camera while mouse is captured");
-                    frame.text("F: spawn a box");
-                    frame.text("Left mouse click: grab/release an object");
-                    frame.separator();
                     frame.text(format!("Using adapter {}", state.renderer.adapter_name));
                     let mouse_pos = frame.io().mouse_pos;
                     // Sometimes the coordinates are reported as very big negative numbers, e.g.
@@ -42,7 +77,534 @@ impl Hud {
                         }
                     ));
                     frame.text(format!("Frame time: {dt:?}"));
+                    if let Some(playback) = state.input.playback() {
+                        let (played, total) = playback.progress();
+                        frame.text(format!(
+                            "Playing back input: frame {played}/{total}, {}x",
+                            playback.speed
+                        ));
+                    } else if state.input.is_recording() {
+                        frame.text("Recording input");
+                    }
+                    frame.separator();
+                    frame.text("MSAA:");
+                    for &count in state.renderer.supported_sample_counts() {
+                        frame.same_line();
+                        let label = if count == 1 {
+                            "Off".to_string()
+                        } else {
+                            format!("{count}x")
+                        };
+                        frame.radio_button(label, sample_count, count);
+                    }
                 });
+
+            build_post_process_window(frame, pp);
+            build_environment_window(frame, env);
+            build_controls_window(frame, &mut state.input, &mut self.capture);
+            build_movement_window(frame, player_settings);
+            build_selection_window(frame, selection, outline, gpu_picking, gizmo);
+            build_hierarchy_window(frame, editor);
+            build_inspector_window(frame, editor);
         })
     }
 }
+
+fn build_post_process_window(frame: &imgui::Ui, pp: &mut PostProcessor) {
+    frame
+        .window("Post-processing")
+        .always_auto_resize(true)
+        .position([20.0, 240.0], Condition::FirstUseEver)
+        .build(|| {
+            let count = pp.passes().len();
+            for i in 0..count {
+                let _id = frame.push_id_usize(i);
+
+                let mut enabled = pp.passes()[i].enabled;
+                if frame.checkbox(pp.passes()[i].effect.name(), &mut enabled) {
+                    pp.set_enabled(i, enabled);
+                }
+                frame.same_line();
+                if frame.small_button("Up") && i > 0 {
+                    pp.swap_passes(i, i - 1);
+                }
+                frame.same_line();
+                if frame.small_button("Down") && i + 1 < count {
+                    pp.swap_passes(i, i + 1);
+                }
+
+                match pp.effect_mut(i) {
+                    PostProcessEffect::Bloom {
+                        threshold,
+                        knee,
+                        intensity,
+                        radius,
+                    } => {
+                        frame.slider("Threshold", 0.0, 5.0, threshold);
+                        frame.slider("Knee", 0.0, 2.0, knee);
+                        frame.slider("Intensity", 0.0, 1.0, intensity);
+                        frame.slider("Radius", 0.5, 3.0, radius);
+                    }
+                    PostProcessEffect::Vignette { radius, intensity } => {
+                        frame.slider("Radius", 0.1, 2.0, radius);
+                        frame.slider("Intensity", 0.0, 1.0, intensity);
+                    }
+                    PostProcessEffect::Tonemapping { operator, exposure } => {
+                        frame.radio_button("Reinhard", operator, TonemappingOperator::Reinhard);
+                        frame.same_line();
+                        frame.radio_button("ACES", operator, TonemappingOperator::Aces);
+                        frame.slider("Exposure", 0.1, 10.0, exposure);
+                    }
+                    PostProcessEffect::Gamma { gamma } => {
+                        frame.slider("Gamma", 0.2, 3.0, gamma);
+                    }
+                    PostProcessEffect::ColorGrading { intensity, .. } => {
+                        frame.slider("Intensity", 0.0, 1.0, intensity);
+                    }
+                    PostProcessEffect::Fxaa { span } => {
+                        frame.slider("Span", 1.0, 16.0, span);
+                    }
+                    PostProcessEffect::ChromaticAberration { intensity } => {
+                        frame.slider("Intensity", 0.0, 0.05, intensity);
+                    }
+                }
+                frame.separator();
+            }
+        });
+}
+
+fn build_environment_window(frame: &imgui::Ui, env: &mut Environment) {
+    frame
+        .window("Environment")
+        .always_auto_resize(true)
+        .position([340.0, 20.0], Condition::FirstUseEver)
+        .build(|| {
+            frame.text("Sky:");
+            frame.same_line();
+            frame.radio_button("None", &mut env.sky_mode, SkyMode::None);
+            frame.same_line();
+            frame.radio_button("Skybox", &mut env.sky_mode, SkyMode::Skybox);
+            frame.same_line();
+            frame.radio_button("Gradient", &mut env.sky_mode, SkyMode::Gradient);
+            frame.same_line();
+            frame.radio_button("Atmosphere", &mut env.sky_mode, SkyMode::Atmosphere);
+
+            match env.sky_mode {
+                SkyMode::None => {
+                    color_edit(frame, "Clear color", &mut env.clear_color);
+                }
+                SkyMode::Gradient => {
+                    color_edit(frame, "Zenith", &mut env.gradient.zenith);
+                    color_edit(frame, "Horizon", &mut env.gradient.horizon);
+                    color_edit(frame, "Ground", &mut env.gradient.ground);
+                }
+                SkyMode::Atmosphere => {
+                    let a = &mut env.atmosphere;
+                    frame.slider("Sun elevation", -10.0, 90.0, &mut a.sun_elevation);
+                    frame.slider("Sun azimuth", -180.0, 180.0, &mut a.sun_azimuth);
+                    frame.slider("Sun intensity", 0.0, 50.0, &mut a.sun_intensity);
+                    frame.slider("Rayleigh", 0.0, 5.0, &mut a.rayleigh);
+                    frame.slider("Mie", 0.0, 5.0, &mut a.mie);
+                    frame.slider("Mie g", 0.0, 0.99, &mut a.mie_g);
+                }
+                SkyMode::Skybox => {}
+            }
+
+            frame.separator();
+            frame.checkbox("Fog", &mut env.fog.enabled);
+            if env.fog.enabled {
+                let fog = &mut env.fog;
+                frame.radio_button("Linear", &mut fog.mode, FogMode::Linear);
+                frame.same_line();
+                frame.radio_button("Exponential", &mut fog.mode, FogMode::Exponential);
+                color_edit(frame, "Fog color", &mut fog.color);
+                match fog.mode {
+                    FogMode::Linear => {
+                        frame.slider("Start", 0.0, 200.0, &mut fog.start);
+                        frame.slider("End", 0.0, 200.0, &mut fog.end);
+                    }
+                    FogMode::Exponential => {
+                        frame.slider("Density", 0.0, 0.2, &mut fog.density);
+                        frame.slider("Height falloff", 0.0, 1.0, &mut fog.height_falloff);
+                        frame.slider("Base height", -20.0, 20.0, &mut fog.base_height);
+                    }
+                }
+            }
+        });
+}
+
+fn build_controls_window(
+    frame: &imgui::Ui,
+    input: &mut Input,
+    capture: &mut Option<(InputAction, Option<usize>)>,
+) {
+    // Finish capturing first so that the new binding is shown right away. Escape cancels.
+    if let Some((action, index)) = *capture
+        && let Some(binding) = input.pressed_binding()
+    {
+        if binding.key != Key::Keyboard(KeyCode::Escape) || binding.modifiers != Default::default()
+        {
+            match index {
+                Some(i) => input.bindings_mut().replace(action, i, binding),
+                None => input.bindings_mut().add(action, binding),
+            }
+        }
+        *capture = None;
+    }
+
+    frame
+        .window("Controls")
+        .always_auto_resize(true)
+        .position([660.0, 20.0], Condition::FirstUseEver)
+        .build(|| {
+            for (action_idx, &action) in InputAction::ALL.iter().enumerate() {
+                let _action_id = frame.push_id_usize(action_idx);
+                frame.text(action.name());
+
+                let bindings = input.bindings().get(action).to_vec();
+                for (i, binding) in bindings.iter().enumerate() {
+                    let _id = frame.push_id_usize(i);
+                    frame.same_line();
+                    let label = if *capture == Some((action, Some(i))) {
+                        "<press a key>".to_string()
+                    } else {
+                        binding.to_string()
+                    };
+                    if frame.small_button(label) {
+                        *capture = Some((action, Some(i)));
+                    }
+                    frame.same_line();
+                    if frame.small_button("x") {
+                        input.bindings_mut().remove(action, i);
+                        *capture = None;
+                    }
+                }
+
+                frame.same_line();
+                let label = if *capture == Some((action, None)) {
+                    "<press a key>"
+                } else {
+                    "+"
+                };
+                if frame.small_button(label) {
+                    *capture = Some((action, None));
+                }
+            }
+
+            let conflicts = input.bindings().conflicts();
+            if !conflicts.is_empty() {
+                frame.separator();
+                for (binding, actions) in conflicts {
+                    let names = actions.iter().map(|a| a.name()).collect::<Vec<_>>();
+                    frame.text_colored(
+                        [1.0, 0.4, 0.4, 1.0],
+                        format!("{binding} is bound to: {}", names.join(", ")),
+                    );
+                }
+            }
+
+            frame.separator();
+            for (stick, label) in [
+                (GamepadStick::Left, "Left stick (move)"),
+                (GamepadStick::Right, "Right stick (look)"),
+            ] {
+                let _id = frame.push_id(label);
+                frame.text(label);
+                let params = input.stick_params_mut(stick);
+                frame.slider("Dead zone", 0.0, 0.9, &mut params.dead_zone);
+                frame.slider("Curve", 1.0, 4.0, &mut params.exponent);
+                frame.slider("Sensitivity", 0.1, 3.0, &mut params.sensitivity);
+            }
+
+            frame.separator();
+            if frame.button("Save") {
+                app::save_bindings(input.bindings());
+            }
+            frame.same_line();
+            if frame.button("Reset to defaults") {
+                *input.bindings_mut() = app::shipped_bindings();
+                *capture = None;
+            }
+        });
+
+    // Keys pressed while capturing shouldn't trigger anything
+    input.set_actions_enabled(capture.is_none());
+}
+
+fn build_movement_window(frame: &imgui::Ui, settings: &mut PlayerSettings) {
+    frame
+        .window("Movement")
+        .always_auto_resize(true)
+        .position([1000.0, 20.0], Condition::FirstUseEver)
+        .build(|| {
+            frame.slider("Fly speed", 1.0, 50.0, &mut settings.fly_speed);
+            frame.slider("Walk speed", 1.0, 20.0, &mut settings.walk_speed);
+            frame.slider("Acceleration", 1.0, 200.0, &mut settings.acceleration);
+            frame.slider("Deceleration", 1.0, 200.0, &mut settings.deceleration);
+            frame.slider("Sprint", 1.0, 5.0, &mut settings.sprint_multiplier);
+            frame.slider("Slow", 0.05, 1.0, &mut settings.slow_multiplier);
+
+            frame.separator();
+            frame
+                .slider_config("Mouse sensitivity", 0.0005, 0.01)
+                .display_format("%.4f")
+                .build(&mut settings.mouse_sensitivity);
+            frame.slider(
+                "Mouse smoothing, s",
+                0.0,
+                0.2,
+                &mut settings.mouse_smoothing,
+            );
+            frame.slider(
+                "Gamepad look speed",
+                0.5,
+                10.0,
+                &mut settings.gamepad_look_speed,
+            );
+            frame.checkbox("Invert Y", &mut settings.invert_y);
+            frame.checkbox("Physics-based grab", &mut settings.physical_grab);
+
+            frame.separator();
+            if frame.button("Save") {
+                app::save_settings(*settings);
+            }
+            frame.same_line();
+            if frame.button("Reset to defaults") {
+                *settings = app::shipped_settings();
+            }
+        });
+}
+
+fn build_selection_window(
+    frame: &imgui::Ui,
+    selection: &mut Selection,
+    outline: &mut OutlineSettings,
+    gpu_picking: &mut bool,
+    gizmo: &mut GizmoSettings,
+) {
+    frame
+        .window("Selection")
+        .always_auto_resize(true)
+        .position([1000.0, 320.0], Condition::FirstUseEver)
+        .build(|| {
+            frame.text(format!("Selected objects: {}", selection.count()));
+            for (i, (label, command)) in [
+                ("Delete", SelectionCommand::Delete),
+                ("Duplicate", SelectionCommand::Duplicate),
+                ("Freeze/unfreeze", SelectionCommand::ToggleFreeze),
+                ("Clear", SelectionCommand::Clear),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                if i > 0 {
+                    frame.same_line();
+                }
+                if frame.button(label) {
+                    selection.command = Some(command);
+                }
+            }
+
+            // Otherwise clicking picks only bodies, with a ray
+            frame.checkbox("Pixel-accurate picking", gpu_picking);
+
+            frame.separator();
+            frame.text("Gizmo (single selected object)");
+            for (i, (label, mode)) in [
+                ("Move", GizmoMode::Translate),
+                ("Rotate", GizmoMode::Rotate),
+                ("Scale", GizmoMode::Scale),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                if i > 0 {
+                    frame.same_line();
+                }
+                frame.radio_button(label, &mut gizmo.mode, mode);
+            }
+            frame.checkbox("Snap", &mut gizmo.snapping);
+            if gizmo.snapping {
+                frame.slider("Move step", 0.05, 2.0, &mut gizmo.translate_step);
+                frame.slider("Rotation step, deg", 1.0, 90.0, &mut gizmo.rotate_step);
+                frame.slider("Scale step", 0.05, 1.0, &mut gizmo.scale_step);
+            }
+
+            frame.separator();
+            frame.checkbox("Outline focused and grabbed", &mut outline.enabled);
+            if outline.enabled {
+                frame.slider("Width, px", 1.0, 10.0, &mut outline.width);
+                color_edit(frame, "Focused", &mut outline.focus_color);
+                color_edit(frame, "Grabbed", &mut outline.grab_color);
+            }
+        });
+
+    // Window pixels are physical, the UI works in logical ones
+    if let Some((min, max)) = selection.drag_rect() {
+        let [sx, sy] = frame.io().display_framebuffer_scale;
+        frame
+            .get_foreground_draw_list()
+            .add_rect(
+                [min.x / sx, min.y / sy],
+                [max.x / sx, max.y / sy],
+                [1.0, 0.8, 0.1, 1.0],
+            )
+            .build();
+    }
+}
+
+fn build_hierarchy_window(frame: &imgui::Ui, editor: &mut Editor) {
+    frame
+        .window("Hierarchy")
+        .size([300.0, 280.0], Condition::FirstUseEver)
+        .position([1320.0, 20.0], Condition::FirstUseEver)
+        .build(|| {
+            frame.text("Spawn:");
+            for prefab in Prefab::ALL {
+                frame.same_line();
+                if frame.small_button(prefab.name()) {
+                    editor.spawn = Some(prefab);
+                }
+            }
+            frame.separator();
+
+            let inspected = editor.inspected.as_ref().map(|i| i.entity);
+            let mut pick = None;
+            for &(e, ref label) in editor.entities() {
+                let _id = frame.push_id_usize(e.id() as usize);
+                if frame
+                    .selectable_config(label)
+                    .selected(inspected == Some(e))
+                    .build()
+                {
+                    pick = Some(e);
+                }
+            }
+            if pick.is_some() {
+                editor.pick = pick;
+            }
+        });
+}
+
+fn build_inspector_window(frame: &imgui::Ui, editor: &mut Editor) {
+    frame
+        .window("Inspector")
+        .always_auto_resize(true)
+        .position([1320.0, 320.0], Condition::FirstUseEver)
+        .build(|| {
+            let Some(inspected) = &mut editor.inspected else {
+                frame.text("Select an object or pick one in the hierarchy");
+                return;
+            };
+            frame.text(&inspected.label);
+            let changed = &mut editor.changed;
+
+            if let Some(tr) = &mut inspected.transform
+                && frame.collapsing_header("Transform", TreeNodeFlags::DEFAULT_OPEN)
+            {
+                changed.position |= drag_vec3(frame, "Position", &mut tr.pos, 0.05);
+                changed.rotation |= drag_vec3(frame, "Rotation, deg", &mut tr.rotation, 1.0);
+                changed.scale |= drag_vec3(frame, "Scale", &mut tr.scale, 0.01);
+            }
+
+            if let Some(body) = &mut inspected.body
+                && frame.collapsing_header("Body", TreeNodeFlags::DEFAULT_OPEN)
+            {
+                changed.body |= frame.checkbox("Movable", &mut body.movable);
+                changed.body |= frame.checkbox("Continuous collisions", &mut body.ccd);
+                changed.body |= frame.checkbox("Lock rotations", &mut body.lock_rotations);
+            }
+
+            if let Some(material) = &mut inspected.material
+                && frame.collapsing_header("Material", TreeNodeFlags::DEFAULT_OPEN)
+            {
+                match material {
+                    MaterialParams::Color { color, wireframe } => {
+                        changed.material |= color_edit(frame, "Color", color);
+                        changed.material |= frame.checkbox("Wireframe", wireframe);
+                    }
+                    MaterialParams::Textured { uv_scale } => {
+                        let mut v: [f32; 2] = (*uv_scale).into();
+                        if imgui::Drag::new("UV scale")
+                            .speed(0.05)
+                            .build_array(frame, &mut v)
+                        {
+                            *uv_scale = Vec2::from(v);
+                            changed.material = true;
+                        }
+                    }
+                    MaterialParams::Fixed(name) => frame.text(format!("{name} material")),
+                }
+            }
+
+            if (inspected.render_order.is_some() || inspected.render_tags.is_some())
+                && frame.collapsing_header("Rendering", TreeNodeFlags::DEFAULT_OPEN)
+            {
+                if let Some(order) = &mut inspected.render_order {
+                    changed.rendering |= frame.input_int("Render order", order).build();
+                }
+                if let Some(tags) = &mut inspected.render_tags {
+                    for bit in 0..8 {
+                        let mask = 1 << bit;
+                        let label = match mask {
+                            RENDER_TAG_SCENE => "Scene".to_string(),
+                            RENDER_TAG_GIZMO => "Gizmo".to_string(),
+                            RENDER_TAG_HIDDEN => "Hidden".to_string(),
+                            _ => format!("Tag {bit}"),
+                        };
+                        if bit % 4 != 0 {
+                            frame.same_line();
+                        }
+                        changed.rendering |= frame.checkbox_flags(label, tags, mask);
+                    }
+                }
+            }
+
+            if !inspected.managed.is_empty() {
+                frame.text(format!("Managed: {}", inspected.managed.join(", ")));
+            }
+
+            if inspected.editable() {
+                frame.separator();
+                for (i, component) in EditorComponent::ALL.into_iter().enumerate() {
+                    if i > 0 {
+                        frame.same_line();
+                    }
+                    if inspected.has(component) {
+                        if frame.small_button(format!("- {}", component.name())) {
+                            editor.command = Some(EditorCommand::Remove(component));
+                        }
+                    } else if inspected.can_add(component)
+                        && frame.small_button(format!("+ {}", component.name()))
+                    {
+                        editor.command = Some(EditorCommand::Add(component));
+                    }
+                }
+                if frame.button("Despawn") {
+                    editor.command = Some(EditorCommand::Despawn);
+                }
+            }
+        });
+}
+
+// Returns whether the color has been changed.
+fn color_edit(frame: &imgui::Ui, label: &str, color: &mut Vec3) -> bool {
+    let mut c: [f32; 3] = (*color).into();
+    let changed = frame.color_edit3(label, &mut c);
+    if changed {
+        *color = c.into();
+    }
+    changed
+}
+
+// Returns whether the vector has been changed.
+fn drag_vec3(frame: &imgui::Ui, label: &str, v: &mut Vec3, speed: f32) -> bool {
+    let mut values: [f32; 3] = (*v).into();
+    let changed = imgui::Drag::new(label)
+        .speed(speed)
+        .build_array(frame, &mut values);
+    if changed {
+        *v = values.into();
+    }
+    changed
+}
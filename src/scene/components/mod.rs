@@ -1,26 +1,49 @@
 mod camera;
+mod editor;
+mod environment;
+mod gizmo;
 mod grab;
 mod hud;
+mod outliner;
+mod picker;
 mod player;
+mod player_avatar;
 mod player_target;
+mod post_processor;
 mod rigid_body;
+mod selection;
 mod transform;
 
-pub use camera::Camera;
+pub use camera::{Camera, CameraParams, Projection};
+pub use editor::{Editor, EditorCommand, EditorComponent, Prefab};
+pub use environment::{Atmosphere, Environment, Fog, FogMode, SkyGradient, SkyMode};
+pub use gizmo::{Gizmo, GizmoHandle, GizmoMode, GizmoSettings};
 pub use grab::Grab;
-pub use hud::Hud;
-pub use player::Player;
+pub use hud::{Hud, HudTargets};
+pub use outliner::{OutlineSettings, Outliner};
+pub use picker::Picker;
+pub use player::{Player, PlayerSettings};
+pub use player_avatar::PlayerAvatar;
 pub use player_target::PlayerTarget;
+pub use post_processor::{PostProcessEffect, PostProcessPass, PostProcessor, TonemappingOperator};
 pub use rigid_body::{RigidBody, RigidBodyParams};
+pub use selection::{Selected, Selection, SelectionCommand};
 pub use transform::Transform;
 
+use hecs::Entity;
+
 use crate::scene::{MaterialHandle, MeshHandle};
 
 pub struct RenderTags(pub u32);
 pub struct RenderOrder(pub i32);
 pub struct Mesh(pub MeshHandle);
 pub struct Material(pub MaterialHandle);
+// Nodes from the scene file are named after their keys
+pub struct Name(pub String);
+// The entity's textured material shows the target of this camera entity
+pub struct CameraTexture(pub Entity);
 
 pub const RENDER_TAG_SCENE: u32 = 0b00000001;
-pub const RENDER_TAG_POST_PROCESS: u32 = 0b00000010;
+// Drawn over the scene by the player camera only
+pub const RENDER_TAG_GIZMO: u32 = 0b00000010;
 pub const RENDER_TAG_HIDDEN: u32 = 0b00000100;
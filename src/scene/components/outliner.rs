@@ -0,0 +1,107 @@
+use hecs::{Entity, World};
+
+use crate::math::Vec3;
+use crate::render::Renderer;
+use crate::scene::{Assets, MaterialHandle, materials};
+
+use super::{Grab, Player, RigidBody};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutlineSettings {
+    pub enabled: bool,
+    // In pixels
+    pub width: f32,
+    pub focus_color: Vec3,
+    pub grab_color: Vec3,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            width: 3.0,
+            focus_color: Vec3::new(1.0, 1.0, 1.0),
+            grab_color: Vec3::new(0.2, 0.7, 1.0),
+        }
+    }
+}
+
+// An entity drawn with an outline, with its own materials for both outline draws
+pub struct Outlined {
+    pub entity: Entity,
+    pub mark: MaterialHandle,
+    pub outline: MaterialHandle,
+    pub color: Vec3,
+}
+
+// Keeps outlines around the player's focus and the grabbed objects
+#[derive(Default)]
+pub struct Outliner {
+    pub settings: OutlineSettings,
+    outlined: Vec<Outlined>,
+}
+
+impl Outliner {
+    pub fn outlined(&self) -> &[Outlined] {
+        &self.outlined
+    }
+
+    pub fn update(world: &mut World, rr: &Renderer, assets: &mut Assets) {
+        let focus_body = {
+            let (_, player) = world.query_mut::<&Player>().into_iter().next().unwrap();
+            player.focus().map(|f| f.body)
+        };
+
+        let mut targets = world
+            .query_mut::<(&RigidBody, Option<&Grab>)>()
+            .into_iter()
+            .filter_map(|(e, (body, grab))| {
+                if grab.is_some() {
+                    Some((e, true))
+                } else {
+                    (Some(body.handle()) == focus_body).then_some((e, false))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (_, outliner) = world
+            .query_mut::<&mut Outliner>()
+            .into_iter()
+            .next()
+            .unwrap();
+        let settings = outliner.settings;
+        if !settings.enabled {
+            targets.clear();
+        }
+
+        // Dropping the ones no longer outlined, including despawned entities
+        outliner.outlined.retain(|outlined| {
+            let keep = targets.iter().any(|&(e, _)| e == outlined.entity);
+            if !keep {
+                assets.remove_material(outlined.mark);
+                assets.remove_material(outlined.outline);
+            }
+            keep
+        });
+
+        for (entity, grabbed) in targets {
+            let color = if grabbed {
+                settings.grab_color
+            } else {
+                settings.focus_color
+            };
+            if let Some(outlined) = outliner.outlined.iter_mut().find(|o| o.entity == entity) {
+                outlined.color = color;
+            } else {
+                let mark = materials::Material::outline(rr, assets, true);
+                let outline = materials::Material::outline(rr, assets, false);
+                outliner.outlined.push(Outlined {
+                    entity,
+                    mark: assets.add_material(mark),
+                    outline: assets.add_material(outline),
+                    color,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use hecs::{Entity, Or, Without, World};
+
+use crate::math::Vec2;
+use crate::render::{RenderPassParams, RenderTarget, RenderTargetParams, Renderer};
+use crate::scene::materials::IdMaterial;
+use crate::scene::{Assets, MaterialHandle, materials};
+
+use super::{
+    Camera, Environment, Material, Mesh, Player, PlayerAvatar, PlayerTarget, RenderTags, Transform,
+};
+
+// Pixel-accurate picking of anything rendered, with or without a body. Entity IDs are rendered
+// into an integer target and read back around the cursor. It waits for the GPU, so it's done only
+// on demand, e.g. when clicking to select.
+pub struct Picker {
+    pub enabled: bool,
+    target: Option<RenderTarget>,
+    // Created on the first pick of each entity
+    materials: HashMap<Entity, MaterialHandle>,
+}
+
+impl Default for Picker {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target: None,
+            materials: HashMap::new(),
+        }
+    }
+}
+
+impl Picker {
+    // Pixels around the position that are searched too, so that thin lines can be hit
+    const RADIUS: u32 = 3;
+
+    pub fn enabled(world: &World) -> bool {
+        world.query::<&Picker>().iter().next().unwrap().1.enabled
+    }
+
+    // The entity seen through the player camera at a window position in pixels, or the nearest
+    // one within a few pixels.
+    pub fn pick(world: &World, rr: &Renderer, assets: &mut Assets, pos: Vec2) -> Option<Entity> {
+        let mut picker_query = world.query::<&mut Picker>();
+        let (_, picker) = picker_query.iter().next().unwrap();
+        let mut cam_query = world.query::<(&Player, &Camera, &Transform)>();
+        let (_, (_, cam, cam_tr)) = cam_query.iter().next().unwrap();
+        let mut env_query = world.query::<&Environment>();
+        let (_, env) = env_query.iter().next().unwrap();
+
+        let size = rr.surface_size();
+        let size = (size.width, size.height);
+        if picker.target.as_ref().is_none_or(|t| t.size() != size) {
+            picker.target = Some(RenderTarget::new(
+                rr,
+                RenderTargetParams {
+                    size: Some(size),
+                    format: Some(IdMaterial::TEXTURE_FORMAT),
+                    sample_count: Some(1),
+                },
+            ));
+        }
+        let target = picker.target.as_ref().unwrap();
+
+        picker.materials.retain(|&e, &mut material| {
+            let alive = world.contains(e);
+            if !alive {
+                assets.remove_material(material);
+            }
+            alive
+        });
+
+        // The player's own helpers are always in the way
+        let mut items =
+            world.query::<Without<
+                (&Mesh, &Material, &Transform, &RenderTags),
+                Or<&PlayerTarget, &PlayerAvatar>,
+            >>();
+        let bundles = items
+            .iter()
+            .filter(|(_, (.., tags))| cam.should_render(tags.0))
+            .filter_map(|(e, (mesh, material, tr, _))| {
+                let wireframe = assets.material(material.0).pickable_wireframe()?;
+                let id_material = *picker.materials.entry(e).or_insert_with(|| {
+                    let material =
+                        materials::Material::id(rr, assets, e.to_bits().get(), wireframe);
+                    assets.add_material(material)
+                });
+                let id_material = assets.material(id_material);
+                id_material.update(rr, cam, cam_tr, tr, env);
+                Some(rr.build_render_bundle(assets.mesh(mesh.0), id_material, Some(target)))
+            })
+            .collect::<Vec<_>>();
+
+        rr.render_pass(
+            &bundles,
+            RenderPassParams {
+                target: Some(target),
+                viewport: cam.viewport(),
+                // No entity
+                clear_color: Some(wgpu::Color::TRANSPARENT),
+                ..Default::default()
+            },
+            None,
+        );
+
+        // Clamped to the target
+        let (x, y) = (pos.x.max(0.0) as u32, pos.y.max(0.0) as u32);
+        if x >= size.0 || y >= size.1 {
+            return None;
+        }
+        let min = (
+            x.saturating_sub(Self::RADIUS),
+            y.saturating_sub(Self::RADIUS),
+        );
+        let max = (
+            (x + Self::RADIUS).min(size.0 - 1),
+            (y + Self::RADIUS).min(size.1 - 1),
+        );
+        let region_width = max.0 - min.0 + 1;
+        let data = rr.read_texture(target.color_tex(), min, (region_width, max.1 - min.1 + 1));
+
+        data.chunks_exact(8)
+            .enumerate()
+            .filter_map(|(i, texel)| {
+                let low = u32::from_le_bytes(texel[..4].try_into().unwrap()) as u64;
+                let high = u32::from_le_bytes(texel[4..].try_into().unwrap()) as u64;
+                let entity = Entity::from_bits(high << 32 | low)?;
+                let (px, py) = (
+                    min.0 + i as u32 % region_width,
+                    min.1 + i as u32 / region_width,
+                );
+                let distance = px.abs_diff(x).pow(2) + py.abs_diff(y).pow(2);
+                Some((distance, entity))
+            })
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, entity)| entity)
+            .filter(|&entity| world.contains(entity))
+    }
+}
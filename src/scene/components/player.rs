@@ -1,16 +1,19 @@
-use std::f32::consts::PI;
+use std::f32::consts::{FRAC_PI_4, PI};
 
 use hecs::{Entity, World};
+use serde::{Deserialize, Serialize};
 
-use crate::input::{Input, InputAction};
+use crate::input::{Input, InputAction, InputAxis};
 use crate::math::{to_point3, Ray, Vec2, Vec3};
-use crate::physics::{ColliderBuilder, ColliderHandle, Physics, RayCastResult, RigidBodyHandle};
-use crate::render::RenderTarget;
+use crate::physics::{
+    CharacterParams, ColliderBuilder, ColliderHandle, Physics, RayCastResult, RigidBodyHandle,
+    SharedShape,
+};
 use crate::render::Renderer;
 use crate::state::State;
 use crate::window::CursorGrab;
 
-use super::camera::Camera;
+use super::camera::{Camera, CameraParams};
 use super::transform::{Transform, TransformSpace};
 use super::RENDER_TAG_SCENE;
 
@@ -21,11 +24,124 @@ pub struct PlayerFocus {
     pub body: RigidBodyHandle,
 }
 
+// Movement and look tuning, saved in the settings file
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    // Units per second
+    pub fly_speed: f32,
+    pub walk_speed: f32,
+    // Units per second squared, deceleration is used when there's no input
+    pub acceleration: f32,
+    pub deceleration: f32,
+    // Speed factors while the Sprint and Slow actions are held
+    pub sprint_multiplier: f32,
+    pub slow_multiplier: f32,
+    // Radians per mouse pixel
+    pub mouse_sensitivity: f32,
+    // Seconds for the smoothed mouse motion to mostly catch up, 0 is raw
+    pub mouse_smoothing: f32,
+    // Radians per second at full stick deflection
+    pub gamepad_look_speed: f32,
+    pub invert_y: bool,
+    // Held objects are pulled with limited force and collide, instead of being teleported
+    pub physical_grab: bool,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            fly_speed: 10.0,
+            walk_speed: 5.0,
+            acceleration: 40.0,
+            deceleration: 30.0,
+            sprint_multiplier: 2.5,
+            slow_multiplier: 0.3,
+            mouse_sensitivity: 0.003,
+            mouse_smoothing: 0.02,
+            gamepad_look_speed: 2.5,
+            invert_y: false,
+            physical_grab: true,
+        }
+    }
+}
+
+impl PlayerSettings {
+    // Missing fields are defaults
+    pub fn from_yaml(yaml: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn to_yaml(self) -> String {
+        serde_yaml::to_string(&self).unwrap()
+    }
+
+    // Fields set in `yaml` replace the ones in `self`, e.g. the user's over the shipped file.
+    pub fn with_overrides(self, yaml: &str) -> anyhow::Result<Self> {
+        let mut fields = self.to_mapping();
+        fields.extend(serde_yaml::from_str::<serde_yaml::Mapping>(yaml)?);
+        Ok(serde_yaml::from_value(fields.into())?)
+    }
+
+    // Only the fields that differ from `base`, see `with_overrides`.
+    pub fn overrides_yaml(self, base: Self) -> String {
+        let base = base.to_mapping();
+        let mut fields = self.to_mapping();
+        fields.retain(|name, value| base.get(name) != Some(value));
+        serde_yaml::to_string(&fields).unwrap()
+    }
+
+    // Through the text, so floats keep their short f32 form instead of being widened to f64
+    fn to_mapping(self) -> serde_yaml::Mapping {
+        serde_yaml::from_str(&self.to_yaml()).unwrap()
+    }
+}
+
+// The character's collider, for showing it when the camera isn't in its eyes
+#[derive(Copy, Clone)]
+pub struct PlayerBody {
+    pub center: Vec3,
+    pub forward: Vec3,
+    pub half_extents: Vec3,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    FirstPerson,
+    // Behind the character, pulled in front of obstacles
+    ThirdPerson,
+    // Around a focus point, the character stays in place
+    Orbit,
+}
+
+impl CameraMode {
+    fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::FirstPerson,
+        }
+    }
+}
+
 pub struct Player {
     // TODO Extract into a component
     collider: ColliderHandle,
-    translation_acc: Vec3,
+    settings: PlayerSettings,
+    velocity: Vec3,
+    // Mouse motion in pixels, smoothed over frames
+    look_delta: Vec2,
     controlled: bool,
+    // Eyes of the character, the camera transform differs from it unless first-person
+    character: Transform,
+    camera_mode: CameraMode,
+    orbit_focus: Vec3,
+    orbit_distance: f32,
+    // With gravity and a capsule collider, otherwise flying as a ball
+    walking: bool,
+    crouching: bool,
+    vertical_speed: f32,
+    grounded: bool,
     // Ray and focus are separate because it's possible to have the ray but no focus (nothing is hit).
     // Ray is optional because the mouse cursor can go outside the window.
     focus_ray: Option<Ray>,
@@ -33,24 +149,62 @@ pub struct Player {
 }
 
 impl Player {
-    const SPEED: f32 = 10.0;
     const MIN_TOP_ANGLE: f32 = 0.1;
     const MIN_BOTTOM_ANGLE: f32 = PI - 0.1;
-    const ROTATION_SPEED: f32 = 0.003;
+    const FLYING_RADIUS: f32 = 0.5;
+    const CROUCH_SPEED: f32 = 2.0;
+    const JUMP_SPEED: f32 = 5.0;
+    const GRAVITY: f32 = 9.81;
+    // Walking capsule
+    const RADIUS: f32 = 0.4;
+    const HEIGHT: f32 = 1.8;
+    const CROUCH_HEIGHT: f32 = 1.0;
+    // Eyes (i.e. the camera) below the top of the capsule
+    const EYE_DEPTH: f32 = 0.15;
+    // Third-person camera pivot above the eyes and distance from it
+    const FOLLOW_HEIGHT: f32 = 0.5;
+    const FOLLOW_DISTANCE: f32 = 4.0;
+    // Kept from obstacles between the pivot and the camera
+    const FOLLOW_MARGIN: f32 = 0.2;
+    // Relative change per second and per wheel line
+    const ORBIT_ZOOM_SPEED: f32 = 2.0;
+    const ORBIT_ZOOM_STEP: f32 = 0.1;
+    const ORBIT_MIN_DISTANCE: f32 = 1.0;
+    const ORBIT_MAX_DISTANCE: f32 = 100.0;
+    const FLYING_PARAMS: CharacterParams = CharacterParams {
+        max_slope_climb_angle: FRAC_PI_4,
+        min_slope_slide_angle: FRAC_PI_4,
+        autostep_height: None,
+        snap_to_ground: None,
+        mass: None,
+    };
+    const WALKING_PARAMS: CharacterParams = CharacterParams {
+        max_slope_climb_angle: FRAC_PI_4,
+        min_slope_slide_angle: FRAC_PI_4,
+        autostep_height: Some(0.35),
+        snap_to_ground: Some(0.3),
+        mass: Some(80.0),
+    };
 
-    pub fn spawn(w: &mut World, rr: &Renderer, physics: &mut Physics, position: Vec3) -> Entity {
-        let rt = RenderTarget::new(rr, None);
+    pub fn spawn(
+        w: &mut World,
+        rr: &Renderer,
+        physics: &mut Physics,
+        position: Vec3,
+        settings: PlayerSettings,
+    ) -> Entity {
         let camera = Camera::new(
             rr.surface_size().width as f32 / rr.surface_size().height as f32,
+            CameraParams::default(),
             RENDER_TAG_SCENE,
-            Some(rt),
+            None,
         );
 
         let mut tr = Transform::from_pos(position);
         tr.look_at(Vec3::from_element(0.0));
 
         let collider = physics.add_collider(
-            ColliderBuilder::ball(0.5)
+            ColliderBuilder::ball(Self::FLYING_RADIUS)
                 .restitution(0.7)
                 .translation(position)
                 .build(),
@@ -59,8 +213,18 @@ impl Player {
         w.spawn((
             Self {
                 collider,
-                translation_acc: Vec3::zeros(),
+                settings,
+                velocity: Vec3::zeros(),
+                look_delta: Vec2::zeros(),
                 controlled: false,
+                character: tr,
+                camera_mode: CameraMode::FirstPerson,
+                orbit_focus: Vec3::zeros(),
+                orbit_distance: 0.0,
+                walking: false,
+                crouching: false,
+                vertical_speed: 0.0,
+                grounded: false,
                 focus_ray: None,
                 focus: None,
             },
@@ -69,6 +233,30 @@ impl Player {
         ))
     }
 
+    pub fn controlled(&self) -> bool {
+        self.controlled
+    }
+
+    pub fn settings(&self) -> &PlayerSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut PlayerSettings {
+        &mut self.settings
+    }
+
+    pub fn body(&self) -> Option<PlayerBody> {
+        if self.camera_mode == CameraMode::FirstPerson {
+            return None;
+        }
+        let (half_extents, offset) = self.body_size();
+        Some(PlayerBody {
+            center: self.character.position() - Vec3::y() * offset,
+            forward: self.character.forward(),
+            half_extents,
+        })
+    }
+
     pub fn focus_ray(&self) -> Option<Ray> {
         self.focus_ray
     }
@@ -77,19 +265,57 @@ impl Player {
         self.focus
     }
 
-    pub fn update(dt: f32, world: &mut World, physics: &mut Physics, state: &State) {
+    // Looking around is locked while e.g. a held object is being rotated.
+    pub fn update(
+        dt: f32,
+        world: &mut World,
+        physics: &mut Physics,
+        state: &State,
+        look_locked: bool,
+    ) {
         let (_, (tr, cam, this)) = world
             .query_mut::<(&mut Transform, &mut Camera, &mut Player)>()
             .into_iter()
             .next()
             .unwrap();
 
-        // Move and rotate
-        if this.controlled {
-            this.rotate(tr, &state.input);
-            this.translate(dt, tr, &state.input, physics);
+        let input = &state.input;
+        let mut character = this.character;
+
+        if input.action_activated(InputAction::ToggleWalking) {
+            this.set_walking(!this.walking, &mut character, physics);
+        }
+
+        // Smoothed motion left from before would otherwise jerk the view once rotating resumes
+        if !this.controlled || look_locked {
+            this.look_delta = Vec2::zeros();
+        }
+
+        // Move and rotate, the orbit camera takes the controls
+        let moves_character = this.controlled && this.camera_mode != CameraMode::Orbit;
+        if moves_character && !look_locked {
+            this.rotate(dt, &mut character, input);
+        }
+        // Even when not controlled, to fall and slow down
+        if this.walking {
+            this.walk(dt, &mut character, input, moves_character, physics);
         } else {
-            this.translation_acc = Vec3::zeros();
+            this.translate(dt, &mut character, input, moves_character, physics);
+        }
+        this.character = character;
+
+        if input.action_activated(InputAction::SwitchCamera) {
+            this.set_camera_mode(this.camera_mode.next(), tr);
+        }
+        match this.camera_mode {
+            CameraMode::FirstPerson => *tr = this.character,
+            CameraMode::ThirdPerson => this.follow(tr, physics),
+            CameraMode::Orbit => {
+                if this.controlled {
+                    this.orbit(dt, tr, input, look_locked);
+                }
+                tr.set_position(this.orbit_focus - tr.forward() * this.orbit_distance);
+            }
         }
 
         if state.input.action_activated(InputAction::ControlPlayer) {
@@ -105,50 +331,261 @@ impl Player {
         dt: f32,
         transform: &mut Transform,
         input: &Input,
+        controlled: bool,
         physics: &mut Physics,
     ) {
-        let mut translation: Vec3 = Vec3::from_element(0.0);
+        let mut direction = Vec3::zeros();
+        if controlled {
+            // Analog, partial deflection moves slower
+            direction = transform.right() * input.axis(InputAxis::MoveX)
+                + transform.up() * input.axis(InputAxis::MoveY)
+                + transform.forward() * input.axis(InputAxis::MoveZ);
+            // Not faster than full speed when combining directions or devices
+            if direction.magnitude() > 1.0 {
+                direction.normalize_mut();
+            }
+        }
+        let speed = self.settings.fly_speed * self.speed_multiplier(input);
+        self.accelerate(direction * speed, dt);
 
-        if input.action_active(InputAction::MoveForward) {
-            translation += transform.forward();
+        let movement =
+            physics.move_character(dt, self.velocity * dt, self.collider, &Self::FLYING_PARAMS);
+        // Obstacles take the speed away
+        if dt > 0.0 {
+            self.velocity = movement.translation / dt;
         }
-        if input.action_active(InputAction::MoveBack) {
-            translation -= transform.forward();
+
+        transform.translate(movement.translation);
+        physics
+            .collider_mut(self.collider)
+            .set_translation(movement.collider_position + movement.translation);
+    }
+
+    // Changes the velocity towards the target one at the configured rates.
+    fn accelerate(&mut self, target: Vec3, dt: f32) {
+        let rate = if target == Vec3::zeros() {
+            self.settings.deceleration
+        } else {
+            self.settings.acceleration
+        };
+        let diff = target - self.velocity;
+        let step = rate * dt;
+        if diff.magnitude() <= step {
+            self.velocity = target;
+        } else {
+            self.velocity += diff.normalize() * step;
         }
-        if input.action_active(InputAction::MoveRight) {
-            translation += transform.right();
+    }
+
+    fn speed_multiplier(&self, input: &Input) -> f32 {
+        if input.action_active(InputAction::Sprint) {
+            self.settings.sprint_multiplier
+        } else if input.action_active(InputAction::Slow) {
+            self.settings.slow_multiplier
+        } else {
+            1.0
         }
-        if input.action_active(InputAction::MoveLeft) {
-            translation -= transform.right();
+    }
+
+    fn walk(
+        &mut self,
+        dt: f32,
+        transform: &mut Transform,
+        input: &Input,
+        controlled: bool,
+        physics: &mut Physics,
+    ) {
+        let crouch = controlled && input.action_active(InputAction::Crouch);
+        if crouch != self.crouching {
+            self.set_crouching(crouch, transform, physics);
+        }
+
+        let mut translation = Vec3::zeros();
+        if controlled {
+            // Along the ground whatever the pitch
+            let flat = |v: Vec3| Vec3::new(v.x, 0.0, v.z).try_normalize(1e-6);
+            if let (Some(right), Some(forward)) =
+                (flat(transform.right()), flat(transform.forward()))
+            {
+                translation =
+                    right * input.axis(InputAxis::MoveX) + forward * input.axis(InputAxis::MoveZ);
+            }
+            if translation.magnitude() > 1.0 {
+                translation.normalize_mut();
+            }
+
+            if self.grounded && !self.crouching && input.action_activated(InputAction::Jump) {
+                self.vertical_speed = Self::JUMP_SPEED;
+            }
         }
-        if input.action_active(InputAction::MoveUp) {
-            translation += transform.up();
+        let speed = if self.crouching {
+            Self::CROUCH_SPEED
+        } else {
+            self.settings.walk_speed * self.speed_multiplier(input)
+        };
+        // Vertical speed is separate
+        self.accelerate(translation * speed, dt);
+        self.vertical_speed -= Self::GRAVITY * dt;
+        let desired = (self.velocity + Vec3::y() * self.vertical_speed) * dt;
+
+        let movement = physics.move_character(dt, desired, self.collider, &Self::WALKING_PARAMS);
+        self.grounded = movement.grounded;
+        // Landed or hit the head
+        if (self.grounded && self.vertical_speed < 0.0)
+            || (self.vertical_speed > 0.0 && movement.translation.y < desired.y * 0.5)
+        {
+            self.vertical_speed = 0.0;
         }
-        if input.action_active(InputAction::MoveDown) {
-            translation -= transform.up();
+
+        transform.translate(movement.translation);
+        physics
+            .collider_mut(self.collider)
+            .set_translation(movement.collider_position + movement.translation);
+    }
+
+    fn set_walking(&mut self, walking: bool, transform: &mut Transform, physics: &mut Physics) {
+        self.walking = walking;
+        self.crouching = false;
+        self.grounded = false;
+        self.vertical_speed = 0.0;
+        self.velocity = Vec3::zeros();
+
+        // Lift the capsule out of the ground
+        let eye_height = Self::HEIGHT - Self::EYE_DEPTH;
+        if walking
+            && let Some(hit) =
+                physics.cast_ray(transform.position(), -Vec3::y(), Some(self.collider))
+            && hit.distance < eye_height
+        {
+            transform.translate(Vec3::y() * (eye_height - hit.distance));
         }
 
-        // Apply only if there's anything to apply. Otherwise getting NaN after normalize() :|
-        if translation.magnitude() > 0.01 {
-            self.translation_acc += translation.normalize() * dt * Self::SPEED;
+        self.update_collider(transform, physics);
+    }
+
+    fn set_crouching(&mut self, crouching: bool, transform: &mut Transform, physics: &mut Physics) {
+        let height_change = Self::HEIGHT - Self::CROUCH_HEIGHT;
+        // Not standing up under something
+        if !crouching
+            && physics
+                .cast_ray(transform.position(), Vec3::y(), Some(self.collider))
+                .is_some_and(|hit| hit.distance < height_change + Self::EYE_DEPTH)
+        {
+            return;
         }
 
-        let (possible_translation, collider_current_pos) =
-            physics.move_character(dt, self.translation_acc, self.collider);
-        self.translation_acc = possible_translation;
+        self.crouching = crouching;
+        // Feet stay in place
+        transform.translate(
+            Vec3::y()
+                * if crouching {
+                    -height_change
+                } else {
+                    height_change
+                },
+        );
+        self.update_collider(transform, physics);
+    }
 
-        let translation = Self::SPEED * dt * self.translation_acc;
-        self.translation_acc -= translation;
+    // Matches the collider to the movement mode and the eyes position.
+    fn update_collider(&self, transform: &Transform, physics: &mut Physics) {
+        let (half_extents, offset) = self.body_size();
+        let shape = if self.walking {
+            SharedShape::capsule_y(half_extents.y - Self::RADIUS, Self::RADIUS)
+        } else {
+            SharedShape::ball(Self::FLYING_RADIUS)
+        };
+        let collider = physics.collider_mut(self.collider);
+        collider.set_shape(shape);
+        collider.set_translation(transform.position() - Vec3::y() * offset);
+    }
 
-        transform.translate(translation);
-        physics
-            .collider_mut(self.collider)
-            .set_translation(collider_current_pos + translation);
+    // Collider half extents and how far its center is below the eyes.
+    fn body_size(&self) -> (Vec3, f32) {
+        if self.walking {
+            let height = if self.crouching {
+                Self::CROUCH_HEIGHT
+            } else {
+                Self::HEIGHT
+            };
+            (
+                Vec3::new(Self::RADIUS, height / 2.0, Self::RADIUS),
+                height / 2.0 - Self::EYE_DEPTH,
+            )
+        } else {
+            (Vec3::from_element(Self::FLYING_RADIUS), 0.0)
+        }
     }
 
-    fn rotate(&mut self, transform: &mut Transform, input: &Input) {
-        let h_delta = input.mouse_delta().0 * Self::ROTATION_SPEED;
-        let mut v_delta = input.mouse_delta().1 * Self::ROTATION_SPEED;
+    fn set_camera_mode(&mut self, mode: CameraMode, camera_tr: &Transform) {
+        if mode == CameraMode::Orbit {
+            // Around what's looked at
+            self.orbit_focus = self.focus.map_or(
+                camera_tr.position() + camera_tr.forward() * Self::FOLLOW_DISTANCE,
+                |f| f.point,
+            );
+            self.orbit_distance = (camera_tr.position() - self.orbit_focus)
+                .magnitude()
+                .clamp(Self::ORBIT_MIN_DISTANCE, Self::ORBIT_MAX_DISTANCE);
+        }
+        self.camera_mode = mode;
+    }
+
+    // Behind the character looking where it looks.
+    fn follow(&self, camera_tr: &mut Transform, physics: &Physics) {
+        let pivot = self.character.position() + Vec3::y() * Self::FOLLOW_HEIGHT;
+        let back = -self.character.forward();
+        let distance = physics
+            .cast_ray(pivot, back, Some(self.collider))
+            .map_or(Self::FOLLOW_DISTANCE, |hit| {
+                (hit.distance - Self::FOLLOW_MARGIN).clamp(0.0, Self::FOLLOW_DISTANCE)
+            });
+        *camera_tr = self.character;
+        camera_tr.set_position(pivot + back * distance);
+    }
+
+    // Rotates around the focus point, moves it in the view space and zooms.
+    fn orbit(&mut self, dt: f32, camera_tr: &mut Transform, input: &Input, look_locked: bool) {
+        if !look_locked {
+            self.rotate(dt, camera_tr, input);
+        }
+
+        let mut pan = camera_tr.right() * input.axis(InputAxis::MoveX)
+            + camera_tr.up() * input.axis(InputAxis::MoveY)
+            + camera_tr.forward() * input.axis(InputAxis::MoveZ);
+        if pan.magnitude() > 1.0 {
+            pan.normalize_mut();
+        }
+        self.orbit_focus += pan * self.settings.fly_speed * self.speed_multiplier(input) * dt;
+
+        let zoom = input.axis(InputAxis::Zoom) * Self::ORBIT_ZOOM_SPEED * dt
+            + input.axis_delta(InputAxis::Zoom) * Self::ORBIT_ZOOM_STEP;
+        self.orbit_distance = (self.orbit_distance * (1.0 - zoom))
+            .clamp(Self::ORBIT_MIN_DISTANCE, Self::ORBIT_MAX_DISTANCE);
+    }
+
+    fn rotate(&mut self, dt: f32, transform: &mut Transform, input: &Input) {
+        let settings = &self.settings;
+        let mouse_delta = Vec2::new(
+            input.axis_delta(InputAxis::LookX),
+            input.axis_delta(InputAxis::LookY),
+        );
+        // Exponential, so that the total motion is kept
+        let k = if settings.mouse_smoothing > 0.0 {
+            1.0 - (-dt / settings.mouse_smoothing).exp()
+        } else {
+            1.0
+        };
+        self.look_delta += (mouse_delta - self.look_delta) * k;
+
+        let h_delta = self.look_delta.x * settings.mouse_sensitivity
+            + input.axis(InputAxis::LookX) * settings.gamepad_look_speed * dt;
+        // Positive is down here
+        let mut v_delta = -self.look_delta.y * settings.mouse_sensitivity
+            - input.axis(InputAxis::LookY) * settings.gamepad_look_speed * dt;
+        if settings.invert_y {
+            v_delta = -v_delta;
+        }
 
         // Protect from overturning: stop the camera from reaching the vertical line by small
         // margin angles.
@@ -174,25 +611,9 @@ impl Player {
                 state.window.inner_size().width as f32,
                 state.window.inner_size().height as f32,
             );
-            // Normalized device coordinates (-1..1)
-            let mut cursor_ndc_pos =
-                (cursor_pos.component_div(&canvas_size)) * 2.0 - Vec2::from_element(1.0);
-            // Needed for some reason... Is there a bug somewhere that gets compensated by this, or is wgpu
-            // NDC origin in the lower left window corner?
-            cursor_ndc_pos.y *= -1.0;
-            let m = tr.matrix() * cam.proj_matrix().try_inverse().unwrap();
-            let cursor_world_pos = m.transform_point(&to_point3(Vec3::new(
-                cursor_ndc_pos.x,
-                cursor_ndc_pos.y,
-                -1.0,
-            )));
-            let cursor_world_pos =
-                Vec3::new(cursor_world_pos.x, cursor_world_pos.y, cursor_world_pos.z);
-
-            let orig = tr.position();
-            let dir = (cursor_world_pos - orig).normalize();
-
-            Some((tr.position(), dir))
+            let (near, far) = cam.window_to_world(tr, cursor_pos, canvas_size);
+
+            Some((near, (far - near).normalize()))
         } else {
             None
         };
@@ -217,3 +638,51 @@ impl Player {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let yaml = String::from_utf8_lossy(include_bytes!("../../../assets/settings.yml"));
+        PlayerSettings::from_yaml(&yaml).unwrap();
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = PlayerSettings {
+            walk_speed: 3.0,
+            invert_y: true,
+            ..PlayerSettings::default()
+        };
+        let reloaded = PlayerSettings::from_yaml(&settings.to_yaml()).unwrap();
+        assert_eq!(reloaded, settings);
+
+        let partial = PlayerSettings::from_yaml("fly_speed: 20.0\nphysical_grab: false").unwrap();
+        assert_eq!(
+            partial,
+            PlayerSettings {
+                fly_speed: 20.0,
+                physical_grab: false,
+                ..PlayerSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn settings_overrides() {
+        let base = PlayerSettings::default();
+        let settings = PlayerSettings {
+            mouse_sensitivity: 0.005,
+            ..base
+        };
+        let yaml = settings.overrides_yaml(base);
+        assert_eq!(yaml.trim(), "mouse_sensitivity: 0.005");
+        assert_eq!(base.with_overrides(&yaml).unwrap(), settings);
+        assert_eq!(
+            base.with_overrides(&base.overrides_yaml(base)).unwrap(),
+            base
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use hecs::{With, World};
+
+use crate::math::Vec3;
+
+use super::{Player, RENDER_TAG_HIDDEN, RENDER_TAG_SCENE, RenderTags, Transform};
+
+// Shows the player's body when the camera isn't first-person
+pub struct PlayerAvatar;
+
+impl PlayerAvatar {
+    pub fn update(world: &mut World) {
+        let body = {
+            let (_, player) = world.query_mut::<&Player>().into_iter().next().unwrap();
+            player.body()
+        };
+
+        for (_, (tr, tags)) in
+            world.query_mut::<With<(&mut Transform, &mut RenderTags), &PlayerAvatar>>()
+        {
+            let Some(body) = body else {
+                tags.0 = RENDER_TAG_HIDDEN;
+                continue;
+            };
+            tr.set_position(body.center);
+            // Turned around the vertical axis only
+            let forward = Vec3::new(body.forward.x, 0.0, body.forward.z);
+            if forward.magnitude() > 1e-6 {
+                tr.look_at(body.center + forward);
+            }
+            tr.set_scale(body.half_extents);
+            tags.0 = RENDER_TAG_SCENE;
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use hecs::World;
+
+use crate::render::{
+    RenderPassParams, RenderTarget, RenderTargetParams, Renderer, SamplerParams, Texture,
+    TextureSize,
+};
+use crate::scene::materials::PostProcessMaterial;
+use crate::scene::{Assets, MeshHandle};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TonemappingOperator {
+    Reinhard,
+    Aces,
+}
+
+pub enum PostProcessEffect {
+    // Brightness above the threshold bleeds into the neighbourhood. Should go before tonemapping.
+    Bloom {
+        threshold: f32,
+        knee: f32,
+        intensity: f32,
+        radius: f32,
+    },
+    Vignette {
+        radius: f32,
+        intensity: f32,
+    },
+    // Brings the HDR image into the displayable range.
+    Tonemapping {
+        operator: TonemappingOperator,
+        exposure: f32,
+    },
+    // Applied on top of the sRGB encoding, 1.0 means no change.
+    Gamma {
+        gamma: f32,
+    },
+    // Path to a 256x16 strip LUT (16 slices of 16x16).
+    ColorGrading {
+        lut: String,
+        intensity: f32,
+    },
+    Fxaa {
+        span: f32,
+    },
+    ChromaticAberration {
+        intensity: f32,
+    },
+}
+
+impl PostProcessEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostProcessEffect::Bloom { .. } => "Bloom",
+            PostProcessEffect::Vignette { .. } => "Vignette",
+            PostProcessEffect::Tonemapping { .. } => "Tonemapping",
+            PostProcessEffect::Gamma { .. } => "Gamma",
+            PostProcessEffect::ColorGrading { .. } => "Color grading",
+            PostProcessEffect::Fxaa { .. } => "FXAA",
+            PostProcessEffect::ChromaticAberration { .. } => "Chromatic aberration",
+        }
+    }
+
+    fn shader(&self) -> &'static str {
+        match self {
+            PostProcessEffect::Bloom { .. } => "post-process-bloom-composite.wgsl",
+            PostProcessEffect::Vignette { .. } => "post-process-vignette.wgsl",
+            PostProcessEffect::Tonemapping { .. } => "post-process-tonemapping.wgsl",
+            PostProcessEffect::Gamma { .. } => "post-process-gamma.wgsl",
+            PostProcessEffect::ColorGrading { .. } => "post-process-color-grading.wgsl",
+            PostProcessEffect::Fxaa { .. } => "post-process-fxaa.wgsl",
+            PostProcessEffect::ChromaticAberration { .. } => {
+                "post-process-chromatic-aberration.wgsl"
+            }
+        }
+    }
+
+    // Packed into the `values` field of the effect's uniform, see the shaders.
+    fn values(&self) -> [f32; 4] {
+        match self {
+            PostProcessEffect::Bloom {
+                threshold,
+                knee,
+                intensity,
+                radius,
+            } => [*threshold, *knee, *intensity, *radius],
+            PostProcessEffect::Vignette { radius, intensity } => [*radius, *intensity, 0.0, 0.0],
+            PostProcessEffect::Tonemapping { operator, exposure } => {
+                let operator = match operator {
+                    TonemappingOperator::Reinhard => 0.0,
+                    TonemappingOperator::Aces => 1.0,
+                };
+                [*exposure, operator, 0.0, 0.0]
+            }
+            PostProcessEffect::Gamma { gamma } => [*gamma, 0.0, 0.0, 0.0],
+            PostProcessEffect::ColorGrading { intensity, .. } => [*intensity, 0.0, 0.0, 0.0],
+            PostProcessEffect::Fxaa { span } => [*span, 0.0, 0.0, 0.0],
+            PostProcessEffect::ChromaticAberration { intensity } => [*intensity, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+pub struct PostProcessPass {
+    pub effect: PostProcessEffect,
+    pub enabled: bool,
+}
+
+#[derive(Copy, Clone)]
+enum StepTarget {
+    Screen,
+    PingPong(usize),
+    BloomDown(usize),
+    BloomUp(usize),
+}
+
+// A single full-screen draw. Most passes need only one, bloom needs several.
+struct Step {
+    material: PostProcessMaterial,
+    target: StepTarget,
+    // Pass whose parameters the step uses
+    pass: Option<usize>,
+    // Texel size of the main input
+    texel_size: [f32; 2],
+}
+
+// Applies a stack of effects to its input and outputs the result to the screen.
+pub struct PostProcessor {
+    // Screen-sized, cameras without own target render here
+    input: RenderTarget,
+    passes: Vec<PostProcessPass>,
+    // Intermediate results, each enabled pass reads from one and writes to the other.
+    targets: [RenderTarget; 2],
+    // Bloom mip chain, progressively halved. Only allocated when bloom is enabled.
+    bloom_down_targets: Vec<RenderTarget>,
+    // Same sizes as the down chain except for the smallest mip which is used directly.
+    bloom_up_targets: Vec<RenderTarget>,
+    // Steps of the enabled passes, in order. When nothing is enabled, contains a single
+    // step that simply copies the source to the screen.
+    steps: Vec<Step>,
+    quad: MeshHandle,
+    // Steps need rebuilding, e.g. when the passes or the targets have changed.
+    dirty: bool,
+}
+
+impl PostProcessor {
+    const BLOOM_MIPS: usize = 6;
+
+    pub fn new(rr: &Renderer, quad: MeshHandle) -> Self {
+        let target_params = RenderTargetParams {
+            format: Some(rr.hdr_texture_format()),
+            ..Default::default()
+        };
+        let input = RenderTarget::new(
+            rr,
+            RenderTargetParams {
+                sample_count: Some(rr.sample_count()),
+                ..target_params
+            },
+        );
+
+        Self {
+            input,
+            passes: vec![
+                PostProcessPass {
+                    effect: PostProcessEffect::Tonemapping {
+                        operator: TonemappingOperator::Aces,
+                        exposure: 1.0,
+                    },
+                    enabled: true,
+                },
+                PostProcessPass {
+                    effect: PostProcessEffect::Vignette {
+                        radius: 1.0,
+                        intensity: 1.0,
+                    },
+                    enabled: true,
+                },
+            ],
+            targets: [
+                RenderTarget::new(rr, target_params),
+                RenderTarget::new(rr, target_params),
+            ],
+            bloom_down_targets: Vec::new(),
+            bloom_up_targets: Vec::new(),
+            steps: Vec::new(),
+            quad,
+            dirty: true,
+        }
+    }
+
+    pub fn input(&self) -> &RenderTarget {
+        &self.input
+    }
+
+    pub fn passes(&self) -> &[PostProcessPass] {
+        &self.passes
+    }
+
+    pub fn set_passes(&mut self, passes: Vec<PostProcessPass>) {
+        self.passes = passes;
+        self.dirty = true;
+    }
+
+    // Parameters can be changed freely, they're uploaded every frame.
+    pub fn effect_mut(&mut self, idx: usize) -> &mut PostProcessEffect {
+        &mut self.passes[idx].effect
+    }
+
+    pub fn set_enabled(&mut self, idx: usize, enabled: bool) {
+        self.passes[idx].enabled = enabled;
+        self.dirty = true;
+    }
+
+    pub fn swap_passes(&mut self, idx1: usize, idx2: usize) {
+        self.passes.swap(idx1, idx2);
+        self.dirty = true;
+    }
+
+    pub fn resize(&mut self, new_size: TextureSize, rr: &Renderer) {
+        self.input.resize(new_size, rr);
+        for target in &mut self.targets {
+            target.resize(new_size, rr);
+        }
+        self.dirty = true;
+    }
+
+    // The input follows the renderer's sample count like the scene cameras.
+    pub fn set_sample_count(&mut self, sample_count: u32, rr: &Renderer) {
+        self.input.set_sample_count(sample_count, rr);
+        self.dirty = true;
+    }
+
+    pub fn update(world: &mut World, rr: &Renderer, assets: &mut Assets) {
+        for (_, pp) in world.query_mut::<&mut PostProcessor>() {
+            if pp.dirty {
+                pp.rebuild(rr, assets);
+                pp.dirty = false;
+            }
+
+            for step in &pp.steps {
+                let values = step
+                    .pass
+                    .map_or([0.0; 4], |idx| pp.passes[idx].effect.values());
+                step.material.set_params(rr, values, step.texel_size);
+            }
+        }
+    }
+
+    pub fn render(&self, rr: &Renderer, assets: &Assets) {
+        let quad = assets.mesh(self.quad);
+        for step in &self.steps {
+            let target = match step.target {
+                StepTarget::Screen => None,
+                StepTarget::PingPong(idx) => Some(&self.targets[idx]),
+                StepTarget::BloomDown(mip) => Some(&self.bloom_down_targets[mip]),
+                StepTarget::BloomUp(mip) => Some(&self.bloom_up_targets[mip]),
+            };
+            let bundle = rr.build_render_bundle(quad, &step.material, target);
+            rr.render_pass(
+                &[bundle],
+                RenderPassParams {
+                    target,
+                    ..Default::default()
+                },
+                None,
+            );
+        }
+    }
+
+    fn rebuild(&mut self, rr: &Renderer, assets: &mut Assets) {
+        let enabled = self
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.enabled)
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        let has_bloom = enabled
+            .iter()
+            .any(|&idx| matches!(self.passes[idx].effect, PostProcessEffect::Bloom { .. }));
+        let size = self.input.size();
+        self.rebuild_bloom_targets(rr, has_bloom.then_some(size));
+
+        let source = &self.input;
+
+        if enabled.is_empty() {
+            let shader = assets.add_shader_from_file(rr, "post-process-copy.wgsl");
+            self.steps = vec![new_step(
+                rr,
+                assets.shader(shader),
+                source,
+                None,
+                StepTarget::Screen,
+                None,
+            )];
+            return;
+        }
+
+        let mut steps = Vec::new();
+        let last = enabled.len() - 1;
+        for (i, pass_idx) in enabled.into_iter().enumerate() {
+            let effect = &self.passes[pass_idx].effect;
+            let input = if i == 0 {
+                source
+            } else {
+                &self.targets[(i - 1) % 2]
+            };
+            let output = if i == last {
+                StepTarget::Screen
+            } else {
+                StepTarget::PingPong(i % 2)
+            };
+
+            let shader = assets.add_shader_from_file(rr, effect.shader());
+            let extra_texture = match effect {
+                PostProcessEffect::Bloom { .. } => {
+                    self.add_bloom_steps(rr, assets, input, pass_idx, &mut steps);
+                    Some(
+                        self.bloom_up_targets
+                            .first()
+                            .unwrap_or(&self.bloom_down_targets[0])
+                            .color_tex(),
+                    )
+                }
+                PostProcessEffect::ColorGrading { lut, .. } => {
+                    let lut = assets.add_2d_texture_from_file(rr, lut, SamplerParams::default());
+                    Some(assets.texture(lut))
+                }
+                _ => None,
+            };
+
+            steps.push(new_step(
+                rr,
+                assets.shader(shader),
+                input,
+                extra_texture,
+                output,
+                Some(pass_idx),
+            ));
+        }
+
+        self.steps = steps;
+    }
+
+    // Down-samples the bright parts of the input through the mip chain and then accumulates
+    // them back up. The result ends up in the largest mip.
+    fn add_bloom_steps(
+        &self,
+        rr: &Renderer,
+        assets: &mut Assets,
+        input: &RenderTarget,
+        pass_idx: usize,
+        steps: &mut Vec<Step>,
+    ) {
+        let prefilter_shader = assets.add_shader_from_file(rr, "post-process-bloom-prefilter.wgsl");
+        let down_shader = assets.add_shader_from_file(rr, "post-process-bloom-downsample.wgsl");
+        let up_shader = assets.add_shader_from_file(rr, "post-process-bloom-upsample.wgsl");
+
+        for mip in 0..self.bloom_down_targets.len() {
+            let (shader, src) = if mip == 0 {
+                (prefilter_shader, input)
+            } else {
+                (down_shader, &self.bloom_down_targets[mip - 1])
+            };
+            steps.push(new_step(
+                rr,
+                assets.shader(shader),
+                src,
+                None,
+                StepTarget::BloomDown(mip),
+                Some(pass_idx),
+            ));
+        }
+
+        for mip in (0..self.bloom_up_targets.len()).rev() {
+            let lower = self
+                .bloom_up_targets
+                .get(mip + 1)
+                .unwrap_or(&self.bloom_down_targets[mip + 1]);
+            steps.push(new_step(
+                rr,
+                assets.shader(up_shader),
+                lower,
+                Some(self.bloom_down_targets[mip].color_tex()),
+                StepTarget::BloomUp(mip),
+                Some(pass_idx),
+            ));
+        }
+    }
+
+    // No size releases the targets.
+    fn rebuild_bloom_targets(&mut self, rr: &Renderer, size: Option<TextureSize>) {
+        self.bloom_down_targets.clear();
+        self.bloom_up_targets.clear();
+
+        let Some((width, height)) = size else {
+            return;
+        };
+
+        let mut size = ((width / 2).max(1), (height / 2).max(1));
+        for _ in 0..Self::BLOOM_MIPS {
+            let params = RenderTargetParams {
+                size: Some(size),
+                format: Some(rr.hdr_texture_format()),
+                ..Default::default()
+            };
+            self.bloom_down_targets.push(RenderTarget::new(rr, params));
+            if size.0 == 1 || size.1 == 1 {
+                break;
+            }
+            size = (size.0 / 2, size.1 / 2);
+        }
+
+        let up_count = self.bloom_down_targets.len() - 1;
+        for mip in 0..up_count {
+            let params = RenderTargetParams {
+                size: Some(self.bloom_down_targets[mip].size()),
+                format: Some(rr.hdr_texture_format()),
+                ..Default::default()
+            };
+            self.bloom_up_targets.push(RenderTarget::new(rr, params));
+        }
+    }
+}
+
+fn new_step(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    input: &RenderTarget,
+    extra_texture: Option<&Texture>,
+    target: StepTarget,
+    pass: Option<usize>,
+) -> Step {
+    // Intermediate targets are never multisampled
+    let (target_format, target_sample_count) = match target {
+        StepTarget::Screen => (rr.surface_texture_format(), rr.sample_count()),
+        _ => (rr.hdr_texture_format(), 1),
+    };
+    let (width, height) = input.size();
+
+    Step {
+        material: PostProcessMaterial::new(
+            rr,
+            shader,
+            input.color_tex(),
+            extra_texture,
+            target_format,
+            target_sample_count,
+        ),
+        target,
+        pass,
+        texel_size: [1.0 / width as f32, 1.0 / height as f32],
+    }
+}
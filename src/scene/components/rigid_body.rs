@@ -1,8 +1,11 @@
+use hecs::{Entity, World};
 use rapier3d::prelude::*;
 
-use crate::math::Vec3;
+use crate::math::{UnitQuat, Vec3};
 use crate::physics::Physics;
 
+use super::Transform;
+
 pub struct RigidBodyParams {
     pub pos: Vec3,
     pub scale: Vec3,
@@ -35,6 +38,32 @@ impl RigidBody {
         Self { handle, movable }
     }
 
+    // Places the entity's body, or only the transform if there's none. Box colliders are scaled
+    // along.
+    pub fn set_pose(
+        world: &mut World,
+        physics: &mut Physics,
+        entity: Entity,
+        pos: Vec3,
+        rotation: UnitQuat,
+        scale: Vec3,
+    ) {
+        let mut q = world
+            .query_one::<(&mut Transform, Option<&RigidBody>)>(entity)
+            .unwrap();
+        let (tr, body) = q.get().unwrap();
+        if let Some(body) = body {
+            if scale != tr.scale() {
+                physics.scale_body_colliders(body.handle(), scale.component_div(&tr.scale()));
+            }
+            let body = physics.body_mut(body.handle());
+            body.set_translation(pos, true);
+            body.set_rotation(rotation, true);
+        }
+        tr.set(pos, *rotation.inverse().quaternion());
+        tr.set_scale(scale);
+    }
+
     pub fn handle(&self) -> RigidBodyHandle {
         self.handle
     }
@@ -48,6 +77,17 @@ impl RigidBody {
         };
         body.set_body_type(new_type, true);
     }
+
+    // Fixed when not movable. `apply` is off while the body is kinematic, the new type then
+    // applies when that ends.
+    pub fn set_movable(&mut self, physics: &mut Physics, movable: bool, apply: bool) {
+        self.movable = movable;
+        if apply {
+            physics
+                .body_mut(self.handle)
+                .set_body_type(body_type(movable), true);
+        }
+    }
 }
 
 fn body_type(movable: bool) -> RigidBodyType {
@@ -0,0 +1,370 @@
+use hecs::{Entity, World};
+
+use crate::input::InputAction;
+use crate::math::{Vec2, Vec3};
+use crate::physics::Physics;
+use crate::render::Renderer;
+use crate::scene::{Assets, MaterialHandle, materials};
+use crate::state::State;
+
+use super::{
+    Camera, CameraTexture, Grab, Material, Mesh, Picker, Player, RenderOrder, RenderTags,
+    RigidBody, Transform,
+};
+
+// Marks a selected entity, rendered once more on top with the highlight material
+pub struct Selected {
+    pub highlight: MaterialHandle,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SelectionCommand {
+    Delete,
+    Duplicate,
+    // Fixed bodies become movable and vice versa
+    ToggleFreeze,
+    Clear,
+}
+
+// Window positions in pixels where a selection drag started and where it is now
+struct Drag {
+    start: Vec2,
+    end: Vec2,
+    // Adds to the current selection instead of replacing it
+    additive: bool,
+}
+
+// Selected bodies, picked by clicking or by dragging a rectangle when the mouse isn't captured.
+// Grabbing one of them moves them all.
+#[derive(Default)]
+pub struct Selection {
+    drag: Option<Drag>,
+    count: usize,
+    // Set by the HUD, run on the next update
+    pub command: Option<SelectionCommand>,
+}
+
+impl Selection {
+    // Shorter drags are clicks
+    const DRAG_THRESHOLD: f32 = 4.0;
+    const HIGHLIGHT_COLOR: Vec3 = Vec3::new(1.0, 0.8, 0.1);
+    // Copies are placed next to the originals with this gap
+    const DUPLICATE_GAP: f32 = 0.5;
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    // Corners of the rectangle being dragged, in window pixels.
+    pub fn drag_rect(&self) -> Option<(Vec2, Vec2)> {
+        self.drag
+            .as_ref()
+            .filter(|drag| drag.is_rect())
+            .map(|drag| (drag.start.inf(&drag.end), drag.start.sup(&drag.end)))
+    }
+
+    pub fn update(world: &mut World, physics: &mut Physics, state: &State, assets: &mut Assets) {
+        let input = &state.input;
+        let rr = &state.renderer;
+        let window_size = Vec2::new(
+            state.window.inner_size().width as f32,
+            state.window.inner_size().height as f32,
+        );
+        let (controlled, focus_body) = {
+            let (_, player) = world.query_mut::<&Player>().into_iter().next().unwrap();
+            (player.controlled(), player.focus().map(|f| f.body))
+        };
+        // The captured mouse always points at the window center
+        let cursor = input
+            .cursor_position()
+            .filter(|_| !controlled)
+            .map_or(window_size / 2.0, |(x, y)| Vec2::new(x, y));
+
+        let command = {
+            let (_, selection) = world
+                .query_mut::<&mut Selection>()
+                .into_iter()
+                .next()
+                .unwrap();
+            selection.command.take()
+        };
+        if input.action_activated(InputAction::DeleteSelected) {
+            Self::delete(world, physics, assets);
+        } else if input.action_activated(InputAction::DuplicateSelected) {
+            Self::duplicate(world, physics, rr, assets);
+        } else if input.action_activated(InputAction::FreezeSelected) {
+            Self::toggle_freeze(world, physics);
+        }
+        match command {
+            Some(SelectionCommand::Delete) => Self::delete(world, physics, assets),
+            Some(SelectionCommand::Duplicate) => Self::duplicate(world, physics, rr, assets),
+            Some(SelectionCommand::ToggleFreeze) => Self::toggle_freeze(world, physics),
+            Some(SelectionCommand::Clear) => Self::clear(world, assets),
+            None => {}
+        }
+
+        let finished_drag = {
+            let (_, selection) = world
+                .query_mut::<&mut Selection>()
+                .into_iter()
+                .next()
+                .unwrap();
+            let add = input.action_activated(InputAction::AddToSelection);
+            if add || input.action_activated(InputAction::Select) {
+                selection.drag = Some(Drag {
+                    start: cursor,
+                    end: cursor,
+                    additive: add,
+                });
+            }
+            if input.action_active(InputAction::Select)
+                || input.action_active(InputAction::AddToSelection)
+            {
+                if let Some(drag) = &mut selection.drag {
+                    drag.end = cursor;
+                }
+                None
+            } else {
+                selection.drag.take()
+            }
+        };
+
+        if let Some(drag) = finished_drag {
+            let picked = if !drag.is_rect() && Picker::enabled(world) {
+                // Also finds what has no body
+                Picker::pick(world, rr, assets, cursor)
+                    .into_iter()
+                    .collect()
+            } else {
+                let bodies = if drag.is_rect() {
+                    Self::bodies_in_rect(world, physics, &drag, window_size)
+                } else {
+                    focus_body.into_iter().collect()
+                };
+                world
+                    .query_mut::<&RigidBody>()
+                    .into_iter()
+                    .filter(|(_, body)| bodies.contains(&body.handle()))
+                    .map(|(e, _)| e)
+                    .collect::<Vec<_>>()
+            };
+
+            if !drag.additive {
+                Self::clear(world, assets);
+            }
+            // Clicking a selected body with the modifier deselects it
+            let toggle = drag.additive && !drag.is_rect();
+            for e in picked {
+                if toggle && world.get::<&Selected>(e).is_ok() {
+                    deselect(world, e, assets);
+                } else {
+                    select(world, e, rr, assets);
+                }
+            }
+        }
+
+        let count = world.query_mut::<&Selected>().into_iter().len();
+        let (_, selection) = world
+            .query_mut::<&mut Selection>()
+            .into_iter()
+            .next()
+            .unwrap();
+        selection.count = count;
+    }
+
+    fn bodies_in_rect(
+        world: &mut World,
+        physics: &Physics,
+        drag: &Drag,
+        window_size: Vec2,
+    ) -> Vec<rapier3d::prelude::RigidBodyHandle> {
+        let (_, (_, cam, cam_tr)) = world
+            .query_mut::<(&Player, &Camera, &Transform)>()
+            .into_iter()
+            .next()
+            .unwrap();
+
+        // Rays under the rectangle corners, in order around it
+        let (min, max) = (drag.start.inf(&drag.end), drag.start.sup(&drag.end));
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            .map(|corner| cam.window_to_world(cam_tr, corner, window_size));
+        let (center_near, center_far) = cam.window_to_world(cam_tr, (min + max) / 2.0, window_size);
+        let inside = center_near + (center_far - center_near).normalize();
+
+        // Sides of the frustum under the rectangle, plus the camera plane to skip what's behind
+        // with orthographic projection
+        let mut planes = (0..4)
+            .map(|i| {
+                let (near, far) = corners[i];
+                let (next_near, _) = corners[(i + 1) % 4];
+                let normal = (next_near - near).cross(&(far - near));
+                let distance = -normal.dot(&near);
+                if normal.dot(&inside) + distance < 0.0 {
+                    (-normal, -distance)
+                } else {
+                    (normal, distance)
+                }
+            })
+            .collect::<Vec<_>>();
+        let forward = cam_tr.forward();
+        planes.push((forward, -forward.dot(&cam_tr.position())));
+
+        physics.bodies_inside_planes(&planes)
+    }
+
+    // Makes the entity the only selected one.
+    pub fn select_only(world: &mut World, e: Entity, rr: &Renderer, assets: &mut Assets) {
+        for other in selected(world) {
+            if other != e {
+                deselect(world, other, assets);
+            }
+        }
+        select(world, e, rr, assets);
+    }
+
+    // Along with its body and material, deselected first.
+    pub fn despawn(world: &mut World, physics: &mut Physics, assets: &mut Assets, e: Entity) {
+        deselect(world, e, assets);
+        if let Ok(material) = world.get::<&Material>(e) {
+            assets.remove_material(material.0);
+        }
+        if let Ok(body) = world.get::<&RigidBody>(e) {
+            physics.remove_body(body.handle());
+        }
+        world.despawn(e).unwrap();
+    }
+
+    fn clear(world: &mut World, assets: &mut Assets) {
+        for e in selected(world) {
+            deselect(world, e, assets);
+        }
+    }
+
+    fn delete(world: &mut World, physics: &mut Physics, assets: &mut Assets) {
+        for e in selected(world) {
+            Self::despawn(world, physics, assets, e);
+        }
+    }
+
+    // Copies are placed side by side with the originals along the X axis and become the selection.
+    fn duplicate(world: &mut World, physics: &mut Physics, rr: &Renderer, assets: &mut Assets) {
+        let entities = selected(world);
+        let Some((min, max)) = entities
+            .iter()
+            .map(|&e| match world.get::<&RigidBody>(e) {
+                Ok(body) => physics.body_aabb(body.handle()),
+                // Meshes are about -1..1 in size
+                Err(_) => {
+                    let tr = world.get::<&Transform>(e).unwrap();
+                    (tr.position() - tr.scale(), tr.position() + tr.scale())
+                }
+            })
+            .reduce(|(min1, max1), (min2, max2)| (min1.inf(&min2), max1.sup(&max2)))
+        else {
+            return;
+        };
+        let offset = Vec3::x() * (max.x - min.x + Self::DUPLICATE_GAP);
+
+        for e in entities {
+            let (tr, order, tags, body, mesh, material, camera_texture) = {
+                let mut q = world
+                    .query_one::<(
+                        &Transform,
+                        Option<&RenderOrder>,
+                        Option<&RenderTags>,
+                        Option<&RigidBody>,
+                        Option<&Mesh>,
+                        Option<&Material>,
+                        Option<&CameraTexture>,
+                    )>(e)
+                    .unwrap();
+                let (tr, order, tags, body, mesh, material, camera_texture) = q.get().unwrap();
+                let mut tr = *tr;
+                tr.set_position(tr.position() + offset);
+                (
+                    tr,
+                    order.map(|o| RenderOrder(o.0)),
+                    tags.map(|t| RenderTags(t.0)),
+                    body.map(|body| RigidBody {
+                        handle: physics.duplicate_body(body.handle(), offset),
+                        movable: body.movable,
+                    }),
+                    mesh.map(|m| Mesh(m.0)),
+                    // Left out if it can't be duplicated
+                    material.and_then(|m| assets.material(m.0).duplicate(rr)),
+                    camera_texture.map(|c| CameraTexture(c.0)),
+                )
+            };
+            let copy = world.spawn((tr,));
+            if let Some(order) = order {
+                world.insert_one(copy, order).unwrap();
+            }
+            if let Some(tags) = tags {
+                world.insert_one(copy, tags).unwrap();
+            }
+            if let Some(body) = body {
+                world.insert_one(copy, body).unwrap();
+            }
+            if let Some(mesh) = mesh {
+                world.insert_one(copy, mesh).unwrap();
+            }
+            if let Some(material) = material {
+                world
+                    .insert_one(copy, Material(assets.add_material(material)))
+                    .unwrap();
+                // Re-bound along with the original when the camera target is re-created
+                if let Some(camera_texture) = camera_texture {
+                    world.insert_one(copy, camera_texture).unwrap();
+                }
+            }
+            // A grabbed original is kinematic, the copy shouldn't be
+            if world.get::<&Grab>(e).is_ok()
+                && let Ok(body) = world.get::<&RigidBody>(copy)
+            {
+                body.set_kinematic(physics, false);
+            }
+
+            deselect(world, e, assets);
+            select(world, copy, rr, assets);
+        }
+    }
+
+    // Frozen if any selected body is movable, otherwise all become movable.
+    fn toggle_freeze(world: &mut World, physics: &mut Physics) {
+        let movable = !world
+            .query_mut::<(&RigidBody, &Selected)>()
+            .into_iter()
+            .any(|(_, (body, _))| body.movable);
+        for (_, (body, _, grab)) in world.query_mut::<(&mut RigidBody, &Selected, Option<&Grab>)>()
+        {
+            // Held ones stay kinematic until released
+            body.set_movable(physics, movable, grab.is_none());
+        }
+    }
+}
+
+impl Drag {
+    fn is_rect(&self) -> bool {
+        let size = (self.end - self.start).abs();
+        size.x >= Selection::DRAG_THRESHOLD && size.y >= Selection::DRAG_THRESHOLD
+    }
+}
+
+fn selected(world: &World) -> Vec<Entity> {
+    world.query::<&Selected>().iter().map(|(e, _)| e).collect()
+}
+
+fn select(world: &mut World, e: Entity, rr: &Renderer, assets: &mut Assets) {
+    if world.get::<&Selected>(e).is_ok() {
+        return;
+    }
+    let material = materials::Material::color(rr, assets, Selection::HIGHLIGHT_COLOR, true);
+    let highlight = assets.add_material(material);
+    world.insert_one(e, Selected { highlight }).unwrap();
+}
+
+fn deselect(world: &mut World, e: Entity, assets: &mut Assets) {
+    if let Ok(selected) = world.remove_one::<Selected>(e) {
+        assets.remove_material(selected.highlight);
+    }
+}
@@ -51,6 +51,15 @@ impl Transform {
         self.pos
     }
 
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn rotation(&self) -> UnitQuat {
+        // Stored inverted, as in the view matrix
+        self.rot.inverse()
+    }
+
     pub fn look_at(&mut self, target: Vec3) {
         self.rot = UnitQuat::look_at_rh(&(target - self.pos), &Vec3::y_axis());
         self.rebuild_matrix();
@@ -1,15 +1,25 @@
 use crate::math::Vec3;
 use crate::render::PositionUvNormalVertex;
-use crate::render::{RenderPipelineParams, Renderer};
+use crate::render::{RenderPipelineParams, Renderer, StencilMode};
 
-use super::super::components::{Camera, Transform};
-use super::uniforms::{Vec3Uniform, WorldViewProjUniform};
+use super::super::components::{Camera, Fog, Transform};
+use super::uniforms::{FogUniform, Vec3Uniform, WorldViewProjUniform};
 
+#[derive(Clone)]
 pub struct ColorMaterial {
     pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    wireframe: bool,
+    color: Vec3,
+    matrices_uniform_bind_group_layout: wgpu::BindGroupLayout,
     matrices_uniform_buf: wgpu::Buffer,
     matrices_uniform_bind_group: wgpu::BindGroup,
+    color_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    color_uniform_buf: wgpu::Buffer,
     color_uniform_bind_group: wgpu::BindGroup,
+    fog_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    fog_uniform_buf: wgpu::Buffer,
+    fog_uniform_bind_group: wgpu::BindGroup,
 }
 
 impl ColorMaterial {
@@ -17,33 +27,88 @@ impl ColorMaterial {
         let (matrices_uniform_bind_group_layout, matrices_uniform_bind_group, matrices_uniform_buf) =
             rr.new_uniform_bind_group(bytemuck::cast_slice(&[WorldViewProjUniform::default()]));
 
-        let (color_uniform_bind_group_layout, color_uniform_bind_group, ..) =
+        let (color_uniform_bind_group_layout, color_uniform_bind_group, color_uniform_buf) =
             rr.new_uniform_bind_group(bytemuck::cast_slice(&[Vec3Uniform::new(color)]));
 
-        let pipeline = rr.new_render_pipeline(RenderPipelineParams {
-            shader_module: shader,
-            depth_write: true,
-            depth_enabled: true,
+        let (fog_uniform_bind_group_layout, fog_uniform_bind_group, fog_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[FogUniform::default()]));
+
+        let pipeline = new_pipeline(
+            rr,
+            shader,
             wireframe,
-            bind_group_layouts: &[
-                &matrices_uniform_bind_group_layout,
-                &color_uniform_bind_group_layout,
-            ],
-            // TODO Leaner vertex format. Can't use it currently because this material
-            // is used for file-loaded meshes where we currently only support a single vertex format.
-            vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
-        });
+            &matrices_uniform_bind_group_layout,
+            &color_uniform_bind_group_layout,
+            &fog_uniform_bind_group_layout,
+        );
 
         Self {
             pipeline,
+            shader: shader.clone(),
+            wireframe,
+            color,
+            matrices_uniform_bind_group_layout,
             matrices_uniform_buf,
             matrices_uniform_bind_group,
+            color_uniform_bind_group_layout,
+            color_uniform_buf,
             color_uniform_bind_group,
+            fog_uniform_bind_group_layout,
+            fog_uniform_buf,
+            fog_uniform_bind_group,
         }
     }
 }
 
 impl ColorMaterial {
+    // Shares everything but the per-object uniforms.
+    pub fn duplicate(&self, rr: &Renderer) -> Self {
+        let (matrices_uniform_bind_group, matrices_uniform_buf) = rr
+            .new_uniform_bind_group_with_layout(
+                &self.matrices_uniform_bind_group_layout,
+                bytemuck::cast_slice(&[WorldViewProjUniform::default()]),
+            );
+        let (color_uniform_bind_group, color_uniform_buf) = rr.new_uniform_bind_group_with_layout(
+            &self.color_uniform_bind_group_layout,
+            bytemuck::cast_slice(&[Vec3Uniform::new(self.color)]),
+        );
+        let (fog_uniform_bind_group, fog_uniform_buf) = rr.new_uniform_bind_group_with_layout(
+            &self.fog_uniform_bind_group_layout,
+            bytemuck::cast_slice(&[FogUniform::default()]),
+        );
+        Self {
+            matrices_uniform_buf,
+            matrices_uniform_bind_group,
+            color_uniform_buf,
+            color_uniform_bind_group,
+            fog_uniform_buf,
+            fog_uniform_bind_group,
+            ..self.clone()
+        }
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    pub fn set_wireframe(&mut self, rr: &Renderer, wireframe: bool) {
+        self.wireframe = wireframe;
+        self.rebuild_pipeline(rr);
+    }
+
+    pub fn color(&self) -> Vec3 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, rr: &Renderer, color: Vec3) {
+        self.color = color;
+        rr.queue().write_buffer(
+            &self.color_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[Vec3Uniform::new(color)]),
+        );
+    }
+
     pub fn set_wvp(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
         rr.queue().write_buffer(
             &self.matrices_uniform_buf,
@@ -56,9 +121,57 @@ impl ColorMaterial {
         );
     }
 
+    pub fn set_fog(&self, rr: &Renderer, fog: &Fog, cam_tr: &Transform) {
+        rr.queue().write_buffer(
+            &self.fog_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[FogUniform::new(fog, cam_tr.position())]),
+        );
+    }
+
+    // Re-creates the pipeline to match the renderer's current sample count.
+    pub fn rebuild_pipeline(&mut self, rr: &Renderer) {
+        self.pipeline = new_pipeline(
+            rr,
+            &self.shader,
+            self.wireframe,
+            &self.matrices_uniform_bind_group_layout,
+            &self.color_uniform_bind_group_layout,
+            &self.fog_uniform_bind_group_layout,
+        );
+    }
+
     pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         encoder.set_pipeline(&self.pipeline);
         encoder.set_bind_group(0, &self.matrices_uniform_bind_group, &[]);
         encoder.set_bind_group(1, &self.color_uniform_bind_group, &[]);
+        encoder.set_bind_group(2, &self.fog_uniform_bind_group, &[]);
     }
 }
+
+fn new_pipeline(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    wireframe: bool,
+    matrices_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    color_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    fog_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    rr.new_render_pipeline(RenderPipelineParams {
+        shader_module: shader,
+        depth_write: true,
+        depth_enabled: true,
+        wireframe,
+        stencil: StencilMode::Off,
+        color_format: rr.hdr_texture_format(),
+        sample_count: rr.sample_count(),
+        bind_group_layouts: &[
+            matrices_uniform_bind_group_layout,
+            color_uniform_bind_group_layout,
+            fog_uniform_bind_group_layout,
+        ],
+        // TODO Leaner vertex format. Can't use it currently because this material
+        // is used for file-loaded meshes where we currently only support a single vertex format.
+        vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
+    })
+}
@@ -0,0 +1,69 @@
+use crate::render::PositionUvNormalVertex;
+use crate::render::{RenderPipelineParams, Renderer, StencilMode};
+
+use super::super::components::{Camera, Transform};
+use super::uniforms::WorldViewProjUniform;
+
+// Renders an entity ID into an integer target for picking
+pub struct IdMaterial {
+    pipeline: wgpu::RenderPipeline,
+    matrices_uniform_buf: wgpu::Buffer,
+    matrices_uniform_bind_group: wgpu::BindGroup,
+    id_uniform_bind_group: wgpu::BindGroup,
+}
+
+impl IdMaterial {
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+
+    // `wireframe` must match the object's own material so that the same mesh can be drawn.
+    pub fn new(rr: &Renderer, shader: &wgpu::ShaderModule, id: u64, wireframe: bool) -> Self {
+        let (matrices_uniform_bind_group_layout, matrices_uniform_bind_group, matrices_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[WorldViewProjUniform::default()]));
+
+        let (id_uniform_bind_group_layout, id_uniform_bind_group, _) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[id as u32, (id >> 32) as u32]));
+
+        // The ID target isn't multisampled, so no rebuilding on sample count changes
+        let pipeline = rr.new_render_pipeline(RenderPipelineParams {
+            shader_module: shader,
+            depth_write: true,
+            depth_enabled: true,
+            wireframe,
+            stencil: StencilMode::Off,
+            color_format: Self::TEXTURE_FORMAT,
+            sample_count: 1,
+            bind_group_layouts: &[
+                &matrices_uniform_bind_group_layout,
+                &id_uniform_bind_group_layout,
+            ],
+            vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
+        });
+
+        Self {
+            pipeline,
+            matrices_uniform_buf,
+            matrices_uniform_bind_group,
+            id_uniform_bind_group,
+        }
+    }
+}
+
+impl IdMaterial {
+    pub fn set_wvp(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
+        rr.queue().write_buffer(
+            &self.matrices_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[WorldViewProjUniform::new(
+                &tr.matrix(),
+                &cam_tr.view_matrix(),
+                &cam.proj_matrix(),
+            )]),
+        );
+    }
+
+    pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
+        encoder.set_pipeline(&self.pipeline);
+        encoder.set_bind_group(0, &self.matrices_uniform_bind_group, &[]);
+        encoder.set_bind_group(1, &self.id_uniform_bind_group, &[]);
+    }
+}
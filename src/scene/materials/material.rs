@@ -1,48 +1,82 @@
-use super::super::components::{Camera, Transform};
 use super::super::Assets;
+use super::super::assets::TextureHandle;
+use super::super::components::{Camera, Environment, Transform};
 use super::color::ColorMaterial;
-use super::post_process::PostProcessMaterial;
+use super::id::IdMaterial;
+use super::outline::OutlineMaterial;
 use super::skybox::SkyboxMaterial;
 use super::textured::TexturedMaterial;
-use crate::math::Vec3;
+use crate::math::{Vec2, Vec3};
 use crate::render;
-use crate::render::{Renderer, Texture};
+use crate::render::{Renderer, SamplerParams, Texture};
+
+// What can be changed in a material after it's created, e.g. in the editor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MaterialParams {
+    Color { color: Vec3, wireframe: bool },
+    Textured { uv_scale: Vec2 },
+    // Managed by the code that created it, only the name is shown
+    Fixed(&'static str),
+}
 
 // TODO Avoid this crap, use trait objects or smth
 pub enum Material {
     Color(ColorMaterial),
+    Id(IdMaterial),
+    Outline(OutlineMaterial),
     Skybox(SkyboxMaterial),
     Textured(TexturedMaterial),
-    PostProcess(PostProcessMaterial),
 }
 
 impl Material {
-    pub fn textured(rr: &Renderer, assets: &mut Assets, tex_path: &str) -> Self {
+    pub fn textured(
+        rr: &Renderer,
+        assets: &mut Assets,
+        tex_path: &str,
+        sampler: SamplerParams,
+        uv_scale: Vec2,
+    ) -> Self {
         let shader = assets.add_shader_from_file(rr, "textured.wgsl");
-        let tex = assets.add_2d_texture_from_file(rr, tex_path);
+        let tex = assets.add_2d_texture_from_file(rr, tex_path, sampler);
         // TODO We shouldn't call assets again to get the actual objects, they should be returned
         // from the Assets' methods that created them.
         Self::Textured(TexturedMaterial::new(
             rr,
             assets.shader(shader),
             assets.texture(tex),
+            uv_scale,
         ))
     }
 
-    pub fn post_process(rr: &Renderer, assets: &mut Assets, src_texture: &Texture) -> Self {
-        let shader = assets.add_shader_from_file(rr, "post-process.wgsl");
-        // TODO We shouldn't call assets again to get the actual objects, they should be returned
-        // from the Assets' methods that created them.
-        Self::PostProcess(PostProcessMaterial::new(
+    // For textures not owned by the assets, e.g. camera render targets.
+    pub fn textured_with_texture(
+        rr: &Renderer,
+        assets: &mut Assets,
+        texture: &Texture,
+        uv_scale: Vec2,
+    ) -> Self {
+        let shader = assets.add_shader_from_file(rr, "textured.wgsl");
+        Self::Textured(TexturedMaterial::new(
             rr,
             assets.shader(shader),
-            src_texture,
+            texture,
+            uv_scale,
         ))
     }
 
     pub fn skybox(rr: &Renderer, assets: &mut Assets, tex_path: &str) -> Self {
-        let shader = assets.add_shader_from_file(rr, "skybox.wgsl");
         let tex = assets.add_cube_texture_from_file(rr, tex_path);
+        Self::skybox_with_texture(rr, assets, tex)
+    }
+
+    // Face paths are in +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn skybox_from_faces(rr: &Renderer, assets: &mut Assets, face_paths: &[String; 6]) -> Self {
+        let tex = assets.add_cube_texture_from_faces(rr, face_paths);
+        Self::skybox_with_texture(rr, assets, tex)
+    }
+
+    fn skybox_with_texture(rr: &Renderer, assets: &mut Assets, tex: TextureHandle) -> Self {
+        let shader = assets.add_shader_from_file(rr, "skybox.wgsl");
         // TODO We shouldn't call assets again to get the actual objects, they should be returned
         // from the Assets' methods that created them.
         Self::Skybox(SkyboxMaterial::new(
@@ -64,12 +98,131 @@ impl Material {
         ))
     }
 
-    pub fn update(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
+    // See `OutlineMaterial`, `mark` is for the first of the two draws.
+    pub fn outline(rr: &Renderer, assets: &mut Assets, mark: bool) -> Self {
+        let shader = assets.add_shader_from_file(rr, "outline.wgsl");
+        Self::Outline(OutlineMaterial::new(rr, assets.shader(shader), mark))
+    }
+
+    // For picking `entity_bits` from the ID target, see `IdMaterial`.
+    pub fn id(rr: &Renderer, assets: &mut Assets, entity_bits: u64, wireframe: bool) -> Self {
+        let shader = assets.add_shader_from_file(rr, "id.wgsl");
+        Self::Id(IdMaterial::new(
+            rr,
+            assets.shader(shader),
+            entity_bits,
+            wireframe,
+        ))
+    }
+
+    pub fn update(
+        &self,
+        rr: &Renderer,
+        cam: &Camera,
+        cam_tr: &Transform,
+        tr: &Transform,
+        env: &Environment,
+    ) {
+        match self {
+            Material::Color(m) => {
+                m.set_wvp(rr, cam, cam_tr, tr);
+                m.set_fog(rr, &env.fog, cam_tr);
+            }
+            Material::Textured(m) => {
+                m.set_wvp(rr, cam, cam_tr, tr);
+                m.set_fog(rr, &env.fog, cam_tr);
+            }
+            Material::Id(m) => m.set_wvp(rr, cam, cam_tr, tr),
+            Material::Outline(m) => m.set_wvp(rr, cam, cam_tr, tr),
+            Material::Skybox(m) => {
+                m.set_wvp(rr, cam, cam_tr);
+                m.set_env(rr, env);
+                m.set_fog(rr, &env.fog, cam_tr);
+            }
+        }
+    }
+}
+
+impl Material {
+    // Only textured materials have a texture to replace, ignored for others.
+    pub fn set_texture(&mut self, rr: &Renderer, texture: &Texture) {
+        if let Material::Textured(m) = self {
+            m.set_texture(rr, texture);
+        }
+    }
+
+    // `width` is in pixels of the viewport being rendered into. Ignored if not an outline.
+    pub fn set_outline(&self, rr: &Renderer, color: Vec3, width: f32, viewport_size: Vec2) {
+        if let Material::Outline(m) = self {
+            m.set_outline(rr, color, width, viewport_size);
+        }
+    }
+
+    pub fn params(&self) -> MaterialParams {
+        match self {
+            Material::Color(m) => MaterialParams::Color {
+                color: m.color(),
+                wireframe: m.wireframe(),
+            },
+            Material::Textured(m) => MaterialParams::Textured {
+                uv_scale: m.uv_scale(),
+            },
+            Material::Id(_) => MaterialParams::Fixed("Id"),
+            Material::Outline(_) => MaterialParams::Fixed("Outline"),
+            Material::Skybox(_) => MaterialParams::Fixed("Skybox"),
+        }
+    }
+
+    // Params of another kind of material are ignored.
+    pub fn set_params(&mut self, rr: &Renderer, params: MaterialParams) {
+        match (self, params) {
+            (Material::Color(m), MaterialParams::Color { color, wireframe }) => {
+                m.set_color(rr, color);
+                if wireframe != m.wireframe() {
+                    m.set_wireframe(rr, wireframe);
+                }
+            }
+            (Material::Textured(m), MaterialParams::Textured { uv_scale }) => {
+                m.set_uv_scale(rr, uv_scale)
+            }
+            _ => {}
+        }
+    }
+
+    // Only color materials have a plain color, ignored for others.
+    pub fn set_color(&mut self, rr: &Renderer, color: Vec3) {
+        if let Material::Color(m) = self {
+            m.set_color(rr, color);
+        }
+    }
+
+    // For another object looking the same, since materials hold per-object uniforms. None for
+    // the ones managed by the code that created them.
+    pub fn duplicate(&self, rr: &Renderer) -> Option<Self> {
+        match self {
+            Material::Color(m) => Some(Material::Color(m.duplicate(rr))),
+            Material::Textured(m) => Some(Material::Textured(m.duplicate(rr))),
+            Material::Id(_) | Material::Outline(_) | Material::Skybox(_) => None,
+        }
+    }
+
+    // Objects with these materials can be picked, with the line topology if it's a wireframe.
+    pub fn pickable_wireframe(&self) -> Option<bool> {
+        match self {
+            Material::Color(m) => Some(m.wireframe()),
+            Material::Textured(_) => Some(false),
+            Material::Id(_) | Material::Outline(_) | Material::Skybox(_) => None,
+        }
+    }
+
+    // Needed when the renderer's sample count changes.
+    pub fn rebuild_pipeline(&mut self, rr: &Renderer) {
         match self {
-            Material::Color(m) => m.set_wvp(rr, cam, cam_tr, tr),
-            Material::Textured(m) => m.set_wvp(rr, cam, cam_tr, tr),
-            Material::Skybox(m) => m.set_wvp(rr, cam, cam_tr),
-            Material::PostProcess(_) => (),
+            Material::Color(m) => m.rebuild_pipeline(rr),
+            Material::Id(_) => {}
+            Material::Outline(m) => m.rebuild_pipeline(rr),
+            Material::Textured(m) => m.rebuild_pipeline(rr),
+            Material::Skybox(m) => m.rebuild_pipeline(rr),
         }
     }
 }
@@ -78,9 +231,10 @@ impl render::ApplyMaterial for Material {
     fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         match self {
             Material::Color(m) => m.apply(encoder),
+            Material::Id(m) => m.apply(encoder),
+            Material::Outline(m) => m.apply(encoder),
             Material::Skybox(m) => m.apply(encoder),
             Material::Textured(m) => m.apply(encoder),
-            Material::PostProcess(m) => m.apply(encoder),
         };
     }
 }
@@ -1,8 +1,12 @@
 mod color;
+mod id;
 mod material;
+mod outline;
 mod post_process;
 mod skybox;
 mod textured;
 mod uniforms;
 
-pub use material::Material;
+pub use id::IdMaterial;
+pub use material::{Material, MaterialParams};
+pub use post_process::PostProcessMaterial;
@@ -0,0 +1,120 @@
+use crate::math::{Vec2, Vec3};
+use crate::render::PositionUvNormalVertex;
+use crate::render::{RenderPipelineParams, Renderer, StencilMode};
+
+use super::super::components::{Camera, Transform};
+use super::uniforms::{OutlineUniform, WorldViewProjUniform};
+
+// Outlines take two draws of the object: the first one marks it in the stencil, the second one
+// is pushed outwards and drawn only outside the marked pixels. Both ignore depth so that outlines
+// show through other objects.
+pub struct OutlineMaterial {
+    pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    stencil: StencilMode,
+    matrices_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    matrices_uniform_buf: wgpu::Buffer,
+    matrices_uniform_bind_group: wgpu::BindGroup,
+    outline_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    outline_uniform_buf: wgpu::Buffer,
+    outline_uniform_bind_group: wgpu::BindGroup,
+}
+
+impl OutlineMaterial {
+    // `mark` makes the first draw, otherwise the outline itself.
+    pub fn new(rr: &Renderer, shader: &wgpu::ShaderModule, mark: bool) -> Self {
+        let (matrices_uniform_bind_group_layout, matrices_uniform_bind_group, matrices_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[WorldViewProjUniform::default()]));
+
+        let (outline_uniform_bind_group_layout, outline_uniform_bind_group, outline_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[OutlineUniform::default()]));
+
+        let stencil = if mark {
+            StencilMode::Mark
+        } else {
+            StencilMode::Unmarked
+        };
+        let pipeline = new_pipeline(
+            rr,
+            shader,
+            stencil,
+            &matrices_uniform_bind_group_layout,
+            &outline_uniform_bind_group_layout,
+        );
+
+        Self {
+            pipeline,
+            shader: shader.clone(),
+            stencil,
+            matrices_uniform_bind_group_layout,
+            matrices_uniform_buf,
+            matrices_uniform_bind_group,
+            outline_uniform_bind_group_layout,
+            outline_uniform_buf,
+            outline_uniform_bind_group,
+        }
+    }
+}
+
+impl OutlineMaterial {
+    pub fn set_wvp(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
+        rr.queue().write_buffer(
+            &self.matrices_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[WorldViewProjUniform::new(
+                &tr.matrix(),
+                &cam_tr.view_matrix(),
+                &cam.proj_matrix(),
+            )]),
+        );
+    }
+
+    // `width` is in pixels of the viewport being rendered into.
+    pub fn set_outline(&self, rr: &Renderer, color: Vec3, width: f32, viewport_size: Vec2) {
+        rr.queue().write_buffer(
+            &self.outline_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[OutlineUniform::new(color, width, viewport_size)]),
+        );
+    }
+
+    // Re-creates the pipeline to match the renderer's current sample count.
+    pub fn rebuild_pipeline(&mut self, rr: &Renderer) {
+        self.pipeline = new_pipeline(
+            rr,
+            &self.shader,
+            self.stencil,
+            &self.matrices_uniform_bind_group_layout,
+            &self.outline_uniform_bind_group_layout,
+        );
+    }
+
+    pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
+        encoder.set_pipeline(&self.pipeline);
+        encoder.set_bind_group(0, &self.matrices_uniform_bind_group, &[]);
+        encoder.set_bind_group(1, &self.outline_uniform_bind_group, &[]);
+    }
+}
+
+fn new_pipeline(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    stencil: StencilMode,
+    matrices_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    outline_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    rr.new_render_pipeline(RenderPipelineParams {
+        shader_module: shader,
+        depth_write: false,
+        depth_enabled: false,
+        wireframe: false,
+        stencil,
+        color_format: rr.hdr_texture_format(),
+        sample_count: rr.sample_count(),
+        bind_group_layouts: &[
+            matrices_uniform_bind_group_layout,
+            outline_uniform_bind_group_layout,
+        ],
+        vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
+    })
+}
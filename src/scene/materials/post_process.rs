@@ -1,37 +1,85 @@
 use crate::render::PositionUvVertex;
 use crate::render::Texture;
-use crate::render::{RenderPipelineParams, Renderer};
+use crate::render::{ApplyMaterial, RenderPipelineParams, Renderer, StencilMode};
+
+use super::uniforms::PostProcessUniform;
 
 pub struct PostProcessMaterial {
     pipeline: wgpu::RenderPipeline,
     texture_bind_group: wgpu::BindGroup,
+    params_uniform_buf: wgpu::Buffer,
+    params_uniform_bind_group: wgpu::BindGroup,
+    extra_texture_bind_group: Option<wgpu::BindGroup>,
 }
 
 impl PostProcessMaterial {
     // TODO Passing shader here is weird because the material should dictate which shader to use.
     // Either avoid passing it or make the material generic and accept *any* shader.
     // Same for other materials.
-    pub fn new(rr: &Renderer, shader: &wgpu::ShaderModule, texture: &Texture) -> Self {
+    // `extra_texture` is an optional second input, e.g. a LUT or a blurred image to combine with.
+    pub fn new(
+        rr: &Renderer,
+        shader: &wgpu::ShaderModule,
+        texture: &Texture,
+        extra_texture: Option<&Texture>,
+        target_format: wgpu::TextureFormat,
+        target_sample_count: u32,
+    ) -> Self {
         let (texture_bind_group_layout, texture_bind_group) =
             rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::D2);
 
+        let (params_uniform_bind_group_layout, params_uniform_bind_group, params_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[PostProcessUniform::default()]));
+
+        let (extra_texture_bind_group_layout, extra_texture_bind_group) = extra_texture
+            .map(|tex| rr.new_texture_bind_group(tex, wgpu::TextureViewDimension::D2))
+            .unzip();
+
+        let mut bind_group_layouts = vec![
+            &texture_bind_group_layout,
+            &params_uniform_bind_group_layout,
+        ];
+        if let Some(layout) = &extra_texture_bind_group_layout {
+            bind_group_layouts.push(layout);
+        }
+
         let pipeline = rr.new_render_pipeline(RenderPipelineParams {
             shader_module: shader,
             depth_write: true,
             depth_enabled: true,
             wireframe: false,
-            bind_group_layouts: &[&texture_bind_group_layout],
+            stencil: StencilMode::Off,
+            color_format: target_format,
+            sample_count: target_sample_count,
+            bind_group_layouts: &bind_group_layouts,
             vertex_buffer_layouts: &[PositionUvVertex::buffer_layout()],
         });
 
         Self {
             pipeline,
             texture_bind_group,
+            params_uniform_buf,
+            params_uniform_bind_group,
+            extra_texture_bind_group,
         }
     }
 
-    pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
+    pub fn set_params(&self, rr: &Renderer, values: [f32; 4], texel_size: [f32; 2]) {
+        rr.queue().write_buffer(
+            &self.params_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[PostProcessUniform::new(values, texel_size)]),
+        );
+    }
+}
+
+impl ApplyMaterial for PostProcessMaterial {
+    fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         encoder.set_pipeline(&self.pipeline);
         encoder.set_bind_group(0, &self.texture_bind_group, &[]);
+        encoder.set_bind_group(1, &self.params_uniform_bind_group, &[]);
+        if let Some(bind_group) = &self.extra_texture_bind_group {
+            encoder.set_bind_group(2, bind_group, &[]);
+        }
     }
 }
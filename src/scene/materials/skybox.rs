@@ -1,15 +1,24 @@
 use crate::render::PositionUvVertex;
 use crate::render::Texture;
-use crate::render::{RenderPipelineParams, Renderer};
+use crate::render::{RenderPipelineParams, Renderer, StencilMode};
 
-use super::super::components::{Camera, Transform};
-use super::uniforms::ViewInvProjUniform;
+use super::super::components::{Camera, Environment, Fog, Transform};
+use super::uniforms::{FogUniform, SkyUniform, ViewInvProjUniform};
 
 pub struct SkyboxMaterial {
     pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    tex_bind_group_layout: wgpu::BindGroupLayout,
     tex_bind_group: wgpu::BindGroup,
     uniform_buf: wgpu::Buffer,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
     uniform_bind_group: wgpu::BindGroup,
+    sky_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    sky_uniform_buf: wgpu::Buffer,
+    sky_uniform_bind_group: wgpu::BindGroup,
+    fog_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    fog_uniform_buf: wgpu::Buffer,
+    fog_uniform_bind_group: wgpu::BindGroup,
 }
 
 impl SkyboxMaterial {
@@ -23,20 +32,35 @@ impl SkyboxMaterial {
         let (tex_bind_group_layout, tex_bind_group) =
             rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::Cube);
 
-        let pipeline = rr.new_render_pipeline(RenderPipelineParams {
-            shader_module: shader,
-            depth_write: false,
-            depth_enabled: true,
-            wireframe: false,
-            bind_group_layouts: &[&uniform_bind_group_layout, &tex_bind_group_layout],
-            vertex_buffer_layouts: &[PositionUvVertex::buffer_layout()],
-        });
+        let (sky_uniform_bind_group_layout, sky_uniform_bind_group, sky_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[SkyUniform::default()]));
+
+        let (fog_uniform_bind_group_layout, fog_uniform_bind_group, fog_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[FogUniform::default()]));
+
+        let pipeline = new_pipeline(
+            rr,
+            shader,
+            &uniform_bind_group_layout,
+            &tex_bind_group_layout,
+            &sky_uniform_bind_group_layout,
+            &fog_uniform_bind_group_layout,
+        );
 
         Self {
             pipeline,
+            shader: shader.clone(),
+            tex_bind_group_layout,
             tex_bind_group,
             uniform_buf,
+            uniform_bind_group_layout,
             uniform_bind_group,
+            sky_uniform_bind_group_layout,
+            sky_uniform_buf,
+            sky_uniform_bind_group,
+            fog_uniform_bind_group_layout,
+            fog_uniform_buf,
+            fog_uniform_bind_group,
         }
     }
 }
@@ -53,9 +77,65 @@ impl SkyboxMaterial {
         );
     }
 
+    pub fn set_env(&self, rr: &Renderer, env: &Environment) {
+        rr.queue().write_buffer(
+            &self.sky_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[SkyUniform::new(env)]),
+        );
+    }
+
+    pub fn set_fog(&self, rr: &Renderer, fog: &Fog, cam_tr: &Transform) {
+        rr.queue().write_buffer(
+            &self.fog_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[FogUniform::new(fog, cam_tr.position())]),
+        );
+    }
+
+    // Re-creates the pipeline to match the renderer's current sample count.
+    pub fn rebuild_pipeline(&mut self, rr: &Renderer) {
+        self.pipeline = new_pipeline(
+            rr,
+            &self.shader,
+            &self.uniform_bind_group_layout,
+            &self.tex_bind_group_layout,
+            &self.sky_uniform_bind_group_layout,
+            &self.fog_uniform_bind_group_layout,
+        );
+    }
+
     pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         encoder.set_pipeline(&self.pipeline);
         encoder.set_bind_group(0, &self.uniform_bind_group, &[]);
         encoder.set_bind_group(1, &self.tex_bind_group, &[]);
+        encoder.set_bind_group(2, &self.sky_uniform_bind_group, &[]);
+        encoder.set_bind_group(3, &self.fog_uniform_bind_group, &[]);
     }
 }
+
+fn new_pipeline(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    tex_bind_group_layout: &wgpu::BindGroupLayout,
+    sky_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    fog_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    rr.new_render_pipeline(RenderPipelineParams {
+        shader_module: shader,
+        depth_write: false,
+        depth_enabled: true,
+        wireframe: false,
+        stencil: StencilMode::Off,
+        color_format: rr.hdr_texture_format(),
+        sample_count: rr.sample_count(),
+        bind_group_layouts: &[
+            uniform_bind_group_layout,
+            tex_bind_group_layout,
+            sky_uniform_bind_group_layout,
+            fog_uniform_bind_group_layout,
+        ],
+        vertex_buffer_layouts: &[PositionUvVertex::buffer_layout()],
+    })
+}
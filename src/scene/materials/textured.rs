@@ -1,47 +1,119 @@
+use crate::math::Vec2;
 use crate::render::PositionUvNormalVertex;
 use crate::render::Texture;
-use crate::render::{RenderPipelineParams, Renderer};
+use crate::render::{RenderPipelineParams, Renderer, StencilMode};
 
-use super::super::components::{Camera, Transform};
-use super::uniforms::WorldViewProjUniform;
+use super::super::components::{Camera, Fog, Transform};
+use super::uniforms::{FogUniform, Vec2Uniform, WorldViewProjUniform};
 
+#[derive(Clone)]
 pub struct TexturedMaterial {
     pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group: wgpu::BindGroup,
     uniform_buf: wgpu::Buffer,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
     uniform_bind_group: wgpu::BindGroup,
+    uv_scale: Vec2,
+    uv_scale_bind_group_layout: wgpu::BindGroupLayout,
+    uv_scale_buf: wgpu::Buffer,
+    uv_scale_bind_group: wgpu::BindGroup,
+    fog_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    fog_uniform_buf: wgpu::Buffer,
+    fog_uniform_bind_group: wgpu::BindGroup,
 }
 
 impl TexturedMaterial {
     // TODO Passing shader here is weird because the material should dictate which shader to use.
     // Either avoid passing it or make the material generic and accept *any* shader.
     // Same for other materials.
-    pub fn new(rr: &Renderer, shader: &wgpu::ShaderModule, texture: &Texture) -> Self {
+    // `uv_scale` tiles the texture, requires a repeating sampler to look right.
+    pub fn new(
+        rr: &Renderer,
+        shader: &wgpu::ShaderModule,
+        texture: &Texture,
+        uv_scale: Vec2,
+    ) -> Self {
         let (uniform_bind_group_layout, uniform_bind_group, uniform_buf) =
             rr.new_uniform_bind_group(bytemuck::cast_slice(&[WorldViewProjUniform::default()]));
 
         let (texture_bind_group_layout, texture_bind_group) =
             rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::D2);
 
-        let pipeline = rr.new_render_pipeline(RenderPipelineParams {
-            shader_module: shader,
-            depth_write: true,
-            depth_enabled: true,
-            wireframe: false,
-            bind_group_layouts: &[&texture_bind_group_layout, &uniform_bind_group_layout],
-            vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
-        });
+        let (uv_scale_bind_group_layout, uv_scale_bind_group, uv_scale_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[Vec2Uniform::new(uv_scale)]));
+
+        let (fog_uniform_bind_group_layout, fog_uniform_bind_group, fog_uniform_buf) =
+            rr.new_uniform_bind_group(bytemuck::cast_slice(&[FogUniform::default()]));
+
+        let pipeline = new_pipeline(
+            rr,
+            shader,
+            &texture_bind_group_layout,
+            &uniform_bind_group_layout,
+            &uv_scale_bind_group_layout,
+            &fog_uniform_bind_group_layout,
+        );
 
         Self {
+            texture_bind_group_layout,
             texture_bind_group,
             uniform_buf,
+            uniform_bind_group_layout,
             uniform_bind_group,
+            uv_scale,
+            uv_scale_bind_group_layout,
+            uv_scale_buf,
+            uv_scale_bind_group,
+            fog_uniform_bind_group_layout,
+            fog_uniform_buf,
+            fog_uniform_bind_group,
+            shader: shader.clone(),
             pipeline,
         }
     }
 }
 
 impl TexturedMaterial {
+    // Shares everything but the per-object uniforms.
+    pub fn duplicate(&self, rr: &Renderer) -> Self {
+        let (uniform_bind_group, uniform_buf) = rr.new_uniform_bind_group_with_layout(
+            &self.uniform_bind_group_layout,
+            bytemuck::cast_slice(&[WorldViewProjUniform::default()]),
+        );
+        let (uv_scale_bind_group, uv_scale_buf) = rr.new_uniform_bind_group_with_layout(
+            &self.uv_scale_bind_group_layout,
+            bytemuck::cast_slice(&[Vec2Uniform::new(self.uv_scale)]),
+        );
+        let (fog_uniform_bind_group, fog_uniform_buf) = rr.new_uniform_bind_group_with_layout(
+            &self.fog_uniform_bind_group_layout,
+            bytemuck::cast_slice(&[FogUniform::default()]),
+        );
+        Self {
+            uniform_buf,
+            uniform_bind_group,
+            uv_scale_buf,
+            uv_scale_bind_group,
+            fog_uniform_buf,
+            fog_uniform_bind_group,
+            ..self.clone()
+        }
+    }
+
+    pub fn uv_scale(&self) -> Vec2 {
+        self.uv_scale
+    }
+
+    pub fn set_uv_scale(&mut self, rr: &Renderer, uv_scale: Vec2) {
+        self.uv_scale = uv_scale;
+        rr.queue().write_buffer(
+            &self.uv_scale_buf,
+            0,
+            bytemuck::cast_slice(&[Vec2Uniform::new(uv_scale)]),
+        );
+    }
+
     pub fn set_wvp(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
         rr.queue().write_buffer(
             &self.uniform_buf,
@@ -54,9 +126,64 @@ impl TexturedMaterial {
         );
     }
 
+    pub fn set_fog(&self, rr: &Renderer, fog: &Fog, cam_tr: &Transform) {
+        rr.queue().write_buffer(
+            &self.fog_uniform_buf,
+            0,
+            bytemuck::cast_slice(&[FogUniform::new(fog, cam_tr.position())]),
+        );
+    }
+
+    // E.g. when a camera's render target shown by the material has been re-created.
+    pub fn set_texture(&mut self, rr: &Renderer, texture: &Texture) {
+        (self.texture_bind_group_layout, self.texture_bind_group) =
+            rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::D2);
+        self.rebuild_pipeline(rr);
+    }
+
+    // Re-creates the pipeline to match the renderer's current sample count.
+    pub fn rebuild_pipeline(&mut self, rr: &Renderer) {
+        self.pipeline = new_pipeline(
+            rr,
+            &self.shader,
+            &self.texture_bind_group_layout,
+            &self.uniform_bind_group_layout,
+            &self.uv_scale_bind_group_layout,
+            &self.fog_uniform_bind_group_layout,
+        );
+    }
+
     pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         encoder.set_pipeline(&self.pipeline);
         encoder.set_bind_group(0, &self.texture_bind_group, &[]);
         encoder.set_bind_group(1, &self.uniform_bind_group, &[]);
+        encoder.set_bind_group(2, &self.uv_scale_bind_group, &[]);
+        encoder.set_bind_group(3, &self.fog_uniform_bind_group, &[]);
     }
 }
+
+fn new_pipeline(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    uv_scale_bind_group_layout: &wgpu::BindGroupLayout,
+    fog_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    rr.new_render_pipeline(RenderPipelineParams {
+        shader_module: shader,
+        depth_write: true,
+        depth_enabled: true,
+        wireframe: false,
+        stencil: StencilMode::Off,
+        color_format: rr.hdr_texture_format(),
+        sample_count: rr.sample_count(),
+        bind_group_layouts: &[
+            texture_bind_group_layout,
+            uniform_bind_group_layout,
+            uv_scale_bind_group_layout,
+            fog_uniform_bind_group_layout,
+        ],
+        vertex_buffer_layouts: &[PositionUvNormalVertex::buffer_layout()],
+    })
+}
@@ -1,4 +1,5 @@
-use crate::math::{Mat4, Vec3, OPENGL_TO_WGPU_MATRIX};
+use super::super::components::{Environment, Fog, FogMode, SkyMode};
+use crate::math::{Mat4, Vec2, Vec3, OPENGL_TO_WGPU_MATRIX};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -16,6 +17,22 @@ impl Default for Vec3Uniform {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vec2Uniform([f32; 2]);
+
+impl Vec2Uniform {
+    pub fn new(v: Vec2) -> Self {
+        Self([v.x, v.y])
+    }
+}
+
+impl Default for Vec2Uniform {
+    fn default() -> Self {
+        Self([1.0, 1.0])
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct WorldViewProjUniform {
@@ -66,3 +83,109 @@ impl Default for ViewInvProjUniform {
         }
     }
 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    color: [f32; 3],
+    // 0 - off, 1 - linear, 2 - exponential
+    mode: u32,
+    camera_pos: [f32; 3],
+    start: f32,
+    end: f32,
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+}
+
+impl FogUniform {
+    pub fn new(fog: &Fog, camera_pos: Vec3) -> Self {
+        Self {
+            color: fog.color.into(),
+            mode: match (fog.enabled, fog.mode) {
+                (false, _) => 0,
+                (true, FogMode::Linear) => 1,
+                (true, FogMode::Exponential) => 2,
+            },
+            camera_pos: camera_pos.into(),
+            start: fog.start,
+            end: fog.end,
+            density: fog.density,
+            height_falloff: fog.height_falloff,
+            base_height: fog.base_height,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    zenith: [f32; 4],
+    horizon: [f32; 4],
+    ground: [f32; 4],
+    // w - intensity
+    sun: [f32; 4],
+    // Rayleigh and Mie multipliers, Mie g
+    scattering: [f32; 4],
+    // 0 - skybox, 1 - gradient, 2 - atmosphere
+    mode: u32,
+    _padding: [u32; 3],
+}
+
+impl SkyUniform {
+    pub fn new(env: &Environment) -> Self {
+        let g = &env.gradient;
+        let a = &env.atmosphere;
+        Self {
+            zenith: g.zenith.push(1.0).into(),
+            horizon: g.horizon.push(1.0).into(),
+            ground: g.ground.push(1.0).into(),
+            sun: a.sun_direction().push(a.sun_intensity).into(),
+            scattering: [a.rayleigh, a.mie, a.mie_g, 0.0],
+            mode: match env.sky_mode {
+                SkyMode::Gradient => 1,
+                SkyMode::Atmosphere => 2,
+                SkyMode::Skybox | SkyMode::None => 0,
+            },
+            _padding: [0; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostProcessUniform {
+    values: [f32; 4],
+    texel_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl PostProcessUniform {
+    pub fn new(values: [f32; 4], texel_size: [f32; 2]) -> Self {
+        Self {
+            values,
+            texel_size,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OutlineUniform {
+    color: [f32; 3],
+    width: f32,
+    viewport_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl OutlineUniform {
+    pub fn new(color: Vec3, width: f32, viewport_size: Vec2) -> Self {
+        Self {
+            color: color.into(),
+            width,
+            viewport_size: viewport_size.into(),
+            _padding: [0.0; 2],
+        }
+    }
+}
@@ -1,9 +1,11 @@
 mod assets;
 mod components;
 pub mod materials;
+#[allow(clippy::module_inception)]
 mod scene;
 mod scene_config;
 
 pub use assets::{Assets, MaterialHandle, MeshHandle};
+pub use components::PlayerSettings;
 pub use scene::Scene;
 pub use scene_config::SceneCfg;
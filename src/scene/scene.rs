@@ -3,32 +3,52 @@ use rapier3d::dynamics::{RigidBodyBuilder, RigidBodyType};
 use rapier3d::prelude::*;
 
 use crate::input::InputAction;
-use crate::math::Vec3;
+use crate::math::{Vec2, Vec3};
 use crate::physics::Physics;
 use crate::render;
-use crate::render::{Renderer, SurfaceSize, Ui};
-use crate::scene::scene_config::{ComponentCfg, MaterialCfg, MeshPrefabCfg, SceneCfg};
+use crate::render::{
+    RenderPassParams, RenderTarget, RenderTargetParams, Renderer, SamplerParams, SurfaceSize,
+    TextureSize, Ui, Viewport,
+};
+use crate::scene::scene_config::{
+    CameraCfg, ColliderShapeCfg, ComponentCfg, EnvironmentCfg, FogModeCfg, MaterialCfg,
+    MeshPrefabCfg, NodeCfg, OutlineCfg, PostProcessEffectCfg, ProjectionCfg, SceneCfg, SkyModeCfg,
+    SkyboxCfg, TextureAddressModeCfg, TextureFilterCfg, TextureSourceCfg, TonemappingOperatorCfg,
+};
 use crate::state::State;
 
 use super::assets::Assets;
 use super::components::{
-    Camera, Grab, Hud, Material, Mesh, Player, PlayerTarget, RenderOrder, RenderTags,
-    Transform, RENDER_TAG_SCENE,
+    Atmosphere, Camera, CameraParams, CameraTexture, Editor, Environment, Fog, FogMode, Gizmo,
+    GizmoHandle, Grab, Hud, HudTargets, Material, Mesh, Name, OutlineSettings, Outliner, Picker,
+    Player, PlayerAvatar, PlayerSettings, PlayerTarget, PostProcessEffect, PostProcessPass,
+    PostProcessor, Prefab, Projection, RENDER_TAG_GIZMO, RENDER_TAG_SCENE, RenderOrder, RenderTags,
+    Selected, Selection, SkyGradient, SkyMode, TonemappingOperator, Transform,
 };
-use super::{components, materials, MeshHandle};
+use super::{MeshHandle, components, materials};
+
+const DEFAULT_SKYBOX: &str = "skybox_bgra.dds";
+const SELECTION_HIGHLIGHT_SCALE: f32 = 1.02;
 
 pub struct Scene {
     world: World,
     physics: Physics,
     postprocessor: Entity,
+    environment: Entity,
     player: Entity,
     hud: Entity,
+    selection: Entity,
+    outliner: Entity,
+    picker: Entity,
+    gizmo: Entity,
+    editor: Entity,
     ui: Ui,
     box_mesh: MeshHandle,
+    basis_mesh: MeshHandle,
 }
 
 impl Scene {
-    pub fn new(state: &State, assets: &mut Assets) -> Self {
+    pub fn new(state: &State, assets: &mut Assets, player_settings: PlayerSettings) -> Self {
         let mut world = World::new();
         let mut physics = Physics::new();
 
@@ -38,40 +58,32 @@ impl Scene {
             &state.renderer,
             &mut physics,
             Vec3::new(7.0, 7.0, 7.0),
+            player_settings,
         );
 
         let quad_mesh = assets.add_mesh(render::Mesh::new_quad(&state.renderer));
 
-        // Skybox
-        let material = materials::Material::skybox(&state.renderer, assets, "skybox_bgra.dds");
-        world.spawn((
+        // Sky, gets its material with the scene config
+        let sky = world.spawn((
             Transform::default(),
             Mesh(quad_mesh),
-            Material(assets.add_material(material)),
             RenderOrder(-100),
             RenderTags(RENDER_TAG_SCENE),
+            Name("sky".to_string()),
         ));
+        let environment = world.spawn((Environment::new(sky),));
 
         // Post-processor
-        let pp_src_tex = world
-            .query_one_mut::<&Camera>(player)
-            .unwrap()
-            .target()
-            .as_ref()
-            .unwrap()
-            .color_tex();
-        let material = materials::Material::post_process(&state.renderer, assets, pp_src_tex);
-        let postprocessor = world.spawn((
-            Transform::default(),
-            Camera::new(1.0, components::RENDER_TAG_POST_PROCESS, None),
-            Mesh(quad_mesh),
-            Material(assets.add_material(material)),
-            RenderOrder(100),
-            RenderTags(components::RENDER_TAG_POST_PROCESS),
-        ));
+        let postprocessor = world.spawn((PostProcessor::new(&state.renderer, quad_mesh),));
 
-        let hud = world.spawn((Hud,));
+        let hud = world.spawn((Hud::default(),));
+        let selection = world.spawn((Selection::default(),));
+        let outliner = world.spawn((Outliner::default(),));
+        let picker = world.spawn((Picker::default(),));
+        let gizmo = Gizmo::spawn(&mut world, &state.renderer, assets);
         let box_mesh = assets.add_mesh_from_file(&state.renderer, "cube.obj");
+        let basis_mesh = assets.add_mesh(render::Mesh::new_basis(&state.renderer));
+        let editor = world.spawn((Editor::new(box_mesh),));
         let ui = Ui::new(&state.window, &state.renderer);
 
         Self {
@@ -79,28 +91,40 @@ impl Scene {
             physics,
             player,
             postprocessor,
+            environment,
             hud,
+            selection,
+            outliner,
+            picker,
+            gizmo,
+            editor,
             box_mesh,
+            basis_mesh,
             ui,
         }
     }
 
-    pub fn update(
-        &mut self,
-        dt: f32,
-        state: &State,
-        assets: &mut Assets,
-        new_canvas_size: &Option<SurfaceSize>,
-    ) {
-        for e in state.input.new_raw_events() {
-            self.ui.handle_event(e, &state.window);
-        }
-
+    // Simulation, can run several times per rendered frame, e.g. when fast-forwarding a playback.
+    pub fn update(&mut self, dt: f32, state: &mut State, assets: &mut Assets) {
         self.physics.update(dt);
 
-        Player::update(dt, &mut self.world, &mut self.physics, state);
-        Grab::update(&mut self.world, &state.input, &mut self.physics);
+        // Looking around turns the held object instead
+        let look_locked = Grab::rotating(&self.world, &state.input);
+        Player::update(dt, &mut self.world, &mut self.physics, state, look_locked);
+        Gizmo::update(
+            &mut self.world,
+            &mut self.physics,
+            &state.input,
+            &state.renderer,
+            assets,
+        );
+        // Clicking a gizmo handle drags it instead of grabbing
+        if !Gizmo::in_use(&self.world) {
+            Grab::update(dt, &mut self.world, &state.input, &mut self.physics);
+        }
+        Selection::update(&mut self.world, &mut self.physics, state, assets);
         PlayerTarget::update(&mut self.world);
+        PlayerAvatar::update(&mut self.world);
 
         if state.input.action_activated(InputAction::Spawn) {
             let player_tr = self.world.query_one_mut::<&Transform>(self.player).unwrap();
@@ -109,39 +133,134 @@ impl Scene {
         }
 
         self.sync_physics();
+    }
+
+    // Once per rendered frame, after the simulation.
+    pub fn prepare_frame(
+        &mut self,
+        dt: f32,
+        state: &mut State,
+        assets: &mut Assets,
+        new_canvas_size: &Option<SurfaceSize>,
+    ) {
+        for e in state.input.new_raw_events() {
+            self.ui.handle_event(e, &state.window);
+        }
+        for e in state.input.ui_events() {
+            self.ui.handle_input_event(e, &state.window);
+        }
 
         if let Some(new_size) = new_canvas_size {
             self.resize(new_size, state, assets);
         }
 
-        self.world
-            .query_one_mut::<&mut Hud>(self.hud)
+        let controlled = self.world.get::<&Player>(self.player).unwrap().controlled();
+        self.ui.set_mouse_enabled(!controlled);
+
+        let prefab = self
+            .world
+            .get::<&mut Editor>(self.editor)
             .unwrap()
-            .build(dt, state, &mut self.ui);
+            .spawn
+            .take();
+        if let Some(prefab) = prefab {
+            let e = self.spawn_prefab(prefab, &state.renderer, assets);
+            self.world.get::<&mut Editor>(self.editor).unwrap().pick = Some(e);
+        }
+        Editor::update(&mut self.world, &mut self.physics, &state.renderer, assets);
+
+        let mut sample_count = state.renderer.sample_count();
+        {
+            let mut hud = self.world.get::<&mut Hud>(self.hud).unwrap();
+            let mut pp = self
+                .world
+                .get::<&mut PostProcessor>(self.postprocessor)
+                .unwrap();
+            let mut env = self
+                .world
+                .get::<&mut Environment>(self.environment)
+                .unwrap();
+            let mut player = self.world.get::<&mut Player>(self.player).unwrap();
+            let mut selection = self.world.get::<&mut Selection>(self.selection).unwrap();
+            let mut outliner = self.world.get::<&mut Outliner>(self.outliner).unwrap();
+            let mut picker = self.world.get::<&mut Picker>(self.picker).unwrap();
+            let mut gizmo = self.world.get::<&mut Gizmo>(self.gizmo).unwrap();
+            let mut editor = self.world.get::<&mut Editor>(self.editor).unwrap();
+            hud.build(
+                dt,
+                state,
+                &mut self.ui,
+                HudTargets {
+                    pp: &mut pp,
+                    env: &mut env,
+                    player_settings: player.settings_mut(),
+                    selection: &mut selection,
+                    outline: &mut outliner.settings,
+                    gpu_picking: &mut picker.enabled,
+                    gizmo: &mut gizmo.settings,
+                    editor: &mut editor,
+                    sample_count: &mut sample_count,
+                },
+            );
+        }
+        state
+            .input
+            .set_ui_capture(self.ui.wants_mouse(), self.ui.wants_keyboard());
+
+        if sample_count != state.renderer.sample_count() {
+            self.set_sample_count(sample_count, state, assets);
+        }
+
+        Environment::update(&mut self.world, &mut state.renderer);
+        Outliner::update(&mut self.world, &state.renderer, assets);
+
+        PostProcessor::update(&mut self.world, &state.renderer, assets);
     }
 
     pub fn render(&mut self, rr: &Renderer, assets: &Assets) {
-        self.render_with_camera(self.player, rr, assets);
-        self.render_with_camera(self.postprocessor, rr, assets);
+        let mut cameras = self
+            .world
+            .query::<&Camera>()
+            .iter()
+            .map(|(e, cam)| (e, cam.priority()))
+            .collect::<Vec<_>>();
+        cameras.sort_by_key(|&(_, priority)| priority);
+
+        // Cameras without own target share the post-processor input, only the first one clears it
+        let mut input_cleared = false;
+        for (camera, _) in cameras {
+            input_cleared |= self.render_with_camera(camera, rr, assets, input_cleared);
+        }
+
+        self.world
+            .get::<&PostProcessor>(self.postprocessor)
+            .unwrap()
+            .render(rr, assets);
+
+        // UI on top of everything
+        rr.render_pass(
+            &[],
+            RenderPassParams {
+                keep_color: true,
+                ..Default::default()
+            },
+            Some(&mut self.ui),
+        );
     }
 
     // TODO Continue adding other stuff until all scene initialization is done via the file.
     pub fn insert_from_cfg(&mut self, cfg: &SceneCfg, state: &State, assets: &mut Assets) {
-        for node in cfg.nodes.values() {
-            let pos = node
-                .pos
-                .map(|pos| Vec3::from_row_slice(&pos))
-                .unwrap_or(Vec3::zeros());
-            let scale = node
-                .scale
-                .map(|scale| Vec3::from_row_slice(&scale))
-                .unwrap_or(Vec3::from_element(1.0));
-            let e = self.world.spawn((
-                Transform::new(pos, scale),
-                RenderOrder(node.render_order),
-                RenderTags(node.render_tags),
-            ));
-
+        // Spawn and place all nodes first so that materials can show cameras from other nodes
+        let nodes = cfg
+            .nodes
+            .iter()
+            .map(|(name, node)| {
+                let (e, pos, scale) = self.spawn_node_from_cfg(name, node, &state.renderer);
+                (name.as_str(), node, e, pos, scale)
+            })
+            .collect::<Vec<_>>();
+
+        for &(_, node, e, pos, scale) in &nodes {
             if let Some(body_def) = &node.body {
                 let movable = body_def.movable.unwrap_or(true);
                 let body_type = if movable {
@@ -151,11 +270,12 @@ impl Scene {
                 };
                 // TODO Move this logic into the RigidBody cmp
                 let body = RigidBodyBuilder::new(body_type).translation(pos).build();
-                // TODO
-                let collider = ColliderBuilder::cuboid(scale.x, scale.y, scale.z)
-                    .restitution(0.2)
-                    .friction(0.7)
-                    .build();
+                let collider = match body_def.shape {
+                    ColliderShapeCfg::Cube => ColliderBuilder::cuboid(scale.x, scale.y, scale.z),
+                }
+                .restitution(0.2)
+                .friction(0.7)
+                .build();
                 let body = self.physics.add_body(body, Some(collider));
                 self.world
                     .insert(
@@ -184,6 +304,7 @@ impl Scene {
             }
 
             if let Some(mat) = &node.material {
+                let mut camera_texture = None;
                 // TODO Cache, don't re-create. Currently when several nodes use the same material,
                 // only one of them is rendered, must be smth with how the materials work.
                 let mat = cfg.materials.iter().find_map(|m| match m {
@@ -204,11 +325,69 @@ impl Scene {
                             None
                         }
                     }
-                    MaterialCfg::Textured { name, texture } => {
+                    MaterialCfg::Textured {
+                        name,
+                        texture,
+                        filter,
+                        address_mode,
+                        anisotropy,
+                        uv_scale,
+                    } => {
                         if *name == mat.name {
-                            let mat =
-                                materials::Material::textured(&state.renderer, assets, texture);
-                            Some(assets.add_material(mat))
+                            let uv_scale = uv_scale.map_or(Vec2::new(1.0, 1.0), Vec2::from);
+                            let mat = match texture {
+                                TextureSourceCfg::File(path) => {
+                                    let sampler = SamplerParams {
+                                        filter: match filter {
+                                            Some(TextureFilterCfg::Nearest) => {
+                                                wgpu::FilterMode::Nearest
+                                            }
+                                            Some(TextureFilterCfg::Linear) | None => {
+                                                wgpu::FilterMode::Linear
+                                            }
+                                        },
+                                        address_mode: match address_mode {
+                                            Some(TextureAddressModeCfg::Clamp) | None => {
+                                                wgpu::AddressMode::ClampToEdge
+                                            }
+                                            Some(TextureAddressModeCfg::Repeat) => {
+                                                wgpu::AddressMode::Repeat
+                                            }
+                                            Some(TextureAddressModeCfg::Mirror) => {
+                                                wgpu::AddressMode::MirrorRepeat
+                                            }
+                                        },
+                                        anisotropy: anisotropy.unwrap_or(1),
+                                    };
+                                    let mat = materials::Material::textured(
+                                        &state.renderer,
+                                        assets,
+                                        path,
+                                        sampler,
+                                        uv_scale,
+                                    );
+                                    assets.add_material(mat)
+                                }
+                                TextureSourceCfg::Camera { camera } => {
+                                    let cam_entity = nodes
+                                        .iter()
+                                        .find(|(name, ..)| name == camera)
+                                        .map(|&(_, _, e, ..)| e)
+                                        .expect("Unknown camera node");
+                                    let cam = self.world.get::<&Camera>(cam_entity).unwrap();
+                                    let target =
+                                        cam.target().as_ref().expect("Camera has no target");
+                                    let mat = materials::Material::textured_with_texture(
+                                        &state.renderer,
+                                        assets,
+                                        target.color_tex(),
+                                        uv_scale,
+                                    );
+                                    camera_texture = Some(cam_entity);
+                                    assets.add_material(mat)
+                                }
+                            };
+                            Some(mat)
                         } else {
                             None
                         }
@@ -220,6 +399,9 @@ impl Scene {
                 } else {
                     panic!("Unable to create material");
                 }
+                if let Some(camera) = camera_texture {
+                    self.world.insert(e, (CameraTexture(camera),)).unwrap();
+                }
             }
 
             for cmp in node.components.as_ref().unwrap_or(&Vec::new()) {
@@ -227,30 +409,250 @@ impl Scene {
                     ComponentCfg::PlayerTarget => {
                         self.world.insert(e, (PlayerTarget,)).unwrap();
                     }
+                    ComponentCfg::PlayerAvatar => {
+                        self.world.insert(e, (PlayerAvatar,)).unwrap();
+                    }
                 }
             }
         }
+
+        let skybox = cfg.environment.as_ref().and_then(|env| env.skybox.as_ref());
+        self.insert_sky_material(skybox, state, assets);
+        if let Some(env_cfg) = &cfg.environment {
+            self.apply_environment_cfg(env_cfg);
+        }
+
+        if let Some(cam_cfg) = &cfg.camera {
+            let size = state.renderer.surface_size();
+            configure_camera(
+                &mut self.world.get::<&mut Camera>(self.player).unwrap(),
+                cam_cfg,
+                (size.width, size.height),
+            );
+        }
+
+        if let Some(outline) = &cfg.outline {
+            self.world
+                .get::<&mut Outliner>(self.outliner)
+                .unwrap()
+                .settings = outline_settings_from_cfg(outline);
+        }
+
+        if let Some(effects) = &cfg.post_process {
+            let passes = effects.iter().map(post_process_pass_from_cfg).collect();
+            self.world
+                .get::<&mut PostProcessor>(self.postprocessor)
+                .unwrap()
+                .set_passes(passes);
+        }
+    }
+
+    // Returns the entity with its position and scale.
+    fn spawn_node_from_cfg(
+        &mut self,
+        name: &str,
+        node: &NodeCfg,
+        rr: &Renderer,
+    ) -> (Entity, Vec3, Vec3) {
+        let pos = node
+            .pos
+            .map(|pos| Vec3::from_row_slice(&pos))
+            .unwrap_or(Vec3::zeros());
+        let scale = node
+            .scale
+            .map(|scale| Vec3::from_row_slice(&scale))
+            .unwrap_or(Vec3::from_element(1.0));
+        let e = self.world.spawn((
+            Transform::new(pos, scale),
+            RenderOrder(node.render_order),
+            RenderTags(node.render_tags),
+            Name(name.to_string()),
+        ));
+
+        if let Some(look_at) = node.look_at {
+            self.world
+                .get::<&mut Transform>(e)
+                .unwrap()
+                .look_at(look_at.into());
+        }
+
+        if let Some(cam_cfg) = &node.camera {
+            let screen_size = (rr.surface_size().width, rr.surface_size().height);
+            let target_size = cam_cfg
+                .target_size
+                .map(|[width, height]| (width, height))
+                .or(cam_cfg
+                    .target_scale
+                    .map(|scale| scaled_size(screen_size, scale)));
+            let target = target_size.map(|size| {
+                RenderTarget::new(
+                    rr,
+                    RenderTargetParams {
+                        size: Some(size),
+                        format: Some(rr.hdr_texture_format()),
+                        sample_count: Some(rr.sample_count()),
+                    },
+                )
+            });
+            let mut cam = Camera::new(
+                1.0,
+                CameraParams::default(),
+                cam_cfg.render_tags.unwrap_or(RENDER_TAG_SCENE),
+                target,
+            );
+            // A fixed size wins over the scale
+            if cam_cfg.target_size.is_none() {
+                cam.set_target_scale(cam_cfg.target_scale);
+            }
+            configure_camera(&mut cam, cam_cfg, target_size.unwrap_or(screen_size));
+            self.world.insert(e, (cam,)).unwrap();
+        }
+
+        (e, pos, scale)
+    }
+
+    // The default skybox is only loaded if the sky has none yet and the config has no other.
+    fn insert_sky_material(
+        &mut self,
+        skybox: Option<&SkyboxCfg>,
+        state: &State,
+        assets: &mut Assets,
+    ) {
+        let sky = self
+            .world
+            .get::<&Environment>(self.environment)
+            .unwrap()
+            .sky();
+        let mat = match skybox {
+            Some(SkyboxCfg::File { path }) => {
+                materials::Material::skybox(&state.renderer, assets, path)
+            }
+            Some(SkyboxCfg::Faces { faces }) => {
+                materials::Material::skybox_from_faces(&state.renderer, assets, faces)
+            }
+            None if self.world.get::<&Material>(sky).is_err() => {
+                materials::Material::skybox(&state.renderer, assets, DEFAULT_SKYBOX)
+            }
+            None => return,
+        };
+        self.world
+            .insert_one(sky, Material(assets.add_material(mat)))
+            .unwrap();
+    }
+
+    fn apply_environment_cfg(&mut self, cfg: &EnvironmentCfg) {
+        let mut env = self
+            .world
+            .get::<&mut Environment>(self.environment)
+            .unwrap();
+
+        if let Some(sky) = &cfg.sky {
+            env.sky_mode = match sky {
+                SkyModeCfg::None => SkyMode::None,
+                SkyModeCfg::Skybox => SkyMode::Skybox,
+                SkyModeCfg::Gradient => SkyMode::Gradient,
+                SkyModeCfg::Atmosphere => SkyMode::Atmosphere,
+            };
+        }
+        if let Some(gradient) = &cfg.gradient {
+            env.gradient = SkyGradient {
+                zenith: gradient.zenith.into(),
+                horizon: gradient.horizon.into(),
+                ground: gradient.ground.into(),
+            };
+        }
+        if let Some(atmosphere) = &cfg.atmosphere {
+            let default = Atmosphere::default();
+            env.atmosphere = Atmosphere {
+                sun_elevation: atmosphere.sun_elevation.unwrap_or(default.sun_elevation),
+                sun_azimuth: atmosphere.sun_azimuth.unwrap_or(default.sun_azimuth),
+                sun_intensity: atmosphere.sun_intensity.unwrap_or(default.sun_intensity),
+                rayleigh: atmosphere.rayleigh.unwrap_or(default.rayleigh),
+                mie: atmosphere.mie.unwrap_or(default.mie),
+                mie_g: atmosphere.mie_g.unwrap_or(default.mie_g),
+            };
+        }
+        if let Some(clear_color) = cfg.clear_color {
+            env.clear_color = clear_color.into();
+        }
+        if let Some(fog) = &cfg.fog {
+            let default = Fog::default();
+            env.fog = Fog {
+                enabled: fog.enabled.unwrap_or(true),
+                mode: match fog.mode {
+                    Some(FogModeCfg::Linear) | None => FogMode::Linear,
+                    Some(FogModeCfg::Exponential) => FogMode::Exponential,
+                },
+                color: fog.color.map_or(default.color, Vec3::from),
+                start: fog.start.unwrap_or(default.start),
+                end: fog.end.unwrap_or(default.end),
+                density: fog.density.unwrap_or(default.density),
+                height_falloff: fog.height_falloff.unwrap_or(default.height_falloff),
+                base_height: fog.base_height.unwrap_or(default.base_height),
+            };
+        }
     }
 
-    // TODO Iterate over any camera, check its target and if it's configured to match the screen
-    // size then resize it.
     fn resize(&mut self, new_size: &SurfaceSize, state: &State, assets: &mut Assets) {
-        let mut player_cam = self.world.get::<&mut Camera>(self.player).unwrap();
-        player_cam.set_aspect(new_size.width as f32 / new_size.height as f32);
-        player_cam
-            .target_mut()
+        // Fixed-size targets keep their size and aspect
+        for (_, cam) in self.world.query_mut::<&mut Camera>() {
+            let size = match (cam.target_scale(), cam.target_mut()) {
+                (_, None) => (new_size.width, new_size.height),
+                (Some(scale), Some(target)) => {
+                    let size = scaled_size((new_size.width, new_size.height), scale);
+                    target.resize(size, &state.renderer);
+                    size
+                }
+                (None, Some(_)) => continue,
+            };
+            let vp = cam.viewport();
+            cam.set_aspect((vp.width * size.0 as f32) / (vp.height * size.1 as f32));
+        }
+        self.rebind_camera_textures(&state.renderer, assets);
+
+        self.world
+            .get::<&mut PostProcessor>(self.postprocessor)
             .unwrap()
             .resize((new_size.width, new_size.height), &state.renderer);
+    }
 
-        let mut mat_cmp = self.world.get::<&mut Material>(self.postprocessor).unwrap();
-        assets.remove_material(mat_cmp.0);
+    // Everything rendered by the scene cameras and to the screen follows the renderer's sample count.
+    fn set_sample_count(&mut self, sample_count: u32, state: &mut State, assets: &mut Assets) {
+        state.renderer.set_sample_count(sample_count);
+        let rr = &state.renderer;
 
-        let color_tex = player_cam.target().as_ref().unwrap().color_tex();
-        let new_mat = materials::Material::post_process(&state.renderer, assets, color_tex);
-        mat_cmp.0 = assets.add_material(new_mat);
+        for (_, cam) in self.world.query_mut::<&mut Camera>() {
+            if let Some(target) = cam.target_mut() {
+                target.set_sample_count(sample_count, rr);
+            }
+        }
+
+        for material in assets.materials_mut() {
+            material.rebuild_pipeline(rr);
+        }
+        self.rebind_camera_textures(rr, assets);
+
+        self.world
+            .get::<&mut PostProcessor>(self.postprocessor)
+            .unwrap()
+            .set_sample_count(sample_count, rr);
+
+        self.ui.rebuild_renderer(rr);
+    }
+
+    // Camera targets are re-created on resize and sample count changes.
+    fn rebind_camera_textures(&self, rr: &Renderer, assets: &mut Assets) {
+        let mut q = self.world.query::<(&Material, &CameraTexture)>();
+        for (_, (material, camera)) in q.iter() {
+            let cam = self.world.get::<&Camera>(camera.0).unwrap();
+            let target = cam.target().as_ref().unwrap();
+            assets
+                .material_mut(material.0)
+                .set_texture(rr, target.color_tex());
+        }
     }
 
-    fn spawn_box(&mut self, pos: Vec3, scale: Vec3, rr: &Renderer, assets: &mut Assets) {
+    fn spawn_box(&mut self, pos: Vec3, scale: Vec3, rr: &Renderer, assets: &mut Assets) -> Entity {
         let body = components::RigidBody::cuboid(
             components::RigidBodyParams {
                 pos,
@@ -259,7 +661,13 @@ impl Scene {
             },
             &mut self.physics,
         );
-        let mat = materials::Material::textured(rr, assets, "crate.png");
+        let mat = materials::Material::textured(
+            rr,
+            assets,
+            "crate.png",
+            SamplerParams::default(),
+            Vec2::new(1.0, 1.0),
+        );
         self.world.spawn((
             Transform::new(pos, scale),
             Mesh(self.box_mesh),
@@ -267,48 +675,195 @@ impl Scene {
             body,
             RenderOrder(0),
             RenderTags(RENDER_TAG_SCENE),
-        ));
+        ))
+    }
+
+    // In front of the player, like spawned boxes.
+    fn spawn_prefab(&mut self, prefab: Prefab, rr: &Renderer, assets: &mut Assets) -> Entity {
+        let player_tr = self.world.query_one_mut::<&Transform>(self.player).unwrap();
+        let pos = player_tr.position() + player_tr.forward().xyz() * 5.0;
+        let e = match prefab {
+            Prefab::Crate => self.spawn_box(pos, Vec3::from_element(1.0), rr, assets),
+            Prefab::Block => {
+                let scale = Vec3::new(2.0, 0.5, 2.0);
+                let body = components::RigidBody::cuboid(
+                    components::RigidBodyParams {
+                        pos,
+                        scale,
+                        movable: false,
+                    },
+                    &mut self.physics,
+                );
+                let mat = materials::Material::color(rr, assets, Vec3::from_element(0.6), false);
+                self.world.spawn((
+                    Transform::new(pos, scale),
+                    Mesh(self.box_mesh),
+                    Material(assets.add_material(mat)),
+                    body,
+                    RenderOrder(0),
+                    RenderTags(RENDER_TAG_SCENE),
+                ))
+            }
+            Prefab::Basis => {
+                let mat = materials::Material::color(rr, assets, Vec3::new(0.2, 0.4, 1.0), true);
+                self.world.spawn((
+                    Transform::from_pos(pos),
+                    Mesh(self.basis_mesh),
+                    Material(assets.add_material(mat)),
+                    RenderOrder(0),
+                    RenderTags(RENDER_TAG_SCENE),
+                ))
+            }
+        };
+        self.world
+            .insert_one(e, Name(prefab.name().to_string()))
+            .unwrap();
+        e
     }
 
-    fn render_with_camera(&mut self, camera: Entity, rr: &Renderer, assets: &Assets) {
-        if let Some((cam, cam_tr)) = self
+    // Returns whether the post-processor input has been rendered into.
+    fn render_with_camera(
+        &mut self,
+        camera: Entity,
+        rr: &Renderer,
+        assets: &Assets,
+        keep_input: bool,
+    ) -> bool {
+        let mut cam_query = self
             .world
             .query_one::<(&Camera, &Transform)>(camera)
-            .unwrap()
-            .get()
-        {
-            let mut items = self
-                .world
-                .query::<(&Mesh, &Material, &Transform, &RenderOrder, &RenderTags)>();
+            .unwrap();
+        let (cam, cam_tr) = cam_query.get().unwrap();
 
-            // Pick what should be rendered by the camera
-            let mut items = items
-                .iter()
-                .filter(|(_, (.., tag))| cam.should_render(tag.0))
-                .map(|(_, (mesh, material, transform, order, _))| {
-                    (mesh, material, transform, order)
-                })
-                .collect::<Vec<_>>();
+        let pp = self
+            .world
+            .get::<&PostProcessor>(self.postprocessor)
+            .unwrap();
+        let target = cam.target().as_ref().unwrap_or(pp.input());
+
+        let env = self.world.get::<&Environment>(self.environment).unwrap();
+
+        let mut items = self.world.query::<(
+            &Mesh,
+            &Material,
+            &Transform,
+            &RenderOrder,
+            &RenderTags,
+            Option<&CameraTexture>,
+        )>();
+
+        // Pick what should be rendered by the camera. Materials showing the camera's own target
+        // are skipped, it can't be sampled while being rendered into.
+        let mut items = items
+            .iter()
+            .filter(|(_, (.., tag, _))| cam.should_render(tag.0))
+            .filter(|(_, (.., shown))| shown.is_none_or(|shown| shown.0 != camera))
+            .map(|(_, (mesh, material, transform, order, ..))| (mesh, material, transform, order))
+            .collect::<Vec<_>>();
+
+        // Sort by render order
+        items.sort_by(|&(.., o1), &(.., o2)| o1.0.partial_cmp(&o2.0).unwrap());
+
+        let mut bundles = items
+            .into_iter()
+            .map(|(mesh, mat, tr, _)| {
+                let mat = assets.material(mat.0);
+                mat.update(rr, cam, cam_tr, tr, &env);
+                rr.build_render_bundle(assets.mesh(mesh.0), mat, Some(target))
+            })
+            // TODO Avoid vec allocation
+            .collect::<Vec<wgpu::RenderBundle>>();
+
+        // Selected objects get a slightly bigger wireframe over them
+        let mut selected = self
+            .world
+            .query::<(&Mesh, &Selected, &Transform, &RenderTags)>();
+        for (_, (mesh, selected, tr, tags)) in selected.iter() {
+            if !cam.should_render(tags.0) {
+                continue;
+            }
+            let mut tr = *tr;
+            tr.set_scale(tr.scale() * SELECTION_HIGHLIGHT_SCALE);
+            let mat = assets.material(selected.highlight);
+            mat.update(rr, cam, cam_tr, &tr, &env);
+            bundles.push(rr.build_render_bundle(assets.mesh(mesh.0), mat, Some(target)));
+        }
 
-            // Sort by render order
-            items.sort_by(|&(.., o1), &(.., o2)| o1.0.partial_cmp(&o2.0).unwrap());
+        // Outlines on top of everything. All outlined objects are marked first so that one's
+        // outline doesn't cover another.
+        let outliner = self.world.get::<&Outliner>(self.outliner).unwrap();
+        let (width, height) = target.size();
+        let viewport_size = Vec2::new(
+            cam.viewport().width * width as f32,
+            cam.viewport().height * height as f32,
+        );
+        let outlined = outliner
+            .outlined()
+            .iter()
+            .filter_map(|outlined| {
+                let mut q = self
+                    .world
+                    .query_one::<(&Mesh, &Transform, &RenderTags)>(outlined.entity)
+                    .ok()?;
+                let (mesh, tr, tags) = q.get()?;
+                cam.should_render(tags.0)
+                    .then_some((outlined, assets.mesh(mesh.0), *tr))
+            })
+            .collect::<Vec<_>>();
+        for (outlined, mesh, tr) in &outlined {
+            let mat = assets.material(outlined.mark);
+            mat.update(rr, cam, cam_tr, tr, &env);
+            mat.set_outline(rr, outlined.color, 0.0, viewport_size);
+            bundles.push(rr.build_render_bundle(mesh, mat, Some(target)));
+        }
+        for (outlined, mesh, tr) in &outlined {
+            let mat = assets.material(outlined.outline);
+            mat.update(rr, cam, cam_tr, tr, &env);
+            mat.set_outline(rr, outlined.color, outliner.settings.width, viewport_size);
+            bundles.push(rr.build_render_bundle(mesh, mat, Some(target)));
+        }
 
-            let bundles = items
-                .into_iter()
-                .map(|(mesh, mat, tr, _)| {
+        let uses_input = cam.target().is_none();
+        rr.render_pass(
+            &bundles,
+            RenderPassParams {
+                target: Some(target),
+                viewport: cam.viewport(),
+                keep_color: uses_input && keep_input,
+                ..Default::default()
+            },
+            None,
+        );
+
+        // Gizmos over everything in the player's view, with the depth cleared
+        if camera == self.player {
+            let mut handles = self
+                .world
+                .query::<(&GizmoHandle, &Mesh, &Material, &Transform, &RenderTags)>();
+            let bundles = handles
+                .iter()
+                .filter(|(_, (.., tags))| tags.0 == RENDER_TAG_GIZMO)
+                .map(|(_, (_, mesh, mat, tr, _))| {
                     let mat = assets.material(mat.0);
-                    mat.update(rr, cam, cam_tr, tr);
-                    rr.build_render_bundle(assets.mesh(mesh.0), mat, cam.target().as_ref())
+                    mat.update(rr, cam, cam_tr, tr, &env);
+                    rr.build_render_bundle(assets.mesh(mesh.0), mat, Some(target))
                 })
-                // TODO Avoid vec allocation
-                .collect::<Vec<wgpu::RenderBundle>>();
-
-            rr.render_pass(
-                &bundles,
-                cam.target().as_ref(),
-                cam.target().is_none().then_some(&mut self.ui),
-            );
+                .collect::<Vec<_>>();
+            if !bundles.is_empty() {
+                rr.render_pass(
+                    &bundles,
+                    RenderPassParams {
+                        target: Some(target),
+                        viewport: cam.viewport(),
+                        keep_color: true,
+                        ..Default::default()
+                    },
+                    None,
+                );
+            }
         }
+
+        uses_input
     }
 
     fn sync_physics(&mut self) {
@@ -321,3 +876,128 @@ impl Scene {
         }
     }
 }
+
+// `target_size` is the size of what the camera renders into, for the aspect.
+fn configure_camera(cam: &mut Camera, cfg: &CameraCfg, target_size: TextureSize) {
+    let default = CameraParams::default();
+    cam.set_params(CameraParams {
+        projection: match cfg.projection {
+            Some(ProjectionCfg::Perspective { fov }) => Projection::Perspective { fov },
+            Some(ProjectionCfg::Orthographic { height }) => Projection::Orthographic { height },
+            None => default.projection,
+        },
+        znear: cfg.znear.unwrap_or(default.znear),
+        zfar: cfg.zfar.unwrap_or(default.zfar),
+    });
+
+    if let Some(priority) = cfg.priority {
+        cam.set_priority(priority);
+    }
+    if let Some([x, y, width, height]) = cfg.viewport {
+        cam.set_viewport(Viewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    let vp = cam.viewport();
+    cam.set_aspect((vp.width * target_size.0 as f32) / (vp.height * target_size.1 as f32));
+}
+
+fn scaled_size(size: TextureSize, scale: f32) -> TextureSize {
+    (
+        ((size.0 as f32 * scale) as u32).max(1),
+        ((size.1 as f32 * scale) as u32).max(1),
+    )
+}
+
+fn outline_settings_from_cfg(cfg: &OutlineCfg) -> OutlineSettings {
+    let default = OutlineSettings::default();
+    OutlineSettings {
+        enabled: cfg.enabled.unwrap_or(default.enabled),
+        width: cfg.width.unwrap_or(default.width),
+        focus_color: cfg.focus_color.map_or(default.focus_color, Vec3::from),
+        grab_color: cfg.grab_color.map_or(default.grab_color, Vec3::from),
+    }
+}
+
+fn post_process_pass_from_cfg(cfg: &PostProcessEffectCfg) -> PostProcessPass {
+    let (effect, enabled) = match cfg {
+        PostProcessEffectCfg::Bloom {
+            enabled,
+            threshold,
+            knee,
+            intensity,
+            radius,
+        } => (
+            PostProcessEffect::Bloom {
+                threshold: threshold.unwrap_or(1.0),
+                knee: knee.unwrap_or(0.5),
+                intensity: intensity.unwrap_or(0.1),
+                radius: radius.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::Vignette {
+            enabled,
+            radius,
+            intensity,
+        } => (
+            PostProcessEffect::Vignette {
+                radius: radius.unwrap_or(1.0),
+                intensity: intensity.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::Tonemapping {
+            enabled,
+            operator,
+            exposure,
+        } => (
+            PostProcessEffect::Tonemapping {
+                operator: match operator {
+                    Some(TonemappingOperatorCfg::Reinhard) => TonemappingOperator::Reinhard,
+                    Some(TonemappingOperatorCfg::Aces) | None => TonemappingOperator::Aces,
+                },
+                exposure: exposure.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::Gamma { enabled, gamma } => (
+            PostProcessEffect::Gamma {
+                gamma: gamma.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::ColorGrading {
+            enabled,
+            lut,
+            intensity,
+        } => (
+            PostProcessEffect::ColorGrading {
+                lut: lut.clone(),
+                intensity: intensity.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::Fxaa { enabled, span } => (
+            PostProcessEffect::Fxaa {
+                span: span.unwrap_or(8.0),
+            },
+            enabled,
+        ),
+        PostProcessEffectCfg::ChromaticAberration { enabled, intensity } => (
+            PostProcessEffect::ChromaticAberration {
+                intensity: intensity.unwrap_or(0.01),
+            },
+            enabled,
+        ),
+    };
+
+    PostProcessPass {
+        effect,
+        enabled: enabled.unwrap_or(true),
+    }
+}
@@ -14,6 +14,7 @@ pub enum MeshPrefabCfg {
 #[derive(Deserialize, Debug)]
 pub enum ComponentCfg {
     PlayerTarget,
+    PlayerAvatar,
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,7 +26,175 @@ pub enum MaterialCfg {
     },
     Textured {
         name: String,
-        texture: String,
+        texture: TextureSourceCfg,
+        // Ignored for camera textures
+        filter: Option<TextureFilterCfg>,
+        address_mode: Option<TextureAddressModeCfg>,
+        anisotropy: Option<u16>,
+        uv_scale: Option<[f32; 2]>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TextureSourceCfg {
+    // Image file path
+    File(String),
+    // Live feed from the camera on the node with this name, it must have a target
+    Camera { camera: String },
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TextureFilterCfg {
+    Nearest,
+    Linear,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TextureAddressModeCfg {
+    Clamp,
+    Repeat,
+    Mirror,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum SkyboxCfg {
+    // .dds, .ktx2 or equirectangular .hdr
+    File { path: String },
+    // In +X, -X, +Y, -Y, +Z, -Z order
+    Faces { faces: [String; 6] },
+}
+
+#[derive(Deserialize, Debug)]
+pub enum SkyModeCfg {
+    None,
+    Skybox,
+    Gradient,
+    Atmosphere,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SkyGradientCfg {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub ground: [f32; 3],
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AtmosphereCfg {
+    // Degrees
+    pub sun_elevation: Option<f32>,
+    pub sun_azimuth: Option<f32>,
+    pub sun_intensity: Option<f32>,
+    pub rayleigh: Option<f32>,
+    pub mie: Option<f32>,
+    pub mie_g: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum FogModeCfg {
+    Linear,
+    Exponential,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FogCfg {
+    pub enabled: Option<bool>,
+    pub mode: Option<FogModeCfg>,
+    pub color: Option<[f32; 3]>,
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+    pub density: Option<f32>,
+    pub height_falloff: Option<f32>,
+    pub base_height: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EnvironmentCfg {
+    pub sky: Option<SkyModeCfg>,
+    pub skybox: Option<SkyboxCfg>,
+    pub gradient: Option<SkyGradientCfg>,
+    pub atmosphere: Option<AtmosphereCfg>,
+    pub clear_color: Option<[f32; 3]>,
+    pub fog: Option<FogCfg>,
+}
+
+// Around the focused and grabbed objects
+#[derive(Deserialize, Debug)]
+pub struct OutlineCfg {
+    pub enabled: Option<bool>,
+    // Pixels
+    pub width: Option<f32>,
+    pub focus_color: Option<[f32; 3]>,
+    pub grab_color: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum ProjectionCfg {
+    // Degrees
+    Perspective { fov: f32 },
+    Orthographic { height: f32 },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CameraCfg {
+    pub projection: Option<ProjectionCfg>,
+    pub znear: Option<f32>,
+    pub zfar: Option<f32>,
+    // Lower renders first
+    pub priority: Option<i32>,
+    // x, y, width, height, normalized, from the top left
+    pub viewport: Option<[f32; 4]>,
+    // Node cameras only. Renders into own texture of this size instead of the screen.
+    pub target_size: Option<[u32; 2]>,
+    // Node cameras only. Renders into own texture of the window size times this, following
+    // window resizes. Ignored if `target_size` is set.
+    pub target_scale: Option<f32>,
+    // Node cameras only, defaults to the scene tag
+    pub render_tags: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TonemappingOperatorCfg {
+    Reinhard,
+    Aces,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum PostProcessEffectCfg {
+    Bloom {
+        enabled: Option<bool>,
+        threshold: Option<f32>,
+        knee: Option<f32>,
+        intensity: Option<f32>,
+        radius: Option<f32>,
+    },
+    Vignette {
+        enabled: Option<bool>,
+        radius: Option<f32>,
+        intensity: Option<f32>,
+    },
+    Tonemapping {
+        enabled: Option<bool>,
+        operator: Option<TonemappingOperatorCfg>,
+        exposure: Option<f32>,
+    },
+    Gamma {
+        enabled: Option<bool>,
+        gamma: Option<f32>,
+    },
+    ColorGrading {
+        enabled: Option<bool>,
+        lut: String,
+        intensity: Option<f32>,
+    },
+    Fxaa {
+        enabled: Option<bool>,
+        span: Option<f32>,
+    },
+    ChromaticAberration {
+        enabled: Option<bool>,
+        intensity: Option<f32>,
     },
 }
 
@@ -52,16 +221,24 @@ pub struct NodeCfg {
     pub render_tags: u32,
     pub pos: Option<[f32; 3]>,
     pub scale: Option<[f32; 3]>,
+    pub look_at: Option<[f32; 3]>,
     pub body: Option<BodyCfg>,
     pub mesh: Option<MeshCfg>,
     pub material: Option<NodeMaterialCfg>,
     pub components: Option<Vec<ComponentCfg>>,
+    pub camera: Option<CameraCfg>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct SceneCfg {
     pub materials: Vec<MaterialCfg>,
     pub nodes: HashMap<String, NodeCfg>,
+    pub environment: Option<EnvironmentCfg>,
+    // Player camera
+    pub camera: Option<CameraCfg>,
+    // Effects in the order of application
+    pub post_process: Option<Vec<PostProcessEffectCfg>>,
+    pub outline: Option<OutlineCfg>,
 }
 
 impl SceneCfg {
@@ -70,6 +247,7 @@ impl SceneCfg {
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 